@@ -0,0 +1,172 @@
+//!
+//! The append-only audit log of an ENV, one `OpEvent` per line,
+//! it is located in the ENV home, and committed by the `git` subcommand.
+//!
+
+use crate::{common::parse_duration, hooks::OpEvent};
+use chaindev::NodeID;
+use ruc::*;
+use serde::{Deserialize, Serialize};
+use std::{fs, io::Write, path::Path};
+
+pub const AUDIT_LOG_NAME: &str = "AUDIT.jsonl";
+
+/// Do nothing if the ENV home does not exist, e.g., after `destroy`
+pub fn append(env_home: &str, ev: &OpEvent) -> Result<()> {
+    if !Path::new(env_home).is_dir() {
+        return Ok(());
+    }
+    let mut line = serde_json::to_string(ev).c(d!())?;
+    line.push('\n');
+    fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(format!("{env_home}/{AUDIT_LOG_NAME}"))
+        .c(d!())
+        .and_then(|mut f| f.write_all(line.as_bytes()).c(d!()))
+}
+
+/// Broken lines(e.g., an interrupted write) are skipped with a warning
+pub fn load(env_home: &str) -> Result<Vec<OpEvent>> {
+    let path = format!("{env_home}/{AUDIT_LOG_NAME}");
+    if !Path::new(&path).exists() {
+        return Ok(vec![]);
+    }
+    let log = fs::read_to_string(&path).c(d!())?;
+    let evs = log
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+        .filter_map(|(idx, l)| {
+            serde_json::from_str::<OpEvent>(l)
+                .map_err(|e| eprintln!("{path}, line {}: {e}", 1 + idx))
+                .ok()
+        })
+        .collect();
+    Ok(evs)
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct HistoryFilter {
+    /// Matches if the operator contains it
+    pub operator: Option<String>,
+    pub operation: Option<String>,
+    pub node: Option<NodeID>,
+    pub failed_only: bool,
+    /// A unix timestamp
+    pub since: Option<u64>,
+    /// Keep the last N events after the other filters
+    pub last: Option<usize>,
+}
+
+impl HistoryFilter {
+    pub fn apply(&self, evs: Vec<OpEvent>) -> Vec<OpEvent> {
+        let mut evs = evs
+            .into_iter()
+            .filter(|ev| {
+                self.operator
+                    .as_deref()
+                    .map(|op| ev.operator.contains(op))
+                    .unwrap_or(true)
+                    && self
+                        .operation
+                        .as_deref()
+                        .map(|op| op == ev.operation)
+                        .unwrap_or(true)
+                    && self
+                        .node
+                        .map(|id| ev.nodes.as_ref().is_some_and(|ns| ns.contains(&id)))
+                        .unwrap_or(true)
+                    && !(self.failed_only && ev.ok)
+                    && self.since.map(|ts| ev.timestamp >= ts).unwrap_or(true)
+            })
+            .collect::<Vec<_>>();
+        if let Some(n) = self.last {
+            evs.drain(..evs.len().saturating_sub(n));
+        }
+        evs
+    }
+}
+
+/// For the `--since` option:
+/// a relative time like '30m', '2h' and '7d', or a unix timestamp
+pub fn parse_since(s: &str) -> std::result::Result<u64, String> {
+    if let Ok(ts) = s.trim().parse::<u64>() {
+        return Ok(ts);
+    }
+    parse_duration(s).map(|secs| ts!().saturating_sub(secs))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn filter_history() {
+        let ev =
+            |operator: &str, operation: &str, nodes: &[NodeID], ts: u64, ok: bool| {
+                let mut ev = OpEvent::new(
+                    &"MyEnv".into(),
+                    operation,
+                    Some(nodes.iter().copied().collect()),
+                    &alt!(ok, Ok(()), Err(eg!())),
+                );
+                ev.operator = operator.to_owned();
+                ev.timestamp = ts;
+                ev
+            };
+        let evs = vec![
+            ev("alice@a", "start", &[1, 2], 100, true),
+            ev("bob@b", "stop", &[2], 200, false),
+            ev("alice@a", "stop", &[3], 300, true),
+        ];
+        let ops = |f: HistoryFilter| {
+            f.apply(evs.clone())
+                .into_iter()
+                .map(|ev| ev.timestamp)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(vec![100, 200, 300], ops(HistoryFilter::default()));
+        assert_eq!(
+            vec![100, 300],
+            ops(HistoryFilter {
+                operator: Some("alice".to_owned()),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            vec![100, 200],
+            ops(HistoryFilter {
+                node: Some(2),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            vec![200],
+            ops(HistoryFilter {
+                failed_only: true,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            vec![300],
+            ops(HistoryFilter {
+                operation: Some("stop".to_owned()),
+                since: Some(250),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            vec![200, 300],
+            ops(HistoryFilter {
+                last: Some(2),
+                ..Default::default()
+            })
+        );
+
+        assert_eq!(Ok(123), parse_since("123"));
+        assert!(parse_since("2h").unwrap() <= ts!() - 7200);
+        assert!(parse_since("2w").is_err());
+    }
+}
@@ -0,0 +1,177 @@
+//!
+//! Dedicated bootnodes, lightweight nodes that only serve the peer discovery:
+//! - the EL part is the `bootnode` tool of geth(discv4 and discv5)
+//! - the CL part is `lighthouse boot_node`(discv5)
+//!
+//! The keys of a bootnode are derived from the seed in its node meta,
+//! so its enode and ENR are stable, and other nodes of the ENV use them
+//! as their bootnodes instead of querying the online nodes.
+//!
+
+use crate::{
+    common::{CL_BN_DIR, CL_BN_ERR_NAME, EL_ERR_NAME},
+    p2p,
+    process::{self, ProcessBackend},
+};
+use chaindev::NodeID;
+use ruc::*;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_EL_BOOTNODE_BIN: &str = "bootnode";
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bootnode {
+    /// The p2p keys of the node are derived from it
+    pub seed: String,
+    /// The `bootnode` tool of geth, a name in $PATH or a path
+    pub el_bin: String,
+    /// Recorded after the node is pushed, for reference
+    #[serde(default)]
+    pub enode: String,
+    /// Recorded after the node is pushed, for reference
+    #[serde(default)]
+    pub enr: String,
+}
+
+impl Bootnode {
+    pub fn new(el_bin: Option<String>) -> Self {
+        Self {
+            seed: ruc::algo::rand::rand_jwt(),
+            el_bin: el_bin.unwrap_or_else(|| DEFAULT_EL_BOOTNODE_BIN.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    /// The same role with new keys, e.g. for a clone
+    pub fn renew(&self) -> Self {
+        Self::new(Some(self.el_bin.clone()))
+    }
+
+    /// `(enode, ENR)` of the bootnode
+    pub fn peer(
+        &self,
+        id: NodeID,
+        ip: &str,
+        el_port: u16,
+        cl_port: u16,
+    ) -> Result<(String, String)> {
+        let enode = p2p::enode(&p2p::derive_key(&self.seed, id, "el"), ip, el_port);
+        p2p::enr(&p2p::derive_key(&self.seed, id, "cl"), ip, cl_port)
+            .c(d!())
+            .map(|enr| (enode, enr))
+    }
+
+    /// Start the two processes of the bootnode,
+    /// they take the places of 'el' and 'cl-bn' of a regular node
+    #[allow(clippy::too_many_arguments)]
+    pub fn cmd_for_start(
+        &self,
+        id: NodeID,
+        home: &str,
+        local_ip: &str,
+        ext_ip: &str,
+        el_port: u16,
+        cl_port: u16,
+        launch: impl Fn(&str, &str, &str) -> String,
+    ) -> String {
+        let el_bin = if self.el_bin.contains('/') {
+            self.el_bin.clone()
+        } else {
+            format!("$(which {})", self.el_bin)
+        };
+        let el_key = p2p::key_hex(&p2p::derive_key(&self.seed, id, "el"));
+        let cl_key = p2p::key_printf(&p2p::derive_key(&self.seed, id, "cl"));
+        let network_dir = format!("{home}/{CL_BN_DIR}/network");
+
+        let el_cmd = format!(
+            r#"
+{home}/bootnode_bin \
+    -nodekeyhex={el_key} \
+    -addr={local_ip}:{el_port} \
+    -nat=extip:{ext_ip} \
+    -v5 "#
+        );
+
+        let cl_cmd = format!(
+            r#"
+{home}/lighthouse_bin boot_node \
+    --testnet-dir={home}/genesis \
+    --network-dir={network_dir} \
+    --listen-address={local_ip} \
+    --port={cl_port} \
+    --enr-address={ext_ip} \
+    --disable-packet-filter "#
+        );
+
+        format!(
+            r#"
+cp -f {el_bin} {home}/bootnode_bin || exit 1
+mkdir -p {network_dir} || exit 1
+printf '{cl_key}' >{network_dir}/key || exit 1
+{}
+{}"#,
+            launch("el", EL_ERR_NAME, &el_cmd),
+            launch("cl-bn", CL_BN_ERR_NAME, &cl_cmd),
+        )
+    }
+}
+
+/// Count the processes of a bootnode, in the way of `cmd_cnt_running` of chaindev,
+/// its two processes are reported as the three of a regular node
+pub fn cmd_cnt_running(home: &str, backend: &ProcessBackend) -> String {
+    let cmd = if let ProcessBackend::Systemd(_) = backend {
+        process::cmd_cnt_running(home)
+    } else {
+        format!(
+            "ps ax -o pid=,args= | {} | wc -l",
+            process::cmd_grep_bins(home, &["bootnode", "lighthouse"])
+        )
+    };
+    cnt_as_regular(&cmd)
+}
+
+fn cnt_as_regular(cmd: &str) -> String {
+    format!("n=$({cmd}); if [ 2 -eq $n ]; then echo 3; else echo $n; fi")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bootnode() {
+        let b = Bootnode::new(None);
+        assert_eq!(DEFAULT_EL_BOOTNODE_BIN, b.el_bin);
+        assert_eq!(
+            pnk!(b.peer(1, "10.0.0.1", 30303, 9000)),
+            pnk!(b.peer(1, "10.0.0.1", 30303, 9000))
+        );
+        assert_ne!(
+            pnk!(b.peer(1, "10.0.0.1", 30303, 9000)),
+            pnk!(b.renew().peer(1, "10.0.0.1", 30303, 9000))
+        );
+
+        let cmd = b.cmd_for_start(
+            1,
+            "/tmp/n1",
+            "127.0.0.1",
+            "127.0.0.1",
+            1,
+            2,
+            |c, _, cmd| format!("{c}: {}", cmd.trim()),
+        );
+        assert!(cmd.contains("el: /tmp/n1/bootnode_bin"));
+        assert!(cmd.contains("cl-bn: /tmp/n1/lighthouse_bin boot_node"));
+
+        let home = "/tmp/__CHAIN_DEV__/a.b/host+root/1";
+        let cnt = cmd_cnt_running(home, &ProcessBackend::Nohup);
+        assert!(cnt.contains(
+            r"/tmp/__CHAIN_DEV__/a\.b/host\+root/1/(bootnode|lighthouse)_bin "
+        ));
+
+        for (n, expected) in [(0, "0"), (1, "1"), (2, "3")] {
+            let out = pnk!(cmd::exec_output(&cnt_as_regular(&format!("echo {n}"))));
+            assert_eq!(expected, out.trim());
+        }
+    }
+}
@@ -0,0 +1,271 @@
+//!
+//! `exp ddev export/import`, a portable bundle of an ENV,
+//! for handing a network over to others.
+//!
+//! Layout of a bundle, a tar package compressed according to
+//! the suffix of its path, e.g. `.tar.zst` or `.tar.gz`:
+//! - `BUNDLE`, the manifest
+//! - `CONFIG`, the ENV meta
+//! - `genesis.tar.gz`, the network cfg files
+//! - `vcdata.tar.gz`, the initial validator keys
+//!
+
+use chaindev::common::hosts::{Host, HostAddr, HostID, Hosts};
+use ruc::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    str::FromStr,
+};
+
+/// The version of the bundles created by this `exp`
+pub const BUNDLE_FORMAT_VERSION: u32 = 1;
+
+pub const MANIFEST_NAME: &str = "BUNDLE";
+pub const CONFIG_NAME: &str = "CONFIG";
+pub const GENESIS_NAME: &str = "genesis.tar.gz";
+pub const VKEYS_NAME: &str = "vcdata.tar.gz";
+
+/// The client binaries that are copied into each node home
+pub const NODE_BINARIES: [&str; 3] = ["geth_bin", "reth_bin", "lighthouse_bin"];
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format_version: u32,
+    /// The name of the exported ENV
+    pub env_name: String,
+    pub exported_at: u64,
+    /// The host addresses and the ssh settings are replaced with placeholders
+    pub redacted: bool,
+    /// The host inventory, the same as the `remote_hosts` of the ENV meta
+    pub hosts: Hosts,
+    /// <binary name, the sha256 checksums found on the nodes>,
+    /// more than one checksum means the nodes are running different builds
+    pub binaries: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl Manifest {
+    pub fn load(dir: &str) -> Result<Self> {
+        let p = format!("{dir}/{MANIFEST_NAME}");
+        let m = fs::read(&p)
+            .c(d!("Not a bundle, {} is missing", MANIFEST_NAME))
+            .and_then(|m| serde_json::from_slice::<Self>(&m).c(d!(p)))?;
+        if m.format_version > BUNDLE_FORMAT_VERSION {
+            return Err(eg!(
+                "The bundle is of format v{}, but this `exp` only supports v{} and below, please upgrade `exp`",
+                m.format_version,
+                BUNDLE_FORMAT_VERSION
+            ));
+        }
+        Ok(m)
+    }
+
+    pub fn save(&self, dir: &str) -> Result<()> {
+        let p = format!("{dir}/{MANIFEST_NAME}");
+        serde_json::to_vec_pretty(self)
+            .c(d!())
+            .and_then(|m| fs::write(&p, m).c(d!(p)))
+    }
+}
+
+/// Pack the files in `dir` as a bundle
+pub fn pack(dir: &str, path: &str) -> Result<()> {
+    let cmd = format!(
+        "tar -C {dir} -caf {path} {MANIFEST_NAME} {CONFIG_NAME} {GENESIS_NAME} {VKEYS_NAME}"
+    );
+    cmd::exec_output(&cmd).c(d!(cmd)).map(|_| ())
+}
+
+/// Unpack a bundle into `dir`
+pub fn unpack(path: &str, dir: &str) -> Result<Manifest> {
+    fs::create_dir_all(dir).c(d!())?;
+    let cmd = format!("tar -C {dir} -xf {path}");
+    cmd::exec_output(&cmd).c(d!(cmd))?;
+    Manifest::load(dir).c(d!())
+}
+
+/// The placeholder address of the `idx`th host of a redacted bundle
+pub fn redacted_addr(idx: usize) -> HostAddr {
+    pnk!(HostAddr::from_str(&format!("host-{:03}", 1 + idx)))
+}
+
+/// Map the hosts of the bundle to the local ones, `<bundle host ID, local host>`:
+/// - the hosts of a plain bundle must all be found in the local configuration
+/// - the hosts of a redacted bundle are mapped in the order of their IDs
+pub fn map_hosts(bundle: &Manifest, local: &Hosts) -> Result<BTreeMap<HostID, Host>> {
+    let bundle_hosts = bundle.hosts.as_ref();
+    let local_hosts = local.as_ref();
+
+    if bundle.redacted {
+        if local_hosts.len() < bundle_hosts.len() {
+            return Err(eg!(
+                "The bundle needs {} hosts, but only {} are configured locally",
+                bundle_hosts.len(),
+                local_hosts.len()
+            ));
+        }
+        return Ok(bundle_hosts
+            .keys()
+            .cloned()
+            .zip(local_hosts.values().cloned())
+            .collect());
+    }
+
+    let missing = bundle_hosts
+        .keys()
+        .filter(|id| !local_hosts.contains_key(*id))
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        return Err(eg!(
+            "Hosts of the bundle are not found in the local configuration: {:?}",
+            missing
+        ));
+    }
+    Ok(bundle_hosts
+        .keys()
+        .map(|id| (id.clone(), local_hosts[id].clone()))
+        .collect())
+}
+
+/// The sha256 checksums of the binaries in a node home, missing ones are skipped
+pub fn cmd_checksums(node_home: &str) -> String {
+    format!(
+        "cd {node_home} || exit 1; sha256sum {} 2>/dev/null; exit 0",
+        NODE_BINARIES.join(" ")
+    )
+}
+
+/// Parse the output of `cmd_checksums`, `<binary name, checksum>`
+pub fn parse_checksums(output: &str) -> BTreeMap<String, String> {
+    output
+        .lines()
+        .filter_map(|l| {
+            let mut fields = l.split_whitespace();
+            let sum = fields.next()?;
+            let name = fields.next()?.trim_start_matches('*');
+            Some((name.to_owned(), sum.to_owned()))
+        })
+        .filter(|(name, _)| NODE_BINARIES.contains(&name.as_str()))
+        .collect()
+}
+
+/// Differences between the binaries of the bundle and the imported nodes
+pub fn diff_binaries(
+    bundle: &BTreeMap<String, BTreeSet<String>>,
+    imported: &BTreeMap<String, BTreeSet<String>>,
+) -> Vec<String> {
+    bundle
+        .keys()
+        .chain(imported.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter(|name| bundle.get(*name) != imported.get(*name))
+        .map(|name| {
+            let sums = |m: &BTreeMap<String, BTreeSet<String>>| {
+                m.get(name)
+                    .map(|s| s.iter().cloned().collect::<Vec<_>>().join(","))
+                    .unwrap_or_else(|| "-".to_owned())
+            };
+            format!(
+                "{name}: {} in the bundle, {} here",
+                sums(bundle),
+                sums(imported)
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn hosts(ids: &[&str]) -> Hosts {
+        let map = ids
+            .iter()
+            .map(|id| (id.to_string(), json!({ "local_ip": id, "weight": 1 })))
+            .collect::<serde_json::Map<_, _>>();
+        pnk!(serde_json::from_value(map.into()))
+    }
+
+    fn manifest(hosts: Hosts, redacted: bool) -> Manifest {
+        Manifest {
+            format_version: BUNDLE_FORMAT_VERSION,
+            env_name: "MyEnv".to_owned(),
+            exported_at: ts!(),
+            redacted,
+            hosts,
+            binaries: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn host_mapping() {
+        let local = hosts(&["10.0.0.1", "10.0.0.2", "10.0.0.3"]);
+
+        let m = manifest(hosts(&["10.0.0.3", "10.0.0.1"]), false);
+        let map = pnk!(map_hosts(&m, &local));
+        assert_eq!(2, map.len());
+        assert_eq!("10.0.0.3", map["10.0.0.3"].meta.addr.local_ip);
+
+        let m = manifest(hosts(&["10.0.0.1", "10.0.0.9"]), false);
+        let e = map_hosts(&m, &local).unwrap_err();
+        assert!(e.get_lowest_msg().to_string().contains("10.0.0.9"));
+
+        let ids = [redacted_addr(0), redacted_addr(1)].map(|a| a.host_id());
+        let m = manifest(hosts(&ids.each_ref().map(|s| s.as_str())), true);
+        let map = pnk!(map_hosts(&m, &local));
+        assert_eq!("10.0.0.1", map[&ids[0]].meta.addr.local_ip);
+        assert_eq!("10.0.0.2", map[&ids[1]].meta.addr.local_ip);
+
+        let m = manifest(hosts(&["a", "b", "c", "d"]), true);
+        assert!(map_hosts(&m, &local).is_err());
+    }
+
+    #[test]
+    fn checksums() {
+        let out = "aa11  geth_bin\nbb22 *reth_bin\nsha256sum: lighthouse_bin: No such file or directory\n";
+        let sums = parse_checksums(out);
+        assert_eq!(2, sums.len());
+        assert_eq!("bb22", sums["reth_bin"]);
+
+        let bundle = BTreeMap::from([
+            ("geth_bin".to_owned(), BTreeSet::from(["aa11".to_owned()])),
+            ("reth_bin".to_owned(), BTreeSet::from(["bb22".to_owned()])),
+        ]);
+        assert!(diff_binaries(&bundle, &bundle).is_empty());
+
+        let mut imported = bundle.clone();
+        imported.insert("reth_bin".to_owned(), BTreeSet::from(["cc33".to_owned()]));
+        let diff = diff_binaries(&bundle, &imported);
+        assert_eq!(
+            vec!["reth_bin: bb22 in the bundle, cc33 here".to_owned()],
+            diff
+        );
+    }
+
+    #[test]
+    fn pack_and_unpack() {
+        let base = format!("/tmp/exp_bundle_test_{}", std::process::id());
+        let src = format!("{base}/src");
+        let dst = format!("{base}/dst");
+        pnk!(fs::create_dir_all(&src));
+
+        pnk!(manifest(hosts(&["10.0.0.1"]), false).save(&src));
+        for f in [CONFIG_NAME, GENESIS_NAME, VKEYS_NAME] {
+            pnk!(fs::write(format!("{src}/{f}"), f));
+        }
+
+        let path = format!("{base}/MyEnv.tar.gz");
+        pnk!(pack(&src, &path));
+        let m = pnk!(unpack(&path, &dst));
+        assert_eq!("MyEnv", m.env_name);
+        assert_eq!(
+            GENESIS_NAME,
+            pnk!(fs::read_to_string(format!("{dst}/{GENESIS_NAME}")))
+        );
+
+        pnk!(fs::remove_dir_all(&base));
+    }
+}
@@ -1,4 +1,10 @@
-use chaindev::common::hosts::HostExpression;
+use crate::{
+    audit::parse_since,
+    chaos::{parse_max_down, parse_percent, parse_rate, parse_time, MaxDown},
+    common::parse_duration,
+    output::OutputFormat,
+};
+use chaindev::{common::hosts::HostExpression, NodeID};
 use clap::{Args, Parser, Subcommand};
 
 #[derive(Parser, Debug)]
@@ -17,6 +23,8 @@ pub enum Commands {
         about = "Manage development clusters on various distributed hosts"
     )]
     DDev(DDevCfg),
+    #[clap(about = "Manage the ENVs of both `dev` and `ddev`")]
+    Env(EnvCfg),
     #[clap(about = "Manage deposit operations")]
     Deposit(DepositCfg),
     #[clap(about = "Exit an existing validator from the beacon chain")]
@@ -35,11 +43,54 @@ pub enum Commands {
     GenBashCompletions,
 }
 
+#[derive(Debug, Args)]
+pub struct EnvCfg {
+    #[clap(subcommand)]
+    pub op: EnvOp,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum EnvOp {
+    #[clap(about = "Migrate the meta of all ENVs to the schema of this `exp`")]
+    Migrate {
+        #[clap(
+            long,
+            help = "Only report the outdated ENVs, exit with an error if any"
+        )]
+        check: bool,
+    },
+}
+
 #[derive(Debug, Args)]
 pub struct DevCfg {
     #[clap(short = 'e', long)]
     pub env_name: Option<String>,
 
+    #[clap(
+        long,
+        global = true,
+        help = "Print the scripts and file transfers of the operation instead of executing them,
+supported by `start`, `restart`, `push-nodes`, `deposit` and `switch-EL-to-*`"
+    )]
+    pub dry_run: bool,
+
+    #[clap(
+        long,
+        global = true,
+        help = "Wait for the lock of the ENV if it is held by another `exp` process,
+instead of failing at once"
+    )]
+    pub wait: bool,
+
+    #[clap(
+        long,
+        global = true,
+        value_enum,
+        help = "The output format of `list`, `list-rpcs`, `debug-failed-nodes`, `show`, `health` and `topology`,
+the legacy output of each command is kept if not set"
+    )]
+    pub output: Option<OutputFormat>,
+
     #[clap(subcommand)]
     pub op: Option<DevOp>,
 }
@@ -55,8 +106,7 @@ pub enum DevOp {
         #[clap(
             short = 'N',
             long,
-            help = "Comma separated NodeID[s], '3', '3,2,4-9', etc.
-if set to 'all', then deposit on all non-fuhrer nodes"
+            help = include_str!("nodes.format")
         )]
         nodes: String,
         #[clap(
@@ -99,8 +149,7 @@ or wait until the deposit transaction is confirmed on chain"
         #[clap(
             short = 'N',
             long,
-            help = "Comma separated NodeID[s], '3', '3,2,4-9', etc.
-if set to 'all', then exit all validators of all non-fuhrer nodes"
+            help = include_str!("nodes.format")
         )]
         nodes: String,
         #[clap(
@@ -135,7 +184,7 @@ or wait until the exit request is confirmed on chain"
         #[clap(
             short = 'N',
             long,
-            help = "Comma separated NodeID[s], '3', '3,2,4-9,1', etc."
+            help = include_str!("nodes.format")
         )]
         nodes: Option<String>,
         #[clap(long, help = "Filter nodes with the geth el")]
@@ -152,8 +201,7 @@ or wait until the exit request is confirmed on chain"
         #[clap(
             short = 'N',
             long,
-            help = "Comma separated NodeID[s], '3', '3,4-9,2,1', etc.
-if set to 'all', then apply the operation to all nodes"
+            help = include_str!("nodes.format")
         )]
         nodes: String,
         #[clap(long, help = "Filter nodes with the geth el")]
@@ -168,7 +216,7 @@ if set to 'all', then apply the operation to all nodes"
         #[clap(
             short = 'N',
             long,
-            help = "Comma separated NodeID[s], '3', '3,2,4-9,1', etc."
+            help = include_str!("nodes.format")
         )]
         nodes: Option<String>,
         #[clap(long, help = "Filter nodes with the geth el")]
@@ -198,6 +246,19 @@ NOTE: the fullnode mode of `reth` is unstable, do NOT use it"
         reth: bool,
         #[clap(conflicts_with = "reth", long, help = "To get FullNode[s], set true")]
         fullnode: bool,
+        #[clap(
+            conflicts_with_all = ["reth", "fullnode"],
+            long,
+            help = "To get dedicated bootnode[s], set true;
+other nodes of the ENV will use them as their bootnodes"
+        )]
+        bootnode: bool,
+        #[clap(
+            requires = "bootnode",
+            long,
+            help = "The `bootnode` tool of geth, a name in $PATH or a path [default: bootnode]"
+        )]
+        el_bootnode_bin: Option<String>,
         #[clap(
             short = 'n',
             long,
@@ -214,7 +275,7 @@ NOTE: the fullnode mode of `reth` is unstable, do NOT use it"
             short = 'N',
             long,
             conflicts_with = "num",
-            help = "Comma separated NodeID[s], '3', '3,2,1,4-9', etc."
+            help = include_str!("nodes.format")
         )]
         nodes: Option<String>,
         #[clap(
@@ -281,6 +342,49 @@ NOTE: the node will be left stopped, a `start` operation may be needed"
         #[clap(short = 'e', long)]
         env_name: Option<String>,
     },
+    #[clap(
+        about = "Show the process status, EL block number and CL sync status of each node"
+    )]
+    Health {
+        #[clap(short = 'e', long)]
+        env_name: Option<String>,
+    },
+    #[clap(
+        about = "Show the peer graphs of the EL and the CL, with the isolated nodes and the partitions"
+    )]
+    Topology {
+        #[clap(short = 'e', long)]
+        env_name: Option<String>,
+        #[clap(
+            long,
+            help = "Also export the graphs in the Graphviz DOT format to this path"
+        )]
+        dot: Option<String>,
+    },
+    #[clap(about = "Keep running, and restart the crashed nodes that should be online")]
+    Supervise {
+        #[clap(short = 'e', long)]
+        env_name: Option<String>,
+        #[clap(
+            short = 'i',
+            long,
+            default_value_t = 30,
+            help = "Seconds between two rounds of checks,
+also the initial delay of the restart backoff"
+        )]
+        interval_secs: u64,
+        #[clap(
+            long,
+            default_value_t = 600,
+            help = "The max delay between two restarts of the same node"
+        )]
+        max_backoff_secs: u64,
+        #[clap(
+            long,
+            help = "An URL, each restart will be POSTed to it as a JSON document"
+        )]
+        webhook: Option<String>,
+    },
     #[clap(about = "List various RPC endpoints of the ENV")]
     ListRpcs {
         #[clap(short = 'e', long)]
@@ -322,6 +426,74 @@ NOTE: the node will be left stopped, a `start` operation may be needed"
     },
     #[clap(about = "Show names of all existing ENVs")]
     List,
+    #[clap(about = "Manage labels and notes of nodes")]
+    Label {
+        #[clap(subcommand)]
+        op: LabelOp,
+    },
+    #[clap(about = "Show the audit log of the mutating operations on the ENV")]
+    History {
+        #[clap(short = 'e', long)]
+        env_name: Option<String>,
+        #[clap(long, help = "Only the operators that contain this string")]
+        operator: Option<String>,
+        #[clap(long, help = "Only this operation, e.g., 'kick_nodes'")]
+        operation: Option<String>,
+        #[clap(short = 'N', long, help = "Only the operations on this node")]
+        node: Option<NodeID>,
+        #[clap(long, help = "Only the failed operations")]
+        failed: bool,
+        #[clap(
+            long,
+            value_parser = parse_since,
+            help = "A relative time like '30m', '2h' and '7d', or a unix timestamp"
+        )]
+        since: Option<u64>,
+        #[clap(short = 'n', long, help = "Only the last N operations")]
+        last: Option<usize>,
+    },
+    #[clap(about = "Manage the notification hooks of the ENV")]
+    Hook {
+        #[clap(subcommand)]
+        op: HookOp,
+    },
+    #[clap(
+        about = "Manage the full snapshots of the node data and the meta of the ENV"
+    )]
+    Snapshot {
+        #[clap(subcommand)]
+        op: SnapshotOp,
+    },
+    #[clap(about = "Inject network faults into the ENV, for testing the resilience")]
+    Chaos {
+        #[clap(subcommand)]
+        op: ChaosOp,
+    },
+    #[clap(about = "Create a new ENV in the same shape as an existing one,
+i.e. the same node counts, EL kinds, hosts and binaries")]
+    Clone {
+        #[clap(help = "The existing ENV")]
+        src: String,
+        #[clap(help = "The name of the new ENV")]
+        dst: String,
+        #[clap(
+            long,
+            help = "Copy the genesis and the data of every node,
+i.e. a separate copy of the same chain state, the source nodes are stopped during the copy;
+a new genesis is generated if not set"
+        )]
+        with_data: bool,
+    },
+    #[clap(
+        about = "Export what external participants need to join the ENV with their own nodes,
+i.e. the genesis, the EL enodes, the CL ENRs, the chain ID, the deposit contract and a run script"
+    )]
+    ExportJoinKit {
+        #[clap(short = 'e', long)]
+        env_name: Option<String>,
+        #[clap(help = "The dir of the kit, it must not exist or be empty")]
+        dir: String,
+    },
     #[clap(about = "Manage the ENV meta in git")]
     Git {
         #[clap(short = 'e', long)]
@@ -392,6 +564,74 @@ the `+` is the delimiter between them two"
     #[clap(long, help = "The path of your custom lighthouse binary")]
     pub cl_bin: Option<String>,
 
+    #[clap(
+        long,
+        help = "Manage the node processes(EL, CL BN, CL VC) by systemd user units,
+instead of the default `nohup`, failed processes will be restarted automatically"
+    )]
+    pub systemd: bool,
+
+    #[clap(
+        long,
+        requires = "systemd",
+        help = "The `RestartSec` of the systemd units, default to 5"
+    )]
+    pub systemd_restart_sec: Option<u32>,
+
+    #[clap(
+        long,
+        requires = "systemd",
+        help = "The `MemoryMax` of each systemd unit, e.g., '8G'"
+    )]
+    pub systemd_memory_max: Option<String>,
+
+    #[clap(
+        long,
+        requires = "systemd",
+        help = "The `CPUQuota` of each systemd unit, e.g., '200%'"
+    )]
+    pub systemd_cpu_quota: Option<String>,
+
+    #[clap(
+        long,
+        help = "How long to wait for the graceful exiting of a stopping process,
+it will be killed by SIGKILL after this timeout, default to 30"
+    )]
+    pub stop_timeout_secs: Option<u64>,
+
+    #[clap(
+        long,
+        conflicts_with = "genesis_data_pre_created",
+        help = "Attach the ENV to an external network operated by others, instead of creating a new one,
+the value is the path of its genesis dir, which contains the `genesis.json` and the `config.yaml`,
+e.g. the `genesis/` of a join kit; the fuhrer nodes have no validators in this mode"
+    )]
+    pub external_network: Option<String>,
+
+    #[clap(
+        long,
+        requires = "external_network",
+        help = "The EL enodes of the external network,
+a comma separated list, or a file with one enode per line, e.g. the `bootnodes.txt` of a join kit"
+    )]
+    pub external_bootnodes: Option<String>,
+
+    #[clap(
+        long,
+        requires = "external_network",
+        help = "The CL ENRs of the external network,
+a comma separated list, or a file with one ENR per line, e.g. the `enrs.txt` of a join kit"
+    )]
+    pub external_enrs: Option<String>,
+
+    #[clap(
+        long,
+        requires = "external_network",
+        help = "The checkpoint sync URL of all nodes, e.g. the beacon API of a node in the external network,
+the nodes of the ENV itself are used if not set"
+    )]
+    pub checkpoint_sync_url: Option<String>,
+
     #[clap(
         long = "force",
         help = "Try to destroy the target ENV and then recreate it"
@@ -404,6 +644,31 @@ pub struct DDevCfg {
     #[clap(short = 'e', long)]
     pub env_name: Option<String>,
 
+    #[clap(
+        long,
+        global = true,
+        help = "Print the scripts and file transfers of the operation instead of executing them,
+supported by `start`, `restart`, `push-nodes`, `deposit` and `switch-EL-to-*`"
+    )]
+    pub dry_run: bool,
+
+    #[clap(
+        long,
+        global = true,
+        help = "Wait for the lock of the ENV if it is held by another `exp` process,
+instead of failing at once"
+    )]
+    pub wait: bool,
+
+    #[clap(
+        long,
+        global = true,
+        value_enum,
+        help = "The output format of `list`, `list-rpcs`, `debug-failed-nodes`, `show`, `health` and `topology`,
+the legacy output of each command is kept if not set"
+    )]
+    pub output: Option<OutputFormat>,
+
     #[clap(subcommand)]
     pub op: Option<DDevOp>,
 }
@@ -419,8 +684,7 @@ pub enum DDevOp {
         #[clap(
             short = 'N',
             long,
-            help = "Comma separated NodeID[s], '3', '3,4-9,2,1', etc.
-if set to 'all', then deposit on all non-fuhrer nodes"
+            help = include_str!("nodes.format")
         )]
         nodes: String,
         #[clap(
@@ -463,8 +727,7 @@ or wait until the deposit transaction is confirmed on chain"
         #[clap(
             short = 'N',
             long,
-            help = "Comma separated NodeID[s], '3', '3,2,4-9,1', etc.
-if set to 'all', then exit all validators of all non-fuhrer nodes"
+            help = include_str!("nodes.format")
         )]
         nodes: String,
         #[clap(
@@ -499,7 +762,7 @@ or wait until the exit request is confirmed on chain"
         #[clap(
             short = 'N',
             long,
-            help = "Comma separated NodeID[s], '3', '3,2,1,4-9', etc."
+            help = include_str!("nodes.format")
         )]
         nodes: Option<String>,
         #[clap(long, help = "Filter nodes with the geth el")]
@@ -518,8 +781,7 @@ or wait until the exit request is confirmed on chain"
         #[clap(
             short = 'N',
             long,
-            help = "Comma separated NodeID[s], '3', '3,4-9,2,1', etc.
-if set to 'all', then apply the operation to all nodes"
+            help = include_str!("nodes.format")
         )]
         nodes: String,
         #[clap(long, help = "Filter nodes with the geth el")]
@@ -534,7 +796,7 @@ if set to 'all', then apply the operation to all nodes"
         #[clap(
             short = 'N',
             long,
-            help = "Comma separated NodeID[s], '3', '3,2,4-9,1', etc."
+            help = include_str!("nodes.format")
         )]
         nodes: Option<String>,
         #[clap(long, help = "Filter nodes with the geth el")]
@@ -572,6 +834,19 @@ NOTE: the fullnode mode of `reth` is unstable, do NOT use it"
         reth: bool,
         #[clap(conflicts_with = "reth", long, help = "To get a FullNode, set true")]
         fullnode: bool,
+        #[clap(
+            conflicts_with_all = ["reth", "fullnode"],
+            long,
+            help = "To get dedicated bootnode[s], set true;
+other nodes of the ENV will use them as their bootnodes"
+        )]
+        bootnode: bool,
+        #[clap(
+            requires = "bootnode",
+            long,
+            help = "The `bootnode` tool of geth, a name in $PATH or a path [default: bootnode]"
+        )]
+        el_bootnode_bin: Option<String>,
         #[clap(
             short = 'n',
             long,
@@ -604,7 +879,7 @@ NOTE: the 'new' node will be left stopped, a `start` operation may be needed")]
             short = 'N',
             long,
             conflicts_with = "num",
-            help = "Comma separated NodeID[s], '3', '3,2,1,4-9', etc."
+            help = include_str!("nodes.format")
         )]
         nodes: Option<String>,
         #[clap(
@@ -708,6 +983,49 @@ NOTE: the node will be left stopped, a `start` operation may be needed"
         #[clap(short = 'e', long)]
         env_name: Option<String>,
     },
+    #[clap(
+        about = "Show the process status, EL block number and CL sync status of each node"
+    )]
+    Health {
+        #[clap(short = 'e', long)]
+        env_name: Option<String>,
+    },
+    #[clap(
+        about = "Show the peer graphs of the EL and the CL, with the isolated nodes and the partitions"
+    )]
+    Topology {
+        #[clap(short = 'e', long)]
+        env_name: Option<String>,
+        #[clap(
+            long,
+            help = "Also export the graphs in the Graphviz DOT format to this path"
+        )]
+        dot: Option<String>,
+    },
+    #[clap(about = "Keep running, and restart the crashed nodes that should be online")]
+    Supervise {
+        #[clap(short = 'e', long)]
+        env_name: Option<String>,
+        #[clap(
+            short = 'i',
+            long,
+            default_value_t = 30,
+            help = "Seconds between two rounds of checks,
+also the initial delay of the restart backoff"
+        )]
+        interval_secs: u64,
+        #[clap(
+            long,
+            default_value_t = 600,
+            help = "The max delay between two restarts of the same node"
+        )]
+        max_backoff_secs: u64,
+        #[clap(
+            long,
+            help = "An URL, each restart will be POSTed to it as a JSON document"
+        )]
+        webhook: Option<String>,
+    },
     #[clap(about = "List various RPC endpoints of the ENV")]
     ListRpcs {
         #[clap(short = 'e', long)]
@@ -827,7 +1145,7 @@ all remote files will be collected into this directory,
             conflicts_with = "failed",
             short = 'N',
             long,
-            help = "Comma separated NodeID[s], '3', '3,2,1,4-9', etc."
+            help = include_str!("nodes.format")
         )]
         nodes: Option<String>,
         #[clap(
@@ -852,10 +1170,109 @@ all remote files will be collected into this directory,
         #[clap(
             short = 'N',
             long,
-            help = "Comma separated NodeID[s], '3', '3,4-9,2,1', etc."
+            help = include_str!("nodes.format")
         )]
         nodes: Option<String>,
     },
+    #[clap(about = "Manage labels and notes of nodes")]
+    Label {
+        #[clap(subcommand)]
+        op: LabelOp,
+    },
+    #[clap(about = "Show the audit log of the mutating operations on the ENV")]
+    History {
+        #[clap(short = 'e', long)]
+        env_name: Option<String>,
+        #[clap(long, help = "Only the operators that contain this string")]
+        operator: Option<String>,
+        #[clap(long, help = "Only this operation, e.g., 'kick_nodes'")]
+        operation: Option<String>,
+        #[clap(short = 'N', long, help = "Only the operations on this node")]
+        node: Option<NodeID>,
+        #[clap(long, help = "Only the failed operations")]
+        failed: bool,
+        #[clap(
+            long,
+            value_parser = parse_since,
+            help = "A relative time like '30m', '2h' and '7d', or a unix timestamp"
+        )]
+        since: Option<u64>,
+        #[clap(short = 'n', long, help = "Only the last N operations")]
+        last: Option<usize>,
+    },
+    #[clap(about = "Manage the notification hooks of the ENV")]
+    Hook {
+        #[clap(subcommand)]
+        op: HookOp,
+    },
+    #[clap(
+        about = "Manage the full snapshots of the node data and the meta of the ENV"
+    )]
+    Snapshot {
+        #[clap(subcommand)]
+        op: SnapshotOp,
+    },
+    #[clap(about = "Inject network faults into the ENV, for testing the resilience")]
+    Chaos {
+        #[clap(subcommand)]
+        op: ChaosOp,
+    },
+    #[clap(about = "Create a new ENV in the same shape as an existing one,
+i.e. the same node counts, EL kinds, hosts and binaries")]
+    Clone {
+        #[clap(help = "The existing ENV")]
+        src: String,
+        #[clap(help = "The name of the new ENV")]
+        dst: String,
+        #[clap(
+            long,
+            help = "Copy the genesis and the data of every node,
+i.e. a separate copy of the same chain state, the source nodes are stopped during the copy;
+a new genesis is generated if not set"
+        )]
+        with_data: bool,
+    },
+    #[clap(
+        about = "Export the ENV as a portable bundle, for handing it over to others,
+including the ENV meta, the genesis, the validator keys, the host inventory and the checksums of the client binaries"
+    )]
+    Export {
+        #[clap(help = "The ENV to be exported")]
+        env_name: String,
+        #[clap(
+            help = "The path of the bundle, compressed according to its suffix, e.g. `bundle.tar.zst`"
+        )]
+        path: String,
+        #[clap(
+            long,
+            help = "Replace the host addresses and the ssh settings with placeholders"
+        )]
+        redact_hosts: bool,
+    },
+    #[clap(about = "Import a bundle created by `export` as a new ENV,
+with the same genesis and the same shape, on the hosts of the local configuration")]
+    Import {
+        #[clap(help = "The path of the bundle")]
+        path: String,
+        #[clap(help = "The name of the new ENV")]
+        env_name: String,
+        #[clap(
+            short = 'H',
+            long,
+            help = include_str!("hosts.format")
+        )]
+        hosts: Option<HostExpression>,
+    },
+    #[clap(
+        about = "Export what external participants need to join the ENV with their own nodes,
+i.e. the genesis, the EL enodes, the CL ENRs, the chain ID, the deposit contract and a run script"
+    )]
+    ExportJoinKit {
+        #[clap(short = 'e', long)]
+        env_name: Option<String>,
+        #[clap(help = "The dir of the kit, it must not exist or be empty")]
+        dir: String,
+    },
     #[clap(about = "Manage the ENV meta in git")]
     Git {
         #[clap(short = 'e', long)]
@@ -865,6 +1282,16 @@ all remote files will be collected into this directory,
         #[clap(short = 'p', long, help = "Push local updates to the remote codebase")]
         push: bool,
     },
+    #[clap(about = "Reconcile an existing ENV with a declarative spec file,
+the differences will be printed as a plan and then be applied")]
+    Apply {
+        #[clap(short = 'e', long)]
+        env_name: Option<String>,
+        #[clap(help = "The path of the spec file in YAML, see 'src/ddev/README.md'")]
+        spec_path: String,
+        #[clap(short = 'p', long, help = "Print the plan only, do not apply it")]
+        plan: bool,
+    },
 }
 
 impl Default for DDevOp {
@@ -924,6 +1351,74 @@ the `+` is the delimiter between them two"
     #[clap(long, help = "The path of your custom consensus layer binary")]
     pub cl_bin: Option<String>,
 
+    #[clap(
+        long,
+        help = "Manage the node processes(EL, CL BN, CL VC) by systemd user units,
+instead of the default `nohup`, failed processes will be restarted automatically"
+    )]
+    pub systemd: bool,
+
+    #[clap(
+        long,
+        requires = "systemd",
+        help = "The `RestartSec` of the systemd units, default to 5"
+    )]
+    pub systemd_restart_sec: Option<u32>,
+
+    #[clap(
+        long,
+        requires = "systemd",
+        help = "The `MemoryMax` of each systemd unit, e.g., '8G'"
+    )]
+    pub systemd_memory_max: Option<String>,
+
+    #[clap(
+        long,
+        requires = "systemd",
+        help = "The `CPUQuota` of each systemd unit, e.g., '200%'"
+    )]
+    pub systemd_cpu_quota: Option<String>,
+
+    #[clap(
+        long,
+        help = "How long to wait for the graceful exiting of a stopping process,
+it will be killed by SIGKILL after this timeout, default to 30"
+    )]
+    pub stop_timeout_secs: Option<u64>,
+
+    #[clap(
+        long,
+        conflicts_with = "genesis_data_pre_created",
+        help = "Attach the ENV to an external network operated by others, instead of creating a new one,
+the value is the path of its genesis dir, which contains the `genesis.json` and the `config.yaml`,
+e.g. the `genesis/` of a join kit; the fuhrer nodes have no validators in this mode"
+    )]
+    pub external_network: Option<String>,
+
+    #[clap(
+        long,
+        requires = "external_network",
+        help = "The EL enodes of the external network,
+a comma separated list, or a file with one enode per line, e.g. the `bootnodes.txt` of a join kit"
+    )]
+    pub external_bootnodes: Option<String>,
+
+    #[clap(
+        long,
+        requires = "external_network",
+        help = "The CL ENRs of the external network,
+a comma separated list, or a file with one ENR per line, e.g. the `enrs.txt` of a join kit"
+    )]
+    pub external_enrs: Option<String>,
+
+    #[clap(
+        long,
+        requires = "external_network",
+        help = "The checkpoint sync URL of all nodes, e.g. the beacon API of a node in the external network,
+the nodes of the ENV itself are used if not set"
+    )]
+    pub checkpoint_sync_url: Option<String>,
+
     #[clap(
         long = "force",
         help = "Try to destroy the target ENV and then recreate it"
@@ -994,3 +1489,207 @@ pub struct ValidatorExitCfg {
     )]
     pub password_path: String,
 }
+
+#[derive(Debug, Subcommand)]
+pub enum LabelOp {
+    #[clap(about = "Add labels and `key=value` notes to nodes")]
+    Add {
+        #[clap(short = 'e', long)]
+        env_name: Option<String>,
+        #[clap(
+            short = 'N',
+            long,
+            help = include_str!("nodes.format")
+        )]
+        nodes: String,
+        #[clap(
+            required = true,
+            help = "Labels like 'canary', or notes like 'owner=alice',
+an existing note will be overwritten by the new value"
+        )]
+        items: Vec<String>,
+    },
+    #[clap(about = "Remove labels and notes from nodes")]
+    Remove {
+        #[clap(short = 'e', long)]
+        env_name: Option<String>,
+        #[clap(
+            short = 'N',
+            long,
+            help = include_str!("nodes.format")
+        )]
+        nodes: String,
+        #[clap(required = true, help = "Names of the labels, or keys of the notes")]
+        items: Vec<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum HookOp {
+    #[clap(about = "Add hooks, they are fired after every mutating operation")]
+    Add {
+        #[clap(short = 'e', long)]
+        env_name: Option<String>,
+        #[clap(
+            short = 'w',
+            long,
+            help = "An URL, each event will be POSTed to it as a JSON document"
+        )]
+        webhook: Vec<String>,
+        #[clap(
+            short = 's',
+            long,
+            help = "A local executable, each event will be passed to it
+by the stdin and by the `EXP_EVENT` environment variable"
+        )]
+        script: Vec<String>,
+    },
+    #[clap(about = "Remove hooks")]
+    Remove {
+        #[clap(short = 'e', long)]
+        env_name: Option<String>,
+        #[clap(short = 'w', long)]
+        webhook: Vec<String>,
+        #[clap(short = 's', long)]
+        script: Vec<String>,
+    },
+    #[clap(about = "Show all hooks of the ENV")]
+    List {
+        #[clap(short = 'e', long)]
+        env_name: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SnapshotOp {
+    #[clap(
+        about = "Stop all nodes, archive the data of every node and the ENV meta,
+then start the nodes that were online"
+    )]
+    Create {
+        #[clap(short = 'e', long)]
+        env_name: Option<String>,
+        name: String,
+    },
+    #[clap(
+        about = "Stop all nodes, put the data and the meta of the snapshot back,
+then start the nodes that were online when the snapshot was created"
+    )]
+    Restore {
+        #[clap(short = 'e', long)]
+        env_name: Option<String>,
+        name: String,
+    },
+    #[clap(about = "Show all snapshots of the ENV")]
+    List {
+        #[clap(short = 'e', long)]
+        env_name: Option<String>,
+    },
+    #[clap(about = "Delete a snapshot")]
+    Delete {
+        #[clap(short = 'e', long)]
+        env_name: Option<String>,
+        name: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ChaosOp {
+    #[clap(about = "Cut the P2P traffic between groups of nodes for a while,
+the network heals automatically at the end")]
+    Partition {
+        #[clap(short = 'e', long)]
+        env_name: Option<String>,
+        #[clap(
+            short = 'g',
+            long,
+            required = true,
+            num_args = 1..,
+            help = "Node selectors of the groups, separated by a standalone '/',
+e.g., '--groups 1-5 / 6-10', nodes out of the groups are not affected"
+        )]
+        groups: Vec<String>,
+        #[clap(
+            short = 'd',
+            long,
+            value_parser = parse_duration,
+            help = "How long the partition lasts, e.g., '90s', '10m' and '1h'"
+        )]
+        duration: u64,
+    },
+    #[clap(about = "Impair the P2P traffic of nodes by `tc netem`,
+e.g., to simulate the nodes spanning continents")]
+    Netem {
+        #[clap(short = 'e', long)]
+        env_name: Option<String>,
+        #[clap(
+            short = 'N',
+            long,
+            help = include_str!("nodes.format")
+        )]
+        nodes: String,
+        #[clap(
+            long,
+            required_unless_present = "loss",
+            value_parser = parse_time,
+            help = "e.g., '150ms'"
+        )]
+        delay: Option<String>,
+        #[clap(
+            long,
+            requires = "delay",
+            value_parser = parse_time,
+            help = "e.g., '30ms', the delay varies within this range"
+        )]
+        jitter: Option<String>,
+        #[clap(long, value_parser = parse_percent, help = "e.g., '1%'")]
+        loss: Option<String>,
+    },
+    #[clap(about = "Randomly stop, kill(-9) or restart nodes over a soak window,
+the downed nodes are started again after a while")]
+    Monkey {
+        #[clap(short = 'e', long)]
+        env_name: Option<String>,
+        #[clap(
+            short = 'N',
+            long,
+            visible_alias = "selector",
+            default_value = "all",
+            help = include_str!("nodes.format")
+        )]
+        nodes: String,
+        #[clap(
+            long,
+            default_value = "1/10m",
+            value_parser = parse_rate,
+            help = "How often to act, e.g., '1/10m' for one action every 10 minutes"
+        )]
+        rate: u64,
+        #[clap(
+            long,
+            default_value = "20%",
+            value_parser = parse_max_down,
+            help = "At most this many nodes of the ENV are down at the same time,
+a percentage of all nodes like '20%', or a count like '2',
+the nodes that are offline for other reasons are counted too"
+        )]
+        max_down: MaxDown,
+        #[clap(
+            short = 'd',
+            long,
+            value_parser = parse_duration,
+            help = "The soak window, e.g., '2h', all downed nodes are started at the end"
+        )]
+        duration: u64,
+        #[clap(
+            long,
+            help = "The seed of the random choices, a random one is used and printed if not set"
+        )]
+        seed: Option<u64>,
+    },
+    #[clap(about = "Remove all impairments, and heal the running partition")]
+    Clear {
+        #[clap(short = 'e', long)]
+        env_name: Option<String>,
+    },
+}
@@ -0,0 +1,789 @@
+//!
+//! Network fault injection between the nodes of an ENV.
+//!
+//! Every fault is applied by a script on each related host,
+//! which reverts its own changes at the end of the duration,
+//! so the ENV always heals, even if `exp` exits in any way.
+//!
+
+use crate::{
+    common::{parse_duration, Ports},
+    selector::Selector,
+};
+use chaindev::{EnvName, NodeID};
+use ruc::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt, mem,
+};
+
+// The handle of the root qdisc of the shaping
+const NETEM_ROOT: &str = "e0";
+
+// Band 1 is the default one, so 15 nodes at most on a host
+const NETEM_BANDS: usize = 16;
+
+/// The P2P endpoints of a node
+#[derive(Clone, Debug)]
+pub struct P2pNode {
+    pub id: NodeID,
+    /// The host that the node is located on, e.g. the host ID in `ddev`
+    pub host: String,
+    /// The addresses that the peers reach the node by
+    pub addrs: Vec<String>,
+    pub home: String,
+    pub tcp_ports: Vec<u16>,
+    pub udp_ports: Vec<u16>,
+}
+
+impl P2pNode {
+    pub fn new(
+        id: NodeID,
+        host: &str,
+        addrs: Vec<String>,
+        home: &str,
+        ports: &Ports,
+    ) -> Self {
+        Self {
+            id,
+            host: host.to_owned(),
+            addrs,
+            home: home.to_owned(),
+            tcp_ports: vec![ports.el_discovery, ports.cl_discovery],
+            udp_ports: vec![
+                ports.el_discovery,
+                ports.el_discovery_v5,
+                ports.cl_discovery,
+                ports.cl_discovery_quic,
+            ],
+        }
+    }
+}
+
+/// Node selectors separated by a standalone '/', e.g. '1-5 / 6-10',
+/// the '/' in a label is not a separator
+pub fn parse_groups(s: &str) -> Result<Vec<Selector>> {
+    let groups = s
+        .split(" / ")
+        .map(|g| g.trim().parse::<Selector>().c(d!(g)))
+        .collect::<Result<Vec<_>>>()?;
+    if groups.len() < 2 {
+        return Err(eg!("At least 2 groups are needed, e.g. '1-5 / 6-10'"));
+    }
+    Ok(groups)
+}
+
+pub struct Partition {
+    groups: Vec<Vec<P2pNode>>,
+}
+
+impl Partition {
+    pub fn new(groups: Vec<Vec<P2pNode>>) -> Result<Self> {
+        let mut seen = BTreeSet::new();
+        for (idx, g) in groups.iter().enumerate() {
+            if g.is_empty() {
+                return Err(eg!("No node is selected by the group {}", 1 + idx));
+            }
+            if let Some(n) = g.iter().find(|n| !seen.insert(n.id)) {
+                return Err(eg!("The node(id: {}) is in more than one group", n.id));
+            }
+        }
+        Ok(Self { groups })
+    }
+
+    pub fn nodes(&self) -> BTreeSet<NodeID> {
+        self.groups.iter().flatten().map(|n| n.id).collect()
+    }
+
+    /// <host, the cmd to run on it>
+    pub fn cmds_for_start(&self, env: &EnvName, secs: u64) -> BTreeMap<String, String> {
+        let mut scripts = BTreeMap::new();
+        for (gi, g) in self.groups.iter().enumerate() {
+            let others = self
+                .groups
+                .iter()
+                .enumerate()
+                .filter(|(hi, _)| *hi != gi)
+                .flat_map(|(_, h)| h.iter())
+                .collect::<Vec<_>>();
+            for n in g.iter() {
+                let s = scripts
+                    .entry(n.host.clone())
+                    .or_insert_with(PartitionScript::default);
+                s.add(n, &others);
+            }
+        }
+        scripts
+            .into_iter()
+            .map(|(host, s)| (host, s.cmd_for_start(env, secs)))
+            .collect()
+    }
+}
+
+// What is done on one host
+#[derive(Default)]
+struct PartitionScript {
+    // The UDP packets from the other groups, matched by the port pairs
+    rules: Vec<String>,
+    // The nodes on the host
+    nodes: Vec<GuardedNode>,
+}
+
+// TCP connections are dialed from random ports, so the port pairs can not tell
+// the groups apart on a shared address; the dials are dropped by the cgroups
+// of the dialing processes instead, which covers both directions,
+// since the nodes on both sides are guarded
+struct GuardedNode {
+    id: NodeID,
+    home: String,
+    // The TCP listeners of the other groups
+    tcp_rules: Vec<String>,
+    // The same listeners, as a filter of `ss`
+    endpoints: Vec<String>,
+}
+
+impl PartitionScript {
+    fn add(&mut self, n: &P2pNode, others: &[&P2pNode]) {
+        let ports = |ports: &[u16]| {
+            ports
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let mut tcp_rules = vec![];
+        let mut endpoints = vec![];
+        for o in others.iter() {
+            for addr in o.addrs.iter() {
+                self.rules.push(format!(
+                    "-s {addr} -p udp -m multiport --sports {} -m multiport --dports {}",
+                    ports(&o.udp_ports),
+                    ports(&n.udp_ports)
+                ));
+                tcp_rules.push(format!(
+                    "-d {addr} -p tcp -m multiport --dports {}",
+                    ports(&o.tcp_ports)
+                ));
+                endpoints.extend(o.tcp_ports.iter().map(|p| format!("dst {addr}:{p}")));
+            }
+        }
+        self.nodes.push(GuardedNode {
+            id: n.id,
+            home: n.home.clone(),
+            tcp_rules,
+            endpoints,
+        });
+    }
+
+    fn cmd_for_start(&self, env: &EnvName, secs: u64) -> String {
+        let tag = format!("exp-chaos-partition-{env}");
+        let path = format!("/tmp/{tag}.sh");
+
+        let quote = |rules: &[String]| {
+            rules
+                .iter()
+                .map(|r| format!("    '{r}'"))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        let rules = quote(&self.rules);
+        let tcp_rules = self
+            .nodes
+            .iter()
+            .map(|n| format!("TCP_{}=(\n{}\n)", n.id, quote(&n.tcp_rules)))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let guards = self
+            .nodes
+            .iter()
+            .map(|n| {
+                format!(
+                    "    guard {0} {1} '( {2} )' TCP_{0} || {{ heal; exit 1; }}",
+                    n.id,
+                    n.home,
+                    n.endpoints.join(" or ")
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let stop = cmd_for_stop_partition(env);
+
+        format!(
+            r#"
+for bin in iptables ss pgrep pkill; do
+    command -v $bin >/dev/null || {{ echo "'$bin' is not found"; exit 1; }}
+done
+iptables -S INPUT >/dev/null || exit 1
+[ -f /sys/fs/cgroup/cgroup.controllers ] || {{ echo "cgroup v2 is needed"; exit 1; }}
+{stop}
+
+cat >{path} <<'EXP_EOF' || exit 1
+#!/usr/bin/env bash
+
+TAG={tag}
+CG=/sys/fs/cgroup/$TAG
+
+RULES=(
+{rules}
+)
+{tcp_rules}
+
+# <pid, the original cgroup>
+declare -A ORIG
+# <'node ID cgroup', 1>
+declare -A GUARDED
+
+heal() {{
+    for chain in INPUT OUTPUT; do
+        iptables -S $chain | grep -F -- "--comment $TAG " | while read -r r; do
+            iptables -D ${{r#-A }}
+        done
+    done
+    for pid in "${{!ORIG[@]}}"; do
+        echo $pid >/sys/fs/cgroup${{ORIG[$pid]}}/cgroup.procs 2>/dev/null
+    done
+    rmdir $CG/* $CG 2>/dev/null
+}}
+trap 'heal; exit 0' TERM INT
+
+# The processes started from the node home
+pids_of() {{
+    ps ax -o pid=,args= | awk -v p="$1/" 'index($2, p) == 1 {{ print $1 }}'
+}}
+
+# If all the processes in the cgroup belong to the node
+owned() {{
+    for p in $(cat /sys/fs/cgroup$1/cgroup.procs 2>/dev/null); do
+        case "$(tr '\0' ' ' </proc/$p/cmdline 2>/dev/null)" in
+            "$2"/*) ;;
+            *) return 1 ;;
+        esac
+    done
+}}
+
+# Drops the TCP dials of a node to the other groups by the cgroups of its processes;
+# a process that shares its cgroup with others is moved into a new one,
+# and its connections to the other groups are closed once,
+# since its existing sockets still belong to the old cgroup
+guard() {{
+    local -n tcp_rules=$4
+    for pid in $(pids_of $2); do
+        local cg=$(sed -n 's/^0:://p' /proc/$pid/cgroup 2>/dev/null)
+        local moved=""
+        [ -n "$cg" ] || continue
+        if [ "$cg" != "/$TAG/$1" ] && ! owned "$cg" "$2"; then
+            mkdir -p $CG/$1 && echo $pid >$CG/$1/cgroup.procs || return 1
+            ORIG[$pid]=$cg
+            cg=/$TAG/$1
+            moved=1
+        fi
+        if [ -z "${{GUARDED[$1 $cg]}}" ]; then
+            for r in "${{tcp_rules[@]}}"; do
+                iptables -I OUTPUT -m cgroup --path ${{cg#/}} $r -m comment --comment $TAG -j DROP || return 1
+            done
+            GUARDED[$1 $cg]=1
+        fi
+        if [ -n "$moved" ]; then
+            ss -Htnp state established "$3" | grep -F "pid=$pid," | while read -r _ _ l r _; do
+                ss -K src "${{l%:*}}" sport = ":${{l##*:}}" dst "${{r%:*}}" dport = ":${{r##*:}}" >/dev/null
+            done
+        fi
+    done
+}}
+
+for r in "${{RULES[@]}}"; do
+    iptables -I INPUT $r -m comment --comment $TAG -j DROP || {{ heal; exit 1; }}
+done
+
+# The restarted processes are guarded in the next round
+end=$(( $(date +%s) + {secs} ))
+while [ $(date +%s) -lt $end ]; do
+{guards}
+    touch {path}.ready
+    sleep 1
+done
+heal
+EXP_EOF
+
+rm -f {path}.ready
+nohup bash {path} >{path}.log 2>&1 &
+pid=$!
+for i in $(seq 100); do
+    [ -f {path}.ready ] && exit 0
+    kill -0 $pid 2>/dev/null || break
+    sleep 0.2
+done
+cat {path}.log
+exit 1
+"#
+        )
+    }
+}
+
+/// The impairments of a node, applied by `tc netem`
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Netem {
+    /// e.g. '150ms'
+    pub delay: Option<String>,
+    /// e.g. '30ms', only works along with the delay
+    pub jitter: Option<String>,
+    /// e.g. '1%'
+    pub loss: Option<String>,
+}
+
+/// The arguments of `tc netem`, e.g. 'delay 150ms 30ms loss 1%'
+impl fmt::Display for Netem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut args = vec![];
+        if let Some(d) = self.delay.as_deref() {
+            args.push(format!("delay {d}"));
+            if let Some(j) = self.jitter.as_deref() {
+                args.push(j.to_owned());
+            }
+        }
+        if let Some(l) = self.loss.as_deref() {
+            args.push(format!("loss {l}"));
+        }
+        write!(f, "{}", args.join(" "))
+    }
+}
+
+/// A time like '150ms', '500us' and '1s'
+pub fn parse_time(s: &str) -> std::result::Result<String, String> {
+    let s = s.trim();
+    let n = ["us", "ms", "s"]
+        .iter()
+        .find_map(|unit| s.strip_suffix(unit))
+        .ok_or_else(|| format!("invalid unit in '{s}', expected us/ms/s"))?;
+    n.parse::<f64>()
+        .ok()
+        .filter(|n| n.is_finite() && *n >= 0.0)
+        .map(|_| s.to_owned())
+        .ok_or_else(|| format!("invalid number in '{s}'"))
+}
+
+/// A percentage like '1%' and '0.5%'
+pub fn parse_percent(s: &str) -> std::result::Result<String, String> {
+    let s = s.trim();
+    s.strip_suffix('%')
+        .and_then(|n| n.parse::<f64>().ok())
+        .filter(|n| (0.0..=100.0).contains(n))
+        .map(|_| s.to_owned())
+        .ok_or_else(|| format!("invalid percentage '{s}', expected something like '1%'"))
+}
+
+/// Rebuild the shaping of a host from all the impairments on it,
+/// `addr` is a local address of the host, the interface that owns it and `lo` are shaped.
+///
+/// The packets from the P2P ports of each node go through its own netem qdisc;
+/// the TCP connections dialed by the node use random ports,
+/// so they are only shaped if the other side is impaired too
+pub fn cmd_for_netem(addr: &str, nodes: &[(P2pNode, Netem)]) -> Result<String> {
+    if NETEM_BANDS <= nodes.len() {
+        return Err(eg!(
+            "At most {} nodes can be impaired on a host",
+            NETEM_BANDS - 1
+        ));
+    }
+
+    let mut setup = String::new();
+    if !nodes.is_empty() {
+        setup.push_str(&format!(
+            "    tc qdisc add dev $dev root handle {NETEM_ROOT}: prio bands {NETEM_BANDS} priomap{} || exit 1\n",
+            " 0".repeat(16)
+        ));
+    }
+    for (idx, (n, netem)) in nodes.iter().enumerate() {
+        let band = 2 + idx;
+        setup.push_str(&format!(
+            "    tc qdisc add dev $dev parent {NETEM_ROOT}:{band:x} handle e{band:x}: netem {netem} || exit 1\n"
+        ));
+        let ports = n
+            .tcp_ports
+            .iter()
+            .chain(n.udp_ports.iter())
+            .collect::<BTreeSet<_>>();
+        for p in ports {
+            setup.push_str(&format!(
+                "    tc filter add dev $dev parent {NETEM_ROOT}: protocol ip prio 1 u32 match ip sport {p} 0xffff flowid {NETEM_ROOT}:{band:x} || exit 1\n"
+            ));
+        }
+    }
+
+    let check = alt!(
+        nodes.is_empty(),
+        "command -v tc >/dev/null || exit 0",
+        r#"command -v tc >/dev/null || { echo "'tc' is not found"; exit 1; }"#
+    );
+
+    Ok(format!(
+        r#"
+{check}
+for dev in $(printf '%s\n' lo $(ip -o addr show to {addr} | awk '{{print $2}}') | sort -u); do
+    if tc qdisc show dev $dev | grep -q '^qdisc prio {NETEM_ROOT}: root'; then
+        tc qdisc del dev $dev root || exit 1
+    fi
+{setup}done
+"#
+    ))
+}
+
+/// Stop the partition and remove the shaping on a host
+pub fn cmd_for_clear(env: &EnvName, addr: &str) -> String {
+    format!(
+        "{}\n{}",
+        cmd_for_stop_partition(env),
+        pnk!(cmd_for_netem(addr, &[]))
+    )
+}
+
+// The running partition script heals before exiting
+fn cmd_for_stop_partition(env: &EnvName) -> String {
+    // The '[e]' stops `pkill` from matching the shell that runs this cmd
+    let pattern = format!("[e]xp-chaos-partition-{env}.sh");
+    format!(
+        r#"
+if pkill -TERM -f '{pattern}'; then
+    while pgrep -f '{pattern}' >/dev/null; do sleep 0.5; done
+fi"#
+    )
+}
+
+/// What the chaos monkey does to a node
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MonkeyAction {
+    /// Stopped gracefully, and started again after a while
+    Stop,
+    /// Stopped by `kill -9`, and started again after a while
+    Kill,
+    /// Stopped and started again at once
+    Restart,
+}
+
+impl MonkeyAction {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Stop => "stop",
+            Self::Kill => "kill",
+            Self::Restart => "restart",
+        }
+    }
+}
+
+/// The cap of the nodes that are down at the same time
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MaxDown {
+    Percent(u8),
+    Count(usize),
+}
+
+impl MaxDown {
+    pub fn cap(self, total: usize) -> usize {
+        match self {
+            Self::Percent(p) => total * p as usize / 100,
+            Self::Count(n) => n,
+        }
+    }
+}
+
+/// A percentage of all nodes like '20%', or a count like '2'
+pub fn parse_max_down(s: &str) -> std::result::Result<MaxDown, String> {
+    let s = s.trim();
+    if let Some(p) = s.strip_suffix('%') {
+        p.parse::<u8>()
+            .ok()
+            .filter(|p| *p <= 100)
+            .map(MaxDown::Percent)
+            .ok_or_else(|| {
+                format!("invalid percentage '{s}', expected something like '20%'")
+            })
+    } else {
+        s.parse::<usize>()
+            .map(MaxDown::Count)
+            .map_err(|e| format!("invalid count '{s}': {e}"))
+    }
+}
+
+/// A rate like '1/10m' or '3/1h', in the seconds between two actions
+pub fn parse_rate(s: &str) -> std::result::Result<u64, String> {
+    let (n, window) = s
+        .trim()
+        .split_once('/')
+        .ok_or_else(|| format!("invalid rate '{s}', expected something like '1/10m'"))?;
+    let n = n
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .filter(|n| 0 < *n)
+        .ok_or_else(|| format!("invalid number of actions in '{s}'"))?;
+    parse_duration(window).map(|secs| (secs / n).max(1))
+}
+
+/// A seed for the chaos monkey, printed at the start,
+/// so the same choices can be made again with it
+pub fn new_monkey_seed() -> u64 {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default();
+    nanos ^ ((std::process::id() as u64) << 32)
+}
+
+/// The random choices of the chaos monkey,
+/// the same seed leads to the same choices against the same ENV states
+pub struct Monkey {
+    // Of the splitmix64
+    state: u64,
+    interval_secs: u64,
+    cap: usize,
+    candidates: Vec<NodeID>,
+    // <node ID, when to start it again>
+    down: BTreeMap<NodeID, u64>,
+}
+
+impl Monkey {
+    pub fn new(
+        seed: u64,
+        interval_secs: u64,
+        cap: usize,
+        candidates: BTreeSet<NodeID>,
+    ) -> Self {
+        Self {
+            state: seed,
+            interval_secs: interval_secs.max(1),
+            cap,
+            candidates: candidates.into_iter().collect(),
+            down: BTreeMap::new(),
+        }
+    }
+
+    /// The nodes to be started again at `now`
+    pub fn due(&mut self, now: u64) -> Vec<NodeID> {
+        let ids = self
+            .down
+            .iter()
+            .filter(|(_, ts)| **ts <= now)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        ids.iter().for_each(|id| {
+            self.down.remove(id);
+        });
+        ids
+    }
+
+    /// All the nodes that are still down, e.g. at the end of the soak window
+    pub fn release_all(&mut self) -> Vec<NodeID> {
+        mem::take(&mut self.down).into_keys().collect()
+    }
+
+    /// The next action, `None` if the cap would be exceeded or no node is available;
+    /// `offline`: the nodes of the ENV that should not be online
+    pub fn next(
+        &mut self,
+        now: u64,
+        offline: &BTreeSet<NodeID>,
+    ) -> Option<(NodeID, MonkeyAction)> {
+        let down = offline
+            .iter()
+            .chain(self.down.keys())
+            .collect::<BTreeSet<_>>();
+        if self.cap < down.len() + 1 {
+            return None;
+        }
+        let available = self
+            .candidates
+            .iter()
+            .filter(|id| !down.contains(id))
+            .copied()
+            .collect::<Vec<_>>();
+        if available.is_empty() {
+            return None;
+        }
+
+        let id = available[self.rand(available.len() as u64) as usize];
+        let action = [
+            MonkeyAction::Stop,
+            MonkeyAction::Kill,
+            MonkeyAction::Restart,
+        ][self.rand(3) as usize];
+        if MonkeyAction::Restart != action {
+            let back = now + 1 + self.rand(self.interval_secs);
+            self.down.insert(id, back);
+        }
+        Some((id, action))
+    }
+
+    // In `[0, n)`
+    fn rand(&mut self, n: u64) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        (z ^ (z >> 31)) % n
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn partition() {
+        assert!(parse_groups("1-5").is_err());
+        assert!(parse_groups("1-5/6-10").is_err());
+        assert_eq!(2, pnk!(parse_groups("label=a/b / 6-10")).len());
+        assert!(parse_groups("1-5 / ").is_err());
+        assert_eq!(2, pnk!(parse_groups(" 1-5 / 6-10 ")).len());
+
+        let node = |id, host: &str| {
+            let ports = Ports {
+                el_discovery: 100 * id as u16 + 1,
+                el_discovery_v5: 100 * id as u16 + 2,
+                cl_discovery: 100 * id as u16 + 3,
+                cl_discovery_quic: 100 * id as u16 + 4,
+                ..Default::default()
+            };
+            P2pNode::new(id, host, vec![host.to_owned()], &format!("/n/{id}"), &ports)
+        };
+
+        assert!(Partition::new(vec![vec![node(1, "h1")], vec![]]).is_err());
+        assert!(Partition::new(vec![vec![node(1, "h1")], vec![node(1, "h1")]]).is_err());
+
+        let p = pnk!(Partition::new(vec![
+            vec![node(1, "h1"), node(2, "h2")],
+            vec![node(3, "h2")],
+        ]));
+        assert_eq!(BTreeSet::from([1, 2, 3]), p.nodes());
+
+        let cmds = p.cmds_for_start(&"MyEnv".into(), 600);
+        assert_eq!(2, cmds.len());
+
+        let h1 = &cmds["h1"];
+        assert!(h1.contains(
+            "'-s h2 -p udp -m multiport --sports 301,302,303,304 -m multiport --dports 101,102,103,104'"
+        ));
+        assert!(
+            h1.contains("TCP_1=(\n    '-d h2 -p tcp -m multiport --dports 301,303'\n)")
+        );
+        assert!(h1.contains(
+            "guard 1 /n/1 '( dst h2:301 or dst h2:303 )' TCP_1 || { heal; exit 1; }"
+        ));
+        assert!(h1.contains("pkill -TERM -f '[e]xp-chaos-partition-MyEnv.sh'"));
+        assert!(h1.contains("end=$(( $(date +%s) + 600 ))"));
+
+        // Both directions are cut on the shared host
+        let h2 = &cmds["h2"];
+        assert!(h2.contains(
+            "--sports 301,302,303,304 -m multiport --dports 201,202,203,204'"
+        ));
+        assert!(h2.contains(
+            "--sports 101,102,103,104 -m multiport --dports 301,302,303,304'"
+        ));
+        assert!(h2.contains(
+            "guard 3 /n/3 '( dst h1:101 or dst h1:103 or dst h2:201 or dst h2:203 )' TCP_3"
+        ));
+        assert!(h2.contains("    '-d h2 -p tcp -m multiport --dports 301,303'"));
+        assert!(h2.contains("    '-d h2 -p tcp -m multiport --dports 201,203'"));
+    }
+
+    #[test]
+    fn netem() {
+        assert_eq!(Ok("150ms".to_owned()), parse_time("150ms"));
+        assert!(parse_time("150").is_err());
+        assert!(parse_time("-1s").is_err());
+        assert_eq!(Ok("0.5%".to_owned()), parse_percent("0.5%"));
+        assert!(parse_percent("101%").is_err());
+        assert!(parse_percent("1").is_err());
+
+        let netem = Netem {
+            delay: Some("150ms".to_owned()),
+            jitter: Some("30ms".to_owned()),
+            loss: Some("1%".to_owned()),
+        };
+        assert_eq!("delay 150ms 30ms loss 1%", netem.to_string());
+        let loss = Netem {
+            loss: Some("1%".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!("loss 1%", loss.to_string());
+
+        let ports = Ports {
+            el_discovery: 30303,
+            el_discovery_v5: 9200,
+            cl_discovery: 9000,
+            cl_discovery_quic: 9001,
+            ..Default::default()
+        };
+        let node = |id| {
+            let p2p = P2pNode::new(id, "h1", vec!["10.0.0.1".to_owned()], "/n", &ports);
+            (p2p, netem.clone())
+        };
+
+        let cmd = pnk!(cmd_for_netem("10.0.0.1", &[node(1), node(2)]));
+        assert!(cmd.contains("ip -o addr show to 10.0.0.1"));
+        assert!(cmd.contains("root handle e0: prio bands 16 priomap 0 0 0"));
+        assert!(cmd.contains("parent e0:3 handle e3: netem delay 150ms 30ms loss 1%"));
+        assert!(cmd.contains("match ip sport 9001 0xffff flowid e0:2"));
+        assert_eq!(2 * 4, cmd.matches("tc filter add").count());
+
+        assert!(
+            cmd_for_netem("10.0.0.1", &(1..16).map(node).collect::<Vec<_>>()).is_ok()
+        );
+        assert!(
+            cmd_for_netem("10.0.0.1", &(1..17).map(node).collect::<Vec<_>>()).is_err()
+        );
+
+        let clear = cmd_for_clear(&"MyEnv".into(), "10.0.0.1");
+        assert!(clear.contains("pkill -TERM -f '[e]xp-chaos-partition-MyEnv.sh'"));
+        assert!(clear.contains("tc qdisc del dev $dev root"));
+        assert!(!clear.contains("tc qdisc add"));
+    }
+
+    #[test]
+    fn monkey() {
+        assert_eq!(Ok(600), parse_rate("1/10m"));
+        assert_eq!(Ok(1200), parse_rate("3/1h"));
+        assert!(parse_rate("0/1h").is_err());
+        assert!(parse_rate("10m").is_err());
+        assert_eq!(Ok(MaxDown::Percent(20)), parse_max_down("20%"));
+        assert_eq!(Ok(MaxDown::Count(2)), parse_max_down("2"));
+        assert!(parse_max_down("120%").is_err());
+        assert_eq!(2, MaxDown::Percent(20).cap(10));
+        assert_eq!(1, MaxDown::Percent(20).cap(9));
+
+        let candidates = BTreeSet::from([1, 2, 3, 4, 5]);
+        let run = |seed| {
+            let mut m = Monkey::new(seed, 60, 2, candidates.clone());
+            (0..20)
+                .map(|i| {
+                    let now = 60 * i;
+                    m.due(now);
+                    m.next(now, &BTreeSet::new())
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(run(7), run(7));
+        assert!(run(7)
+            .iter()
+            .flatten()
+            .any(|(_, a)| MonkeyAction::Restart != *a));
+
+        // The cap is never exceeded
+        let mut m = Monkey::new(7, 600, 2, candidates.clone());
+        let mut down = BTreeSet::new();
+        for _ in 0..100 {
+            if let Some((id, a)) = m.next(0, &BTreeSet::from([5])) {
+                assert_ne!(5, id);
+                if MonkeyAction::Restart != a {
+                    down.insert(id);
+                }
+            }
+        }
+        assert_eq!(1, down.len());
+        assert!(m.due(0).is_empty());
+        assert_eq!(down.into_iter().collect::<Vec<_>>(), m.release_all());
+        assert!(m.next(0, &BTreeSet::from([4, 5])).is_none());
+    }
+}
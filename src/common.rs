@@ -1,10 +1,19 @@
-use chaindev::{beacon_based::common::NodePorts, NodeID};
+use crate::{
+    bootnode::Bootnode,
+    chaos::Netem,
+    external::ExternalNetwork,
+    hooks::Hooks,
+    p2p,
+    process::{ProcessBackend, DEFAULT_STOP_TIMEOUT_SECS},
+    schema::SCHEMA_VERSION,
+};
+use chaindev::{beacon_based::common::NodePorts, EnvName, NodeID};
 use ruc::*;
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 use std::{
     collections::{BTreeMap, BTreeSet},
-    env, thread,
+    env, fmt, fs, thread,
 };
 
 pub const EL_DIR: &str = "el";
@@ -17,12 +26,176 @@ pub const CL_VC_LOG_NAME: &str = "cl.vc.log";
 
 pub const MGMT_LOG_NAME: &str = "mgmt.log";
 
-// pub const EL_ERR_NAME: &str = "el.err";
-// pub const CL_BN_ERR_NAME: &str = "cl.bn.err";
-// pub const CL_VC_ERR_NAME: &str = "cl.vc.err";
+pub const EL_ERR_NAME: &str = "el.err";
+pub const CL_BN_ERR_NAME: &str = "cl.bn.err";
+pub const CL_VC_ERR_NAME: &str = "cl.vc.err";
+
+/// The captured stdout/stderr of the EL, CL BN and CL VC processes,
+/// they are in the node home
+pub const ERR_FILES: [&str; 3] = [EL_ERR_NAME, CL_BN_ERR_NAME, CL_VC_ERR_NAME];
+
+/// How many lines of each `ERR_FILES` are shown in failure reports
+pub const ERR_TAIL_LINES: usize = 20;
 
 pub type MnemonicWords = String;
 
+/// Errors of converting the cmdline arguments into an ENV operation,
+/// all of them are caused by user inputs,
+/// so a one-line message is enough, no backtrace is needed.
+#[derive(Debug)]
+pub enum CfgError {
+    /// The value of `--genesis-data-pre-created`
+    InvalidGenesisData(String),
+    /// A path of `--genesis-data-pre-created`
+    GenesisDataNotAccessible(String),
+    /// Neither `--hosts` nor the $EXP_DDEV_HOSTS[_JSON] is set
+    NoHosts,
+    /// A host expression, a JSON cfg path, or an env var name
+    InvalidHosts(String),
+    /// The value of `--host-addr`
+    InvalidHostAddr(String),
+    /// <the value of `--nodes`, reason>
+    InvalidNodes(String, String),
+    /// <ENV name, reason>
+    InvalidEnv(EnvName, String),
+    /// The reason, of `--external-network` and the related options
+    InvalidExternalNetwork(String),
+    /// A label, a note, or a name of them
+    InvalidLabel(String),
+}
+
+impl CfgError {
+    /// The process exit code, `1` is used by the failures of operations,
+    /// and `2` is used by clap for its own usage errors.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::InvalidGenesisData(_) | Self::GenesisDataNotAccessible(_) => 3,
+            Self::NoHosts | Self::InvalidHosts(_) | Self::InvalidHostAddr(_) => 4,
+            Self::InvalidNodes(..) => 5,
+            Self::InvalidEnv(..) => 6,
+            Self::InvalidLabel(_) => 7,
+            Self::InvalidExternalNetwork(_) => 8,
+        }
+    }
+}
+
+impl fmt::Display for CfgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidGenesisData(v) => write!(
+                f,
+                "Invalid `--genesis-data-pre-created`: '{v}', expected format: '/PATH/TO/genesis.tar.gz+/PATH/TO/vcdata.tar.gz'"
+            ),
+            Self::GenesisDataNotAccessible(p) => {
+                write!(f, "The pre-created genesis data is not accessible: '{p}'")
+            }
+            Self::NoHosts => write!(
+                f,
+                "No hosts registered! Use `--hosts` or $EXP_DDEV_HOSTS to set"
+            ),
+            Self::InvalidHosts(h) => write!(
+                f,
+                "Invalid hosts: '{h}', neither a readable JSON cfg nor a valid host expression, see `exp ddev create --help`"
+            ),
+            Self::InvalidHostAddr(a) => write!(
+                f,
+                "Invalid host address: '{a}', expected format: '[local_network_id%]local_ip[|ext_ip]'"
+            ),
+            Self::InvalidNodes(n, reason) => write!(f, "Invalid nodes: '{n}', {reason}"),
+            Self::InvalidEnv(en, reason) => write!(f, "ENV '{en}': {reason}"),
+            Self::InvalidExternalNetwork(reason) => {
+                write!(f, "Invalid external network: {reason}")
+            }
+            Self::InvalidLabel(l) => write!(
+                f,
+                "Invalid label or note: '{l}', names can only contain ASCII letters, digits and '_-.:/'"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CfgError {}
+
+/// Like `parse_nodes`, but for the values of the cmdline arguments
+pub fn parse_nodes_arg(
+    nodes_expr: &str,
+) -> std::result::Result<BTreeSet<NodeID>, CfgError> {
+    parse_nodes(nodes_expr).map_err(|_| {
+        CfgError::InvalidNodes(
+            nodes_expr.to_owned(),
+            "expected comma separated node IDs or ID ranges, e.g., '3,2,4-9'".to_owned(),
+        )
+    })
+}
+
+/// A duration like '90s', '30m', '2h' and '7d', in seconds
+pub fn parse_duration(s: &str) -> std::result::Result<u64, String> {
+    let s = s.trim();
+    let (n, unit) = s.split_at(s.len().saturating_sub(1));
+    let unit = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => return Err(format!("invalid unit in '{s}', expected s/m/h/d")),
+    };
+    n.parse::<u64>()
+        .map(|n| n * unit)
+        .map_err(|e| format!("invalid number in '{s}': {e}"))
+}
+
+/// Split the items of `label add/remove` into labels and notes,
+/// `key=value` items are notes, and only names are accepted when removing
+pub fn parse_label_items(
+    items: &[String],
+    remove: bool,
+) -> std::result::Result<(BTreeSet<String>, BTreeMap<String, String>), CfgError> {
+    let valid = |name: &str| {
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "_-.:/".contains(c))
+    };
+
+    let mut labels = BTreeSet::new();
+    let mut notes = BTreeMap::new();
+
+    for i in items.iter().map(|i| i.trim()) {
+        match i.split_once('=') {
+            Some((k, v)) if !remove && valid(k.trim()) && !v.trim().is_empty() => {
+                notes.insert(k.trim().to_owned(), v.trim().to_owned());
+            }
+            None if valid(i) => {
+                labels.insert(i.to_owned());
+            }
+            _ => return Err(CfgError::InvalidLabel(i.to_owned())),
+        }
+    }
+
+    Ok((labels, notes))
+}
+
+/// Parse the value of `--genesis-data-pre-created`
+pub fn parse_genesis_data_arg(
+    v: Option<String>,
+) -> std::result::Result<(Option<String>, Option<String>), CfgError> {
+    let Some(s) = v else {
+        return Ok((None, None));
+    };
+
+    let paths = s.split('+').collect::<Vec<_>>();
+    if 2 != paths.len() || paths.iter().any(|p| p.is_empty()) {
+        return Err(CfgError::InvalidGenesisData(s));
+    }
+    for p in paths.iter() {
+        if std::fs::metadata(p).is_err() {
+            return Err(CfgError::GenesisDataNotAccessible(p.to_string()));
+        }
+    }
+
+    Ok((Some(paths[0].to_owned()), Some(paths[1].to_owned())))
+}
+
 pub fn json_el_kind(v: &Option<JsonValue>) -> Result<Eth1Kind> {
     if let Some(v) = v {
         serde_json::from_value::<NodeCustomData>(v.clone())
@@ -33,6 +206,90 @@ pub fn json_el_kind(v: &Option<JsonValue>) -> Result<Eth1Kind> {
     }
 }
 
+pub fn json_labels(v: &Option<JsonValue>) -> Result<BTreeSet<String>> {
+    if let Some(v) = v {
+        serde_json::from_value::<NodeCustomData>(v.clone())
+            .c(d!())
+            .map(|d| d.labels)
+    } else {
+        Ok(BTreeSet::new())
+    }
+}
+
+pub fn json_notes(v: &Option<JsonValue>) -> Result<BTreeMap<String, String>> {
+    if let Some(v) = v {
+        serde_json::from_value::<NodeCustomData>(v.clone())
+            .c(d!())
+            .map(|d| d.notes)
+    } else {
+        Ok(BTreeMap::new())
+    }
+}
+
+pub fn json_bootnode(v: &Option<JsonValue>) -> Result<Option<Bootnode>> {
+    if let Some(v) = v {
+        serde_json::from_value::<NodeCustomData>(v.clone())
+            .c(d!())
+            .map(|d| d.bootnode)
+    } else {
+        Ok(None)
+    }
+}
+
+pub fn json_bootnode_set(jv: &mut Option<JsonValue>, b: Bootnode) -> Result<()> {
+    let mut v = if let Some(v) = jv {
+        serde_json::from_value::<NodeCustomData>(v.clone()).c(d!())?
+    } else {
+        NodeCustomData::default()
+    };
+    v.bootnode = Some(b);
+    jv.replace(v.to_json_value());
+    Ok(())
+}
+
+/// Add labels and notes, or remove labels and notes by names
+pub fn json_labels_update(
+    jv: &mut Option<JsonValue>,
+    remove: bool,
+    labels: &BTreeSet<String>,
+    notes: &BTreeMap<String, String>,
+) -> Result<()> {
+    let mut v = if let Some(v) = jv {
+        serde_json::from_value::<NodeCustomData>(v.clone()).c(d!())?
+    } else {
+        NodeCustomData::default()
+    };
+
+    if remove {
+        v.labels.retain(|l| !labels.contains(l));
+        v.notes.retain(|k, _| !labels.contains(k));
+    } else {
+        v.labels.extend(labels.iter().cloned());
+        v.notes
+            .extend(notes.iter().map(|(k, v)| (k.to_owned(), v.to_owned())));
+    }
+
+    jv.replace(v.to_json_value());
+
+    Ok(())
+}
+
+/// Carry the labels and the notes of `src` over to `dst`
+pub fn json_labels_copy(
+    dst: &mut Option<JsonValue>,
+    src: &Option<JsonValue>,
+) -> Result<()> {
+    let mut v = if let Some(v) = dst {
+        serde_json::from_value::<NodeCustomData>(v.clone()).c(d!())?
+    } else {
+        NodeCustomData::default()
+    };
+    v.labels = json_labels(src).c(d!())?;
+    v.notes = json_notes(src).c(d!())?;
+    dst.replace(v.to_json_value());
+    Ok(())
+}
+
 pub fn json_el_kind_set(jv: &mut Option<JsonValue>, k: Eth1Kind) -> Result<()> {
     let v = if let Some(v) = jv {
         let mut v = serde_json::from_value::<NodeCustomData>(v.clone()).c(d!())?;
@@ -60,8 +317,8 @@ pub fn json_deposits_append(
         v
     } else {
         NodeCustomData {
-            el_kind: Eth1Kind::default(),
             deposits,
+            ..Default::default()
         }
     };
 
@@ -112,26 +369,88 @@ pub fn json_el_kind_matched(v: &Option<JsonValue>, k: Eth1Kind) -> Result<bool>
     json_el_kind(v).map(|i| i == k).c(d!())
 }
 
+/// How many validators have been deposited on the node
+pub fn json_deposits_cnt(v: &Option<JsonValue>) -> Result<usize> {
+    if let Some(v) = v {
+        serde_json::from_value::<NodeCustomData>(v.clone())
+            .c(d!())
+            .map(|d| d.deposits.values().map(|idxs| idxs.len()).sum())
+    } else {
+        Ok(0)
+    }
+}
+
+/// The legacy output of `show`: without the genesis data and other internal fields,
+/// and the deposited validators of each node are replaced by their numbers
+pub fn env_show_json(mut env: JsonValue) -> Result<JsonValue> {
+    let obj = env.as_object_mut().c(d!("not an object"))?;
+    obj.remove("node_cmdline_generator");
+
+    let meta = obj
+        .get_mut("meta")
+        .and_then(|m| m.as_object_mut())
+        .c(d!("no meta"))?;
+
+    meta.remove("genesis");
+    meta.remove("genesis_vkeys");
+    meta.remove("genesis_mnemonic_words");
+    meta.remove("genesis_validator_num");
+    meta.remove("nodes_should_be_online");
+    meta.remove("next_node_id");
+
+    for field in ["fuhrer_nodes", "nodes"] {
+        let Some(nodes) = meta.get_mut(field).and_then(|n| n.as_object_mut()) else {
+            continue;
+        };
+        for (id, n) in nodes.iter_mut() {
+            let Some(data) = n.get_mut("custom_data").filter(|d| !d.is_null()) else {
+                continue;
+            };
+            let deposits = serde_json::from_value::<NodeCustomData>(data.clone())
+                .c(d!("node {}", id))?
+                .deposits
+                .into_iter()
+                .map(|(k, idxs)| (k, idxs.len()))
+                .collect::<BTreeMap<_, _>>();
+            data["deposits"] = serde_json::to_value(deposits).c(d!())?;
+        }
+    }
+
+    Ok(env)
+}
+
 #[derive(Clone, Default, Debug, Serialize, Deserialize)]
 pub struct NodeCustomData {
     pub el_kind: Eth1Kind,
 
     /// Mnemonic => deposited validator number
     pub deposits: BTreeMap<MnemonicWords, BTreeSet<u16>>,
+
+    /// Used by the `label=` node selector
+    #[serde(default)]
+    pub labels: BTreeSet<String>,
+
+    /// Free-form key/value metadata
+    #[serde(default)]
+    pub notes: BTreeMap<String, String>,
+
+    /// Set if the node is a dedicated bootnode, see `crate::bootnode`
+    #[serde(default)]
+    pub bootnode: Option<Bootnode>,
 }
 
 impl NodeCustomData {
     pub fn new_with_geth() -> Self {
         Self {
             el_kind: Eth1Kind::Geth,
-            deposits: map! {B},
+            ..Default::default()
         }
     }
 
     pub fn new_with_reth() -> Self {
         Self {
             el_kind: Eth1Kind::Reth,
-            deposits: map! {B},
+            ..Default::default()
         }
     }
 
@@ -140,15 +459,20 @@ impl NodeCustomData {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Eth1Kind {
+    #[default]
     Geth = 0,
     Reth = 1,
 }
 
-impl Default for Eth1Kind {
-    fn default() -> Self {
-        Self::Geth
+impl fmt::Display for Eth1Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Geth => "geth",
+            Self::Reth => "reth",
+        };
+        write!(f, "{s}")
     }
 }
 
@@ -163,6 +487,29 @@ pub struct CustomInfo {
     pub el_geth_bin: String,
     pub el_reth_bin: String,
     pub cl_bin: String,
+    #[serde(default)]
+    pub process_backend: ProcessBackend,
+    #[serde(default = "default_stop_timeout_secs")]
+    pub stop_timeout_secs: u64,
+    #[serde(default)]
+    pub hooks: Hooks,
+    /// `0` for the ENVs created before versioning, see `crate::schema`
+    #[serde(default)]
+    pub schema_version: u32,
+    /// Set if the ENV is attached to an external network, see `crate::external`
+    #[serde(default)]
+    pub external: Option<ExternalNetwork>,
+    /// The p2p keys and the JWT secrets of the nodes are derived from it,
+    /// see `crate::p2p`, empty in the ENVs created by older versions
+    #[serde(default)]
+    pub identity_seed: String,
+    /// <node ID, the active impairments>, see `crate::chaos`
+    #[serde(default)]
+    pub netem: BTreeMap<NodeID, Netem>,
+}
+
+fn default_stop_timeout_secs() -> u64 {
+    DEFAULT_STOP_TIMEOUT_SECS
 }
 
 impl Default for CustomInfo {
@@ -171,6 +518,13 @@ impl Default for CustomInfo {
             el_geth_bin: String::from("geth"),
             el_reth_bin: String::from("reth"),
             cl_bin: String::from("lighthouse"),
+            process_backend: ProcessBackend::Nohup,
+            stop_timeout_secs: DEFAULT_STOP_TIMEOUT_SECS,
+            hooks: Hooks::default(),
+            schema_version: SCHEMA_VERSION,
+            external: None,
+            identity_seed: p2p::new_seed(),
+            netem: Default::default(),
         }
     }
 }
@@ -392,6 +746,170 @@ pub fn cl_get_boot_nodes(
     Ok((ret.0, ret.1 .0.join(","), ret.1 .1.join(",")))
 }
 
+/// `DEPOSIT_CONTRACT_ADDRESS` in the `config.yaml` of the genesis dir
+pub fn genesis_deposit_contract(genesis_dir: &str) -> Result<String> {
+    let p = format!("{genesis_dir}/config.yaml");
+    let cfg = fs::read_to_string(&p)
+        .c(d!(p))
+        .and_then(|s| serde_yml::from_str::<serde_yml::Value>(&s).c(d!()))?;
+    cfg["DEPOSIT_CONTRACT_ADDRESS"]
+        .as_str()
+        .map(|s| s.to_owned())
+        .c(d!("DEPOSIT_CONTRACT_ADDRESS not found"))
+}
+
+/// `config.chainId` in the `genesis.json` of the genesis dir
+pub fn genesis_chain_id(genesis_dir: &str) -> Result<u64> {
+    let p = format!("{genesis_dir}/genesis.json");
+    fs::read(&p)
+        .c(d!(p))
+        .and_then(|g| serde_json::from_slice::<JsonValue>(&g).c(d!()))
+        .and_then(|g| g["config"]["chainId"].as_u64().c(d!("chainId not found")))
+}
+
+////////////////////////////////////////////////////////////////
+// Outputs of the `--dry-run` mode
+////////////////////////////////////////////////////////////////
+
+/// A script that would be executed on the target
+pub fn dry_run_script(target: &str, script: &str) {
+    println!("\x1b[33;1m# [{target}] script:\x1b[0m");
+    println!("{}\n", script.trim());
+}
+
+/// A file that would be transferred to the target
+pub fn dry_run_transfer(target: &str, from: &str, to: &str) {
+    println!("\x1b[33;1m# [{target}] transfer:\x1b[0m {from} => {to}\n");
+}
+
+/// Anything else that would be done
+pub fn dry_run_note(target: &str, note: &str) {
+    println!("\x1b[33;1m# [{target}]\x1b[0m {note}\n");
+}
+
+/// How chaindev runs the scripts of a node
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScriptRunner {
+    /// `exp dev`, the start script is spawned by a local bash
+    Local,
+    /// `exp ddev`, every script goes through the remote shell
+    Remote,
+}
+
+impl ScriptRunner {
+    /// The script exactly as it is run, `start`: if it starts the node
+    pub fn wrap(self, script: &str, start: bool) -> String {
+        match self {
+            Self::Local if start => format!("ulimit -n 102400; {script}"),
+            Self::Local => script.to_owned(),
+            Self::Remote => format!("ulimit -n 100000 >/dev/null 2>&1;{script}"),
+        }
+    }
+}
+
+/// The scripts of starting a node, `cnt_running` decides if the node is running already
+pub fn dry_run_start_node(
+    target: &str,
+    runner: ScriptRunner,
+    cnt_running: &str,
+    start: &str,
+) {
+    dry_run_script(target, &runner.wrap(cnt_running, false));
+    dry_run_note(
+        target,
+        "the node will be stopped first if 1~2 processes are detected above",
+    );
+    dry_run_script(target, &runner.wrap(start, true));
+}
+
+/// The latest block number of an EL node
+pub fn el_get_block_number(rpc_endpoint: &str) -> Result<u64> {
+    let body = r#"{"jsonrpc":"2.0","method":"eth_blockNumber","params":[],"id":1}"#;
+    ruc::http::post(
+        rpc_endpoint,
+        body.as_bytes(),
+        Some(&[("Content-Type", "application/json")]),
+    )
+    .c(d!())
+    .and_then(|(_code, resp)| serde_json::from_slice::<JsonValue>(&resp).c(d!()))
+    .and_then(|v| {
+        let h = v["result"].as_str().c(d!())?;
+        u64::from_str_radix(h.trim_start_matches("0x"), 16).c(d!())
+    })
+}
+
+/// Return: "(<head slot>, <is syncing>)"
+pub fn cl_get_sync_status(rpc_endpoint: &str) -> Result<(u64, bool)> {
+    ruc::http::get(
+        &format!("{rpc_endpoint}/eth/v1/node/syncing"),
+        Some(&[("Content-Type", "application/json")]),
+    )
+    .c(d!())
+    .and_then(|(_code, resp)| serde_json::from_slice::<JsonValue>(&resp).c(d!()))
+    .and_then(|v| {
+        let slot = v["data"]["head_slot"]
+            .as_str()
+            .c(d!())?
+            .parse::<u64>()
+            .c(d!())?;
+        let syncing = v["data"]["is_syncing"].as_bool().c(d!())?;
+        Ok((slot, syncing))
+    })
+}
+
+/// Return: "(<the enode of the node>, <the enodes of its peers>)"
+pub fn el_get_peers(rpc_endpoint: &str) -> Result<(String, Vec<String>)> {
+    let call = |method: &str| {
+        let body =
+            format!(r#"{{"jsonrpc":"2.0","method":"{method}","params":[],"id":1}}"#);
+        ruc::http::post(
+            rpc_endpoint,
+            body.as_bytes(),
+            Some(&[("Content-Type", "application/json")]),
+        )
+        .c(d!())
+        .and_then(|(_code, resp)| serde_json::from_slice::<JsonValue>(&resp).c(d!()))
+    };
+
+    let enode = call("admin_nodeInfo")
+        .and_then(|v| v["result"]["enode"].as_str().c(d!()).map(|s| s.to_owned()))?;
+    let peers = call("admin_peers").and_then(|v| {
+        v["result"]
+            .as_array()
+            .c(d!())?
+            .iter()
+            .map(|p| p["enode"].as_str().c(d!()).map(|s| s.to_owned()))
+            .collect::<Result<Vec<_>>>()
+    })?;
+
+    Ok((enode, peers))
+}
+
+/// Return: "(<the peer ID of the node>, <the peer IDs of its connected peers>)"
+pub fn cl_get_peers(rpc_endpoint: &str) -> Result<(String, Vec<String>)> {
+    let get = |path: &str| {
+        ruc::http::get(
+            &format!("{rpc_endpoint}{path}"),
+            Some(&[("Content-Type", "application/json")]),
+        )
+        .c(d!())
+        .and_then(|(_code, resp)| serde_json::from_slice::<JsonValue>(&resp).c(d!()))
+    };
+
+    let peer_id = get("/eth/v1/node/identity")
+        .and_then(|v| v["data"]["peer_id"].as_str().c(d!()).map(|s| s.to_owned()))?;
+    let peers = get("/eth/v1/node/peers?state=connected").and_then(|v| {
+        v["data"]
+            .as_array()
+            .c(d!())?
+            .iter()
+            .map(|p| p["peer_id"].as_str().c(d!()).map(|s| s.to_owned()))
+            .collect::<Result<Vec<_>>>()
+    })?;
+
+    Ok((peer_id, peers))
+}
+
 pub fn node_sync_from_genesis() -> bool {
     env::var("EXPCHAIN_NODE_SYNC_FROM_GENESIS").is_ok()
 }
@@ -407,10 +925,6 @@ pub fn new_sb_runtime() -> sb::runtime::Runtime {
 ////////////////////////////////////////////////////////////////
 ////////////////////////////////////////////////////////////////
 
-pub fn parse_nodes_to_vec(nodes_expr: &str) -> Result<Vec<NodeID>> {
-    parse_nodes(nodes_expr).map(|ns| ns.into_iter().collect())
-}
-
 pub fn parse_nodes(nodes_expr: &str) -> Result<BTreeSet<NodeID>> {
     if nodes_expr.is_empty() {
         return Err(eg!("The nodes expression is empty!"));
@@ -440,61 +954,104 @@ pub fn parse_nodes(nodes_expr: &str) -> Result<BTreeSet<NodeID>> {
 #[macro_export]
 macro_rules! def_select_nodes {
     () => {
+        // Resolve a node selector against the ENV,
+        // the fuhrer nodes are candidates only if `include_fuhrer_nodes` is set
+        fn env_select_nodes(
+            env: &SysEnv<CustomInfo, Ports, CmdGenerator>,
+            sel: &$crate::selector::Selector,
+            include_fuhrer_nodes: bool,
+        ) -> Result<BTreeSet<NodeID>> {
+            let failed = if sel.needs_failed() {
+                env_failed_nodes(env).c(d!())?
+            } else {
+                BTreeSet::new()
+            };
+
+            let mut nodes = env.meta.nodes.values().collect::<Vec<_>>();
+            if include_fuhrer_nodes {
+                nodes.extend(env.meta.fuhrers.values());
+            }
+            let facts = nodes
+                .into_iter()
+                .map(|n| node_facts(env, n).c(d!()))
+                .collect::<Result<Vec<_>>>()?;
+
+            Ok(sel.select(&facts, &failed))
+        }
+
+        // For the operations that do not accept fuhrer nodes,
+        // fuhrer or non-existing IDs written explicitly are treated as errors
+        fn env_select_non_fuhrer_nodes(
+            env: &SysEnv<CustomInfo, Ports, CmdGenerator>,
+            nodes_expr: &str,
+        ) -> Result<Vec<Node<Ports>>> {
+            let sel = nodes_expr
+                .parse::<$crate::selector::Selector>()
+                .c(d!(nodes_expr))?;
+
+            for id in sel.explicit_ids() {
+                if env.meta.fuhrers.contains_key(&id) {
+                    return Err(eg!(
+                        "Fuhrer node(id: {}) can not be selected by this operation",
+                        id
+                    ));
+                }
+                if !env.meta.nodes.contains_key(&id) {
+                    return Err(eg!("The node(id: {}) does not exist", id));
+                }
+            }
+
+            env_select_nodes(env, &sel, false).c(d!()).map(|ids| {
+                ids.iter()
+                    .map(|id| env.meta.nodes.get(id).cloned().unwrap())
+                    .collect()
+            })
+        }
+
         fn select_nodes(
             env_name: &chaindev::EnvName,
             nodes_expr: Option<&str>,
             filter_geth: bool,
             filter_reth: bool,
             include_fuhrer_nodes: bool,
-        ) -> Result<Option<std::collections::BTreeSet<chaindev::NodeID>>> {
+        ) -> std::result::Result<Option<BTreeSet<NodeID>>, $crate::common::CfgError> {
+            use $crate::{common::CfgError, selector::Selector};
+
             if nodes_expr.is_none() && !filter_geth && !filter_reth {
-                Ok(None)
-            } else if nodes_expr.is_some() && !filter_geth && !filter_reth {
-                $crate::common::parse_nodes(nodes_expr.unwrap())
-                    .map(Some)
-                    .c(d!())
-            } else {
-                let env = load_sysenv(env_name).c(d!())?;
-                let get_ids = |nodes: &std::collections::BTreeMap<
-                    chaindev::NodeID,
-                    Node<Ports>, /*USE RELATIVE PATH*/
-                >| {
-                    nodes
-                        .values()
-                        .filter(|n| {
-                            if filter_geth && filter_reth {
-                                true
-                            } else if filter_geth {
-                                pnk!(json_el_kind_matched(
-                                    &n.custom_data,
-                                    Eth1Kind::Geth
-                                ))
-                            } else if filter_reth {
-                                pnk!(json_el_kind_matched(
-                                    &n.custom_data,
-                                    Eth1Kind::Reth
-                                ))
-                            } else {
-                                true
-                            }
-                        })
-                        .map(|n| n.id)
-                        .collect::<BTreeSet<_>>()
-                };
-
-                let mut ids = get_ids(&env.meta.nodes);
-
-                if include_fuhrer_nodes {
-                    ids.append(&mut get_ids(&env.meta.fuhrers));
-                }
+                return Ok(None);
+            }
 
-                if let Some(expr) = nodes_expr {
-                    let parsed = $crate::common::parse_nodes(expr).c(d!())?;
-                    ids = ids.intersection(&parsed).copied().collect();
+            let sel = if let Some(expr) = nodes_expr {
+                expr.parse::<Selector>().map_err(|e| {
+                    CfgError::InvalidNodes(expr.to_owned(), e.get_lowest_msg())
+                })?
+            } else {
+                Selector::All
+            };
+
+            let el = |k| Selector::And(Box::new(sel.clone()), Box::new(Selector::El(k)));
+            let sel = match (filter_geth, filter_reth) {
+                (true, false) => el(Eth1Kind::Geth),
+                (false, true) => el(Eth1Kind::Reth),
+                _ => {
+                    // No need to load the ENV
+                    if let Some(ids) = sel.as_ids() {
+                        return Ok(Some(ids.clone()));
+                    }
+                    sel
                 }
-
-                Ok(Some(ids))
-            }
+            };
+
+            let env = load_sysenv(env_name).map_err(|_| {
+                CfgError::InvalidEnv(
+                    env_name.clone(),
+                    "failed to load, use `list` to check the existing ENVs".to_owned(),
+                )
+            })?;
+
+            env_select_nodes(&env, &sel, include_fuhrer_nodes)
+                .map(Some)
+                .map_err(|e| CfgError::InvalidEnv(env_name.clone(), e.get_lowest_msg()))
         }
     };
 }
@@ -502,13 +1059,13 @@ macro_rules! def_select_nodes {
 #[macro_export]
 macro_rules! select_nodes_by_el_kind {
     ($nodes_expr: expr, $filter_geth: expr, $filter_reth: expr, $env_name: expr, $include_fuhrer_nodes: expr) => {{
-        pnk!(select_nodes(
+        select_nodes(
             &$env_name,
             $nodes_expr.as_deref(),
             $filter_geth,
             $filter_reth,
-            $include_fuhrer_nodes
-        ))
+            $include_fuhrer_nodes,
+        )?
     }};
     ($nodes_expr: expr, $filter_geth: expr, $filter_reth: expr, $env_name: expr) => {{
         select_nodes_by_el_kind!(
@@ -4,18 +4,37 @@
 //! The distributed version of `exp dev`.
 //!
 
+mod spec;
+
 use crate::{
-    cfg::{DDevCfg, DDevOp},
+    audit::{self, HistoryFilter},
+    bootnode::{self, Bootnode},
+    bundle,
+    cfg::{ChaosOp, DDevCfg, DDevOp, HookOp, LabelOp, SnapshotOp},
+    chaos::{self, MaxDown, Monkey, Netem, P2pNode, Partition},
     common::*,
     def_select_nodes,
+    external::ExternalNetwork,
+    hooks::OpEvent,
+    join_kit::JoinKit,
+    lock::{lock_path, EnvLock},
+    output::*,
+    p2p::{self, NodeIdentity},
     pos::{create_mnemonic_words, deposit::do_deposit, exit::exit_by_mnemonic},
+    process::{self, ProcessBackend, SystemdLimits},
+    schema::{self, SCHEMA_VERSION},
     select_nodes_by_el_kind,
+    selector::{NodeFacts, Selector},
+    snapshot,
+    supervise::{Backoff, RestartEvent},
+    topology::{self, Topology},
 };
 use alloy::{
     primitives::{hex, Address},
     signers::k256::ecdsa::SigningKey,
 };
 use chaindev::{
+    beacon_based::common::NodePorts,
     beacon_ddev::{
         remote::{
             collect_files_from_nodes as env_collect_files,
@@ -25,7 +44,7 @@ use chaindev::{
         Op, NODE_HOME_GENESIS_DIR_DST, NODE_HOME_GENESIS_DST, NODE_HOME_VCDATA_DST,
     },
     common::{
-        hosts::{HostAddr, HostExpression, Hosts},
+        hosts::{HostAddr, HostExpression, HostMeta, Hosts},
         remote::Remote,
         NodeCmdGenerator,
     },
@@ -33,42 +52,371 @@ use chaindev::{
 };
 use ruc::{algo::rand::rand_jwt, cmd, *};
 use serde::{Deserialize, Serialize};
-use serde_json::Value as JsonValue;
+use spec::{EnvSpec, NodeGroup, NodeState, Plan};
 use std::{
-    collections::{BTreeSet, HashSet},
+    collections::{BTreeMap, BTreeSet, HashSet},
     env, fs, mem,
+    path::PathBuf,
     str::FromStr,
+    thread,
 };
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct EnvCfg {
     sys_cfg: SysCfg<CustomInfo, Ports, ExtraOp>,
+    dry_run: bool,
+    wait: bool,
 }
 
 def_select_nodes!();
 
 impl EnvCfg {
     pub fn exec(&self) -> Result<()> {
-        self.sys_cfg
+        if self.dry_run {
+            return self.exec_dry_run().c(d!());
+        }
+
+        schema::ensure("ddev", &self.sys_cfg.name, self.wait).c(d!())?;
+
+        let Some(operation) = self.operation() else {
+            return self.exec_op();
+        };
+
+        let en = &self.sys_cfg.name;
+
+        // Held until the audit log and the hooks are done
+        let _lock =
+            EnvLock::acquire(&lock_path("ddev", en), operation, self.wait).c(d!())?;
+
+        let pre = load_sysenv(en).ok();
+        let res = self.exec_op();
+        let post = load_sysenv(en).ok();
+
+        // The ENV does not exist before `create` or after `destroy`
+        let Some(env) = post.as_ref().or(pre.as_ref()) else {
+            return res;
+        };
+
+        let nodes = self.affected_nodes(pre.as_ref(), post.as_ref());
+        let ev = OpEvent::new(en, operation, nodes, &res);
+
+        if let Err(e) = audit::append(&env.meta.home, &ev) {
+            eprintln!("Audit log: {}", e.get_lowest_msg());
+        }
+
+        let hooks = &env.meta.custom_data.hooks;
+        if !hooks.is_empty() {
+            hooks.fire(&ev);
+        }
+
+        res
+    }
+
+    fn exec_op(&self) -> Result<()> {
+        let res = match &self.sys_cfg.op {
+            Op::Create { opts } if opts.custom_data.external.is_some() => {
+                self.exec_create_external(opts).c(d!())
+            }
+            _ => self.sys_cfg.exec(CmdGenerator).c(d!()),
+        };
+
+        res.and_then(|_| match &self.sys_cfg.op {
+            Op::Create { opts: _ } => {
+                record_genesis_deposits(&self.sys_cfg.name).c(d!())
+            }
+            Op::PushNodes { .. } | Op::MigrateNodes { .. } => {
+                record_bootnodes(&self.sys_cfg.name).c(d!())
+            }
+            _ => Ok(()),
+        })
+    }
+
+    // The network config of the external network is packed as the genesis data
+    fn exec_create_external(&self, opts: &SysOpts<CustomInfo>) -> Result<()> {
+        let ext = opts.custom_data.external.as_ref().c(d!())?;
+        let tmp = format!("/tmp/exp_external_{}_{}", self.sys_cfg.name, ts!());
+
+        let res = ext.pack_genesis(&tmp).c(d!()).and_then(|(genesis, vkeys)| {
+            let mut opts = opts.clone();
+            opts.genesis_tgz_path = Some(genesis);
+            opts.genesis_vkeys_tgz_path = Some(vkeys);
+            SysCfg {
+                name: self.sys_cfg.name.clone(),
+                op: Op::<CustomInfo, Ports, ExtraOp>::Create { opts },
+            }
             .exec(CmdGenerator)
             .c(d!())
-            .and_then(|_| match &self.sys_cfg.op {
-                Op::Create { opts: _ } => {
-                    let mut env = load_sysenv(&self.sys_cfg.name).c(d!())?;
-                    let fuhrer = env.meta.fuhrers.values_mut().next().unwrap();
-                    let map = map!{B
-                        env.meta.genesis_mnemonic_words.clone() => (0..env.meta.genesis_validator_num).collect()
+        });
+        omit!(fs::remove_dir_all(&tmp));
+
+        res
+    }
+
+    // The name of a mutating operation, `None` for the read-only ones
+    fn operation(&self) -> Option<&'static str> {
+        let name = match &self.sys_cfg.op {
+            Op::Create { .. } => "create",
+            Op::Destroy { .. } => "destroy",
+            Op::PushNodes { .. } => "push_nodes",
+            Op::MigrateNodes { .. } => "migrate_nodes",
+            Op::KickNodes { .. } => "kick_nodes",
+            Op::PushHosts { .. } => "push_hosts",
+            Op::KickHosts { .. } => "kick_hosts",
+            Op::Protect => "protect",
+            Op::Unprotect => "unprotect",
+            Op::Start { .. } => "start",
+            Op::Stop { .. } => "stop",
+            Op::Restart { .. } => "restart",
+            Op::HostPutFile { .. } => "host_put_file",
+            Op::HostExec { .. } => "host_exec",
+            Op::Custom(op) => match op {
+                ExtraOp::Deposit { .. } => "deposit",
+                ExtraOp::ValidatorExit { .. } => "validator_exit",
+                ExtraOp::SwitchELToGeth { .. } => "switch_el_to_geth",
+                ExtraOp::SwitchELToReth { .. } => "switch_el_to_reth",
+                ExtraOp::Label { .. } => "label",
+                // Only listing them
+                ExtraOp::Hook {
+                    webhooks, scripts, ..
+                } if webhooks.is_empty() && scripts.is_empty() => return None,
+                ExtraOp::Hook { .. } => "hook",
+                ExtraOp::SnapshotCreate { .. } => "snapshot_create",
+                ExtraOp::SnapshotRestore { .. } => "snapshot_restore",
+                ExtraOp::SnapshotDelete { .. } => "snapshot_delete",
+                ExtraOp::ChaosPartition { .. } => "chaos_partition",
+                ExtraOp::ChaosNetem { .. } => "chaos_netem",
+                ExtraOp::ChaosClear => "chaos_clear",
+                ExtraOp::Clone { .. } => "clone",
+                ExtraOp::Import { .. } => "import",
+                ExtraOp::Apply { .. } => "apply",
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(name)
+    }
+
+    // The nodes that are affected by the operation, best effort
+    fn affected_nodes(
+        &self,
+        pre: Option<&SysEnv<CustomInfo, Ports, CmdGenerator>>,
+        post: Option<&SysEnv<CustomInfo, Ports, CmdGenerator>>,
+    ) -> Option<BTreeSet<NodeID>> {
+        let ids = |env: Option<&SysEnv<CustomInfo, Ports, CmdGenerator>>| {
+            env.map(|env| {
+                env.meta
+                    .nodes
+                    .keys()
+                    .chain(env.meta.fuhrers.keys())
+                    .copied()
+                    .collect::<BTreeSet<_>>()
+            })
+        };
+        match &self.sys_cfg.op {
+            Op::Start { nodes, .. }
+            | Op::Stop { nodes, .. }
+            | Op::Restart { nodes, .. }
+            | Op::KickNodes { nodes, .. }
+                if nodes.is_some() =>
+            {
+                nodes.clone()
+            }
+            Op::MigrateNodes { nodes, .. } => Some(nodes.clone()),
+            Op::PushNodes { .. }
+            | Op::KickNodes { .. }
+            | Op::PushHosts { .. }
+            | Op::KickHosts { .. }
+            | Op::Custom(ExtraOp::Apply { .. }) => {
+                let (pre, post) = (ids(pre)?, ids(post)?);
+                Some(pre.symmetric_difference(&post).copied().collect())
+            }
+            Op::Custom(
+                ExtraOp::SwitchELToGeth { nodes, .. }
+                | ExtraOp::SwitchELToReth { nodes, .. },
+            ) => Some(nodes.clone()),
+            Op::Custom(
+                ExtraOp::Deposit { nodes, .. }
+                | ExtraOp::ValidatorExit { nodes, .. }
+                | ExtraOp::Label { nodes, .. }
+                | ExtraOp::ChaosNetem { nodes, .. },
+            ) => {
+                let sel = nodes.trim().parse::<Selector>().ok()?;
+                env_select_nodes(pre?, &sel, true).ok()
+            }
+            Op::Custom(ExtraOp::ChaosPartition { groups, .. }) => {
+                env_partition(pre?, groups).ok().map(|p| p.nodes())
+            }
+            _ => None,
+        }
+    }
+
+    // Print what the operation would do,
+    // nothing will be executed and the ENV meta will not be changed
+    fn exec_dry_run(&self) -> Result<()> {
+        let en = &self.sys_cfg.name;
+        match &self.sys_cfg.op {
+            Op::Start {
+                nodes,
+                realloc_ports,
+                ..
+            } => {
+                let env = load_sysenv(en).c(d!())?;
+                for n in dry_run_nodes(&env, nodes.as_ref()).c(d!())? {
+                    dry_run_start(&env, &n, *realloc_ports);
+                }
+                Ok(())
+            }
+            Op::Restart {
+                nodes,
+                realloc_ports,
+                wait_itv_secs,
+                ..
+            } => {
+                let env = load_sysenv(en).c(d!())?;
+                for n in dry_run_nodes(&env, nodes.as_ref()).c(d!())? {
+                    let target = format!("node {} @ {}", n.id, n.host.host_id());
+                    dry_run_script(
+                        &target,
+                        &CmdGenerator.cmd_for_stop(&n, &env.meta, false),
+                    );
+                    dry_run_note(&target, &format!("sleep {wait_itv_secs} seconds"));
+                    dry_run_start(&env, &n, *realloc_ports);
+                }
+                Ok(())
+            }
+            Op::PushNodes {
+                host,
+                custom_data,
+                fullnode,
+                num,
+            } => {
+                let env = load_sysenv(en).c(d!())?;
+
+                let next_id = serde_json::to_value(&env.meta).c(d!())?["next_node_id"]
+                    .as_u64()
+                    .c(d!())? as NodeID;
+                let host = if let Some(addr) = host {
+                    env.meta
+                        .hosts
+                        .as_ref()
+                        .get(&addr.host_id())
+                        .c(d!("The host does not exist"))?
+                        .meta
+                        .clone()
+                } else {
+                    env.meta
+                        .hosts
+                        .as_ref()
+                        .values()
+                        .max_by_key(|h| h.weight)
+                        .c(d!("No hosts found"))?
+                        .meta
+                        .clone()
+                };
+                let ports = Ports::try_create(&Ports::reserved()).c(d!())?;
+
+                dry_run_note(
+                    "localhost",
+                    "the host and ports of each new node are allocated at runtime, placeholders are used below",
+                );
+
+                for id in next_id..(next_id + *num as NodeID) {
+                    let n = Node {
+                        id,
+                        home: format!("{}/{id}", env.meta.home),
+                        host: host.clone(),
+                        ports: ports.clone(),
+                        kind: alt!(*fullnode, NodeKind::FullNode, NodeKind::ArchiveNode),
+                        custom_data: Some(custom_data.clone()),
                     };
-                    json_deposits_append(&mut fuhrer.custom_data, map).c(d!())
-                        .and_then(|_| env.write_cfg().c(d!()))
+                    let target = format!("node {} @ {}", n.id, n.host.host_id());
+                    dry_run_script(
+                        &target,
+                        &format!("mkdir -p {0} && touch {0}/{MGMT_LOG_NAME}", n.home),
+                    );
+                    dry_run_transfer(
+                        &target,
+                        &format!(
+                            "<genesis data of the ENV, {} bytes>",
+                            env.meta.genesis.len()
+                        ),
+                        &format!(
+                            "{}:{}/{NODE_HOME_GENESIS_DST}",
+                            n.host.host_id(),
+                            n.home
+                        ),
+                    );
+                    dry_run_start(&env, &n, false);
                 }
-                _ => Ok(()),
+
+                Ok(())
+            }
+            Op::Custom(
+                op @ (ExtraOp::Deposit { .. }
+                | ExtraOp::SwitchELToGeth { .. }
+                | ExtraOp::SwitchELToReth { .. }
+                | ExtraOp::Apply { .. }),
+            ) => op.exec(en).c(d!()),
+            _ => Err(eg!(
+                "The `--dry-run` mode is not supported by this operation"
+            )),
+        }
+    }
+}
+
+fn dry_run_nodes(
+    env: &SysEnv<CustomInfo, Ports, CmdGenerator>,
+    ids: Option<&BTreeSet<NodeID>>,
+) -> Result<Vec<Node<Ports>>> {
+    if let Some(ids) = ids {
+        ids.iter()
+            .map(|id| {
+                env.meta
+                    .nodes
+                    .get(id)
+                    .or_else(|| env.meta.fuhrers.get(id))
+                    .cloned()
+                    .c(d!("The node(id: {id}) does not exist"))
             })
+            .collect()
+    } else {
+        Ok(env
+            .meta
+            .fuhrers
+            .values()
+            .chain(env.meta.nodes.values())
+            .cloned()
+            .collect())
+    }
+}
+
+fn dry_run_start(
+    env: &SysEnv<CustomInfo, Ports, CmdGenerator>,
+    n: &Node<Ports>,
+    realloc_ports: bool,
+) {
+    let target = format!("node {} @ {}", n.id, n.host.host_id());
+    if realloc_ports {
+        dry_run_note(
+            &target,
+            "the ports will be reallocated if they are occupied",
+        );
     }
+    dry_run_start_node(
+        &target,
+        ScriptRunner::Remote,
+        &CmdGenerator.cmd_cnt_running(n, &env.meta),
+        &CmdGenerator.cmd_for_start(n, &env.meta),
+    );
 }
 
-impl From<DDevCfg> for EnvCfg {
-    fn from(cfg: DDevCfg) -> Self {
+impl TryFrom<DDevCfg> for EnvCfg {
+    type Error = CfgError;
+
+    fn try_from(cfg: DDevCfg) -> std::result::Result<Self, CfgError> {
+        let dry_run = cfg.dry_run;
+        let wait = cfg.wait;
+        let output = cfg.output;
         let mut en = cfg
             .env_name
             .as_deref()
@@ -81,36 +429,51 @@ impl From<DDevCfg> for EnvCfg {
                     en = n.into();
                 }
 
-                let hosts = copts
-                    .hosts
-                    .as_deref()
-                    .map(|hs| hs.into())
-                    .or_else(|| pnk!(env_hosts()));
-                let hosts = pnk!(
-                    hosts,
-                    "No hosts registered! Use `--hosts` or $EXP_DDEV_HOSTS to set."
-                );
+                let hosts = if let Some(hs) = copts.hosts.as_deref() {
+                    Hosts::from_str(hs)
+                        .map_err(|_| CfgError::InvalidHosts(hs.to_owned()))?
+                } else {
+                    env_hosts_arg()?.ok_or(CfgError::NoHosts)?
+                };
 
                 let (genesis_tgz_path, genesis_vkeys_tgz_path) =
-                    if let Some(s) = copts.genesis_data_pre_created {
-                        let paths = s.split('+').collect::<Vec<_>>();
-                        if 2 != paths.len() {
-                            pnk!(Err(eg!("Invalid value")));
-                        }
-                        for p in paths.iter() {
-                            if fs::metadata(p).is_err() {
-                                pnk!(Err(eg!("File not accessible")));
-                            }
-                        }
-                        (Some(paths[0].to_owned()), Some(paths[1].to_owned()))
-                    } else {
-                        (None, None)
-                    };
+                    parse_genesis_data_arg(copts.genesis_data_pre_created)?;
+
+                let external = copts
+                    .external_network
+                    .as_deref()
+                    .map(|dir| {
+                        ExternalNetwork::new(
+                            dir,
+                            copts.external_bootnodes.as_deref(),
+                            copts.external_enrs.as_deref(),
+                            copts.checkpoint_sync_url.clone(),
+                        )
+                    })
+                    .transpose()
+                    .map_err(|e| CfgError::InvalidExternalNetwork(e.get_lowest_msg()))?;
 
                 let custom_data = CustomInfo {
                     el_geth_bin: copts.el_geth_bin.unwrap_or("geth".to_owned()),
                     el_reth_bin: copts.el_reth_bin.unwrap_or("reth".to_owned()),
                     cl_bin: copts.cl_bin.unwrap_or_else(|| "lighthouse".to_owned()),
+                    process_backend: if copts.systemd {
+                        ProcessBackend::Systemd(SystemdLimits {
+                            restart_sec: copts.systemd_restart_sec.unwrap_or(5),
+                            memory_max: copts.systemd_memory_max,
+                            cpu_quota: copts.systemd_cpu_quota,
+                        })
+                    } else {
+                        ProcessBackend::Nohup
+                    },
+                    stop_timeout_secs: copts
+                        .stop_timeout_secs
+                        .unwrap_or(process::DEFAULT_STOP_TIMEOUT_SECS),
+                    hooks: Default::default(),
+                    schema_version: SCHEMA_VERSION,
+                    external,
+                    identity_seed: p2p::new_seed(),
+                    netem: Default::default(),
                 };
 
                 let envopts = SysOpts {
@@ -144,6 +507,7 @@ impl From<DDevCfg> for EnvCfg {
                     wallet_seckey_path,
                     withdraw_0x01_addr,
                     async_wait,
+                    dry_run,
                 })
             }
             DDevOp::ValidatorExit {
@@ -234,18 +598,24 @@ impl From<DDevCfg> for EnvCfg {
                 host_addr,
                 reth,
                 fullnode,
+                bootnode,
+                el_bootnode_bin,
                 num,
             } => {
                 if let Some(n) = env_name {
                     en = n.into();
                 }
+                let mut custom_data = alt!(
+                    reth,
+                    NodeCustomData::new_with_reth(),
+                    NodeCustomData::new_with_geth()
+                );
+                if bootnode {
+                    custom_data.bootnode = Some(Bootnode::new(el_bootnode_bin));
+                }
                 Op::PushNodes {
-                    host: host_addr.map(|a| pnk!(HostAddr::from_str(&a))),
-                    custom_data: alt!(
-                        reth,
-                        NodeCustomData::new_with_reth().to_json_value(),
-                        NodeCustomData::new_with_geth().to_json_value()
-                    ),
+                    host: host_addr_arg(host_addr)?,
+                    custom_data: custom_data.to_json_value(),
                     fullnode,
                     num,
                 }
@@ -259,10 +629,10 @@ impl From<DDevCfg> for EnvCfg {
                 if let Some(n) = env_name {
                     en = n.into();
                 }
-                let nodes = pnk!(parse_nodes(&nodes));
+                let nodes = parse_nodes_arg(&nodes)?;
                 Op::MigrateNodes {
                     nodes,
-                    host: host_addr.map(|a| pnk!(HostAddr::from_str(&a))),
+                    host: host_addr_arg(host_addr)?,
                     force,
                 }
             }
@@ -299,9 +669,7 @@ impl From<DDevCfg> for EnvCfg {
                 if let Some(n) = env_name {
                     en = n.into();
                 }
-                let hosts = pnk!(hosts
-                    .map(|h| pnk!(parse_cfg(&h)))
-                    .or_else(|| pnk!(env_hosts())));
+                let hosts = hosts_arg(hosts.as_deref())?.ok_or(CfgError::NoHosts)?;
                 Op::PushHosts { hosts }
             }
             DDevOp::KickHosts {
@@ -328,6 +696,7 @@ impl From<DDevCfg> for EnvCfg {
                 Op::Custom(ExtraOp::Show {
                     clean_up,
                     write_back,
+                    output,
                 })
             }
             DDevOp::ShowHosts { hosts, json } => {
@@ -354,15 +723,53 @@ impl From<DDevCfg> for EnvCfg {
                     cl_bn_metric,
                     cl_vc,
                     cl_vc_metric,
+                    output,
                 })
             }
             DDevOp::DebugFailedNodes { env_name } => {
                 if let Some(n) = env_name {
                     en = n.into();
                 }
-                Op::DebugFailedNodes
+                if let Some(output) = output {
+                    Op::Custom(ExtraOp::DebugFailedNodes { output })
+                } else {
+                    Op::DebugFailedNodes
+                }
+            }
+            DDevOp::Health { env_name } => {
+                if let Some(n) = env_name {
+                    en = n.into();
+                }
+                Op::Custom(ExtraOp::Health { output })
+            }
+            DDevOp::Topology { env_name, dot } => {
+                if let Some(n) = env_name {
+                    en = n.into();
+                }
+                Op::Custom(ExtraOp::Topology { dot, output })
+            }
+            DDevOp::Supervise {
+                env_name,
+                interval_secs,
+                max_backoff_secs,
+                webhook,
+            } => {
+                if let Some(n) = env_name {
+                    en = n.into();
+                }
+                Op::Custom(ExtraOp::Supervise {
+                    interval_secs,
+                    max_backoff_secs,
+                    webhook,
+                })
+            }
+            DDevOp::List => {
+                if let Some(output) = output {
+                    Op::Custom(ExtraOp::List { output })
+                } else {
+                    Op::List
+                }
             }
-            DDevOp::List => Op::List,
             DDevOp::HostPutFile {
                 env_name,
                 local_path,
@@ -375,9 +782,7 @@ impl From<DDevCfg> for EnvCfg {
                 Op::HostPutFile {
                     local_path,
                     remote_path,
-                    hosts: hosts
-                        .map(|h| pnk!(parse_cfg(&h)))
-                        .or_else(|| pnk!(env_hosts())),
+                    hosts: hosts_arg(hosts.as_deref())?,
                 }
             }
             DDevOp::HostGetFile {
@@ -392,9 +797,7 @@ impl From<DDevCfg> for EnvCfg {
                 Op::HostGetFile {
                     remote_path,
                     local_base_dir,
-                    hosts: hosts
-                        .map(|h| pnk!(parse_cfg(&h)))
-                        .or_else(|| pnk!(env_hosts())),
+                    hosts: hosts_arg(hosts.as_deref())?,
                 }
             }
             DDevOp::HostExec {
@@ -409,9 +812,7 @@ impl From<DDevCfg> for EnvCfg {
                 Op::HostExec {
                     cmd,
                     script_path,
-                    hosts: hosts
-                        .map(|h| pnk!(parse_cfg(&h)))
-                        .or_else(|| pnk!(env_hosts())),
+                    hosts: hosts_arg(hosts.as_deref())?,
                 }
             }
             DDevOp::GetLogs {
@@ -446,15 +847,192 @@ impl From<DDevCfg> for EnvCfg {
                 if let Some(n) = env_name {
                     en = n.into();
                 }
-                let nodes = pnk!(parse_nodes(&nodes));
-                Op::Custom(ExtraOp::SwitchELToGeth { nodes })
+                let nodes = parse_nodes_arg(&nodes)?;
+                Op::Custom(ExtraOp::SwitchELToGeth { nodes, dry_run })
             }
             DDevOp::SwitchELToReth { env_name, nodes } => {
                 if let Some(n) = env_name {
                     en = n.into();
                 }
-                let nodes = pnk!(parse_nodes(&nodes));
-                Op::Custom(ExtraOp::SwitchELToReth { nodes })
+                let nodes = parse_nodes_arg(&nodes)?;
+                Op::Custom(ExtraOp::SwitchELToReth { nodes, dry_run })
+            }
+            DDevOp::Label { op } => {
+                let (env_name, nodes, items, remove) = match op {
+                    LabelOp::Add {
+                        env_name,
+                        nodes,
+                        items,
+                    } => (env_name, nodes, items, false),
+                    LabelOp::Remove {
+                        env_name,
+                        nodes,
+                        items,
+                    } => (env_name, nodes, items, true),
+                };
+                if let Some(n) = env_name {
+                    en = n.into();
+                }
+                let (labels, notes) = parse_label_items(&items, remove)?;
+                Op::Custom(ExtraOp::Label {
+                    nodes,
+                    remove,
+                    labels,
+                    notes,
+                })
+            }
+            DDevOp::History {
+                env_name,
+                operator,
+                operation,
+                node,
+                failed,
+                since,
+                last,
+            } => {
+                if let Some(n) = env_name {
+                    en = n.into();
+                }
+                Op::Custom(ExtraOp::History {
+                    filter: HistoryFilter {
+                        operator,
+                        operation,
+                        node,
+                        failed_only: failed,
+                        since,
+                        last,
+                    },
+                    output: output.unwrap_or(OutputFormat::Table),
+                })
+            }
+            DDevOp::Hook { op } => {
+                let (env_name, webhooks, scripts, remove) = match op {
+                    HookOp::Add {
+                        env_name,
+                        webhook,
+                        script,
+                    } => (env_name, webhook, script, false),
+                    HookOp::Remove {
+                        env_name,
+                        webhook,
+                        script,
+                    } => (env_name, webhook, script, true),
+                    HookOp::List { env_name } => (env_name, vec![], vec![], false),
+                };
+                if let Some(n) = env_name {
+                    en = n.into();
+                }
+                Op::Custom(ExtraOp::Hook {
+                    webhooks: webhooks.into_iter().collect(),
+                    scripts: scripts.into_iter().collect(),
+                    remove,
+                })
+            }
+            DDevOp::Snapshot { op } => {
+                let (env_name, op) = match op {
+                    SnapshotOp::Create { env_name, name } => {
+                        (env_name, ExtraOp::SnapshotCreate { name })
+                    }
+                    SnapshotOp::Restore { env_name, name } => {
+                        (env_name, ExtraOp::SnapshotRestore { name })
+                    }
+                    SnapshotOp::List { env_name } => (env_name, ExtraOp::SnapshotList),
+                    SnapshotOp::Delete { env_name, name } => {
+                        (env_name, ExtraOp::SnapshotDelete { name })
+                    }
+                };
+                if let Some(n) = env_name {
+                    en = n.into();
+                }
+                Op::Custom(op)
+            }
+            DDevOp::Chaos { op } => {
+                let (env_name, op) = match op {
+                    ChaosOp::Partition {
+                        env_name,
+                        groups,
+                        duration,
+                    } => (
+                        env_name,
+                        ExtraOp::ChaosPartition {
+                            groups: groups.join(" "),
+                            duration_secs: duration,
+                        },
+                    ),
+                    ChaosOp::Netem {
+                        env_name,
+                        nodes,
+                        delay,
+                        jitter,
+                        loss,
+                    } => (
+                        env_name,
+                        ExtraOp::ChaosNetem {
+                            nodes,
+                            netem: Netem {
+                                delay,
+                                jitter,
+                                loss,
+                            },
+                        },
+                    ),
+                    ChaosOp::Monkey {
+                        env_name,
+                        nodes,
+                        rate,
+                        max_down,
+                        duration,
+                        seed,
+                    } => (
+                        env_name,
+                        ExtraOp::ChaosMonkey {
+                            nodes,
+                            interval_secs: rate,
+                            max_down,
+                            duration_secs: duration,
+                            seed,
+                        },
+                    ),
+                    ChaosOp::Clear { env_name } => (env_name, ExtraOp::ChaosClear),
+                };
+                if let Some(n) = env_name {
+                    en = n.into();
+                }
+                Op::Custom(op)
+            }
+            DDevOp::Clone {
+                src,
+                dst,
+                with_data,
+            } => {
+                en = dst.into();
+                Op::Custom(ExtraOp::Clone {
+                    src: src.into(),
+                    with_data,
+                })
+            }
+            DDevOp::Export {
+                env_name,
+                path,
+                redact_hosts,
+            } => {
+                en = env_name.into();
+                Op::Custom(ExtraOp::Export { path, redact_hosts })
+            }
+            DDevOp::Import {
+                path,
+                env_name,
+                hosts,
+            } => {
+                en = env_name.into();
+                let hosts = hosts_arg(hosts.as_deref())?.ok_or(CfgError::NoHosts)?;
+                Op::Custom(ExtraOp::Import { path, hosts })
+            }
+            DDevOp::ExportJoinKit { env_name, dir } => {
+                if let Some(n) = env_name {
+                    en = n.into();
+                }
+                Op::Custom(ExtraOp::ExportJoinKit { dir })
             }
             DDevOp::Git {
                 env_name,
@@ -466,11 +1044,26 @@ impl From<DDevCfg> for EnvCfg {
                 }
                 Op::Custom(ExtraOp::Git { remote_url, push })
             }
+            DDevOp::Apply {
+                env_name,
+                spec_path,
+                plan,
+            } => {
+                if let Some(n) = env_name {
+                    en = n.into();
+                }
+                Op::Custom(ExtraOp::Apply {
+                    spec_path,
+                    plan_only: plan || dry_run,
+                })
+            }
         };
 
-        Self {
+        Ok(Self {
             sys_cfg: SysCfg { name: en, op },
-        }
+            dry_run,
+            wait,
+        })
     }
 }
 
@@ -483,11 +1076,17 @@ impl NodeCmdGenerator<Node<Ports>, EnvMeta<CustomInfo, Node<Ports>>> for CmdGene
         n: &Node<Ports>,
         e: &EnvMeta<CustomInfo, Node<Ports>>,
     ) -> String {
+        if pnk!(json_bootnode(&n.custom_data)).is_some() {
+            return bootnode::cmd_cnt_running(&n.home, &e.custom_data.process_backend);
+        }
+        if let ProcessBackend::Systemd(_) = e.custom_data.process_backend {
+            return process::cmd_cnt_running(&n.home);
+        }
+        // The binaries are copied into the node home before starting
         format!(
-            "ps ax -o pid,args | grep -E '({0}.*{3}/)|({1}.*{3}/)|({2}.*{3}/)' | grep -v 'grep' | wc -l",
-            e.custom_data.el_geth_bin, e.custom_data.el_reth_bin, e.custom_data.cl_bin, n.home
+            "ps ax -o pid=,args= | {} | wc -l",
+            process::cmd_grep_bins(&n.home, &["geth", "reth", "lighthouse"])
         )
-        .replace('+', r"\+")
     }
 
     fn cmd_for_start(
@@ -498,8 +1097,12 @@ impl NodeCmdGenerator<Node<Ports>, EnvMeta<CustomInfo, Node<Ports>>> for CmdGene
         let home = &n.home;
         let genesis_dir = format!("{home}/genesis");
 
-        let rand_jwt = ruc::algo::rand::rand_jwt();
+        // `None` in the ENVs created by old versions,
+        // the clients generate their own keys in that case
+        let identity = NodeIdentity::derive(&e.custom_data.identity_seed, n.id);
+
         let auth_jwt = format!("{home}/auth.jwt");
+        let write_jwt = p2p::cmd_write_jwt(identity.as_ref(), &auth_jwt);
 
         let geth = if e.custom_data.el_geth_bin.contains("/") {
             e.custom_data.el_geth_bin.clone()
@@ -521,7 +1124,7 @@ impl NodeCmdGenerator<Node<Ports>, EnvMeta<CustomInfo, Node<Ports>>> for CmdGene
 
         let prepare_cmd = format!(
             r#"
-echo "{rand_jwt}" > {auth_jwt} | tr -d '\n' || exit 1
+{write_jwt}
 
 cp -f {geth} {home}/geth_bin || exit 1
 cp -f {reth} {home}/reth_bin || exit 1
@@ -540,8 +1143,39 @@ fi "#
         let local_ip = &n.host.addr.local_ip;
         let ext_ip = n.host.addr.connection_addr();
 
+        let launch = |component: &str, err_file: &str, cmd: &str| {
+            process::cmd_for_launch(
+                &e.custom_data.process_backend,
+                e.custom_data.stop_timeout_secs,
+                &n.home,
+                component,
+                err_file,
+                cmd,
+            )
+        };
+
+        if let Some(b) = pnk!(json_bootnode(&n.custom_data)) {
+            let cmd = b.cmd_for_start(
+                n.id,
+                home,
+                local_ip,
+                ext_ip,
+                n.ports.el_discovery,
+                n.ports.cl_discovery,
+                launch,
+            );
+            return format!("\n{prepare_cmd}\n{cmd}\n");
+        }
+
+        // Used instead of the online nodes if any
+        let dedicated = dedicated_bootnodes(e, n.id);
+
         let ts_start = ts!();
         let (el_bootnodes, cl_bn_bootnodes, cl_bn_trusted_peers, checkpoint_sync_url) = loop {
+            if let Some((enodes, enrs, url)) = dedicated.clone() {
+                break (enodes, enrs, String::new(), url);
+            }
+
             let online_nodes = e
                 .nodes_should_be_online
                 .iter()
@@ -621,6 +1255,13 @@ fi "#
             );
         };
 
+        let (el_bootnodes, cl_bn_bootnodes, checkpoint_sync_url) =
+            if let Some(ext) = e.custom_data.external.as_ref() {
+                ext.merge_peers(el_bootnodes, cl_bn_bootnodes, checkpoint_sync_url)
+            } else {
+                (el_bootnodes, cl_bn_bootnodes, checkpoint_sync_url)
+            };
+
         ////////////////////////////////////////////////
         // EL
         ////////////////////////////////////////////////
@@ -634,6 +1275,18 @@ fi "#
         let el_engine_port = n.ports.el_engine_api;
         let el_metric_port = n.ports.el_metric;
 
+        let el_key_path = format!("{home}/p2p/el.key");
+        let (cmd_write_keys, geth_key_arg, reth_key_arg) =
+            if let Some(i) = identity.as_ref() {
+                (
+                    i.cmd_write_keys(&el_key_path, &format!("{home}/{CL_BN_DIR}")),
+                    format!(" --nodekey={el_key_path}"),
+                    format!(" --p2p-secret-key={el_key_path}"),
+                )
+            } else {
+                Default::default()
+            };
+
         let el_cmd = if Eth1Kind::Geth == el_kind {
             let el_gc_mode = if matches!(n.kind, NodeKind::FullNode) {
                 "full"
@@ -652,7 +1305,7 @@ fi "#
 
             let cmd_run_part_0 = format!(
                 r#"
-nohup {home}/geth_bin \
+{home}/geth_bin{geth_key_arg} \
     --syncmode=full \
     --gcmode={el_gc_mode} \
     --networkid=$(grep -Po '(?<="chainId":)\s*\d+' {el_genesis} | tr -d ' ') \
@@ -685,9 +1338,8 @@ nohup {home}/geth_bin \
                 format!(" --bootnodes='{el_bootnodes}'")
             };
 
-            let cmd_run_part_2 = " >/dev/null 2>&1 &";
-
-            cmd_init_part + &cmd_run_part_0 + &cmd_run_part_1 + cmd_run_part_2
+            cmd_init_part
+                + &launch("el", EL_ERR_NAME, &(cmd_run_part_0 + &cmd_run_part_1))
         } else if Eth1Kind::Reth == el_kind {
             let cmd_init_part = format!(
                 r#"
@@ -701,7 +1353,7 @@ fi "#
 
             let cmd_run_part_0 = format!(
                 r#"
-nohup {home}/reth_bin node \
+{home}/reth_bin node{reth_key_arg} \
     --chain={el_genesis} \
     --datadir={el_dir} \
     --log.file.directory={el_dir}/logs \
@@ -725,6 +1377,13 @@ nohup {home}/reth_bin node \
 
             let cmd_run_part_1 = if el_bootnodes.is_empty() {
                 String::new()
+            } else if dedicated.is_some() {
+                // The dedicated bootnodes do not accept connections
+                format!(
+                    r#"\
+    --bootnodes='{el_bootnodes}' \
+    "#
+                )
             } else {
                 format!(
                     r#"\
@@ -742,9 +1401,8 @@ nohup {home}/reth_bin node \
             //     cmd_run_part_1.push_str(" --full");
             // }
 
-            let cmd_run_part_2 = " >/dev/null 2>&1 &";
-
-            cmd_init_part + &cmd_run_part_0 + &cmd_run_part_1 + cmd_run_part_2
+            cmd_init_part
+                + &launch("el", EL_ERR_NAME, &(cmd_run_part_0 + &cmd_run_part_1))
         } else {
             pnk!(Err(eg!("The fuhrering world is over!")))
         };
@@ -771,12 +1429,15 @@ nohup {home}/reth_bin node \
             };
 
         let cl_bn_cmd = {
-            let cmd_run_part_0 = format!(
+            let cmd_prepare_part = format!(
                 r#"
 mkdir -p {cl_bn_dir} || exit 1
-sleep 0.5
+sleep 0.5 "#
+            );
 
-nohup {home}/lighthouse_bin beacon_node \
+            let cmd_run_part_0 = format!(
+                r#"
+{home}/lighthouse_bin beacon_node \
     --testnet-dir={cl_genesis} \
     --datadir={cl_bn_dir} \
     --logfile={cl_bn_dir}/logs/{CL_BN_LOG_NAME} \
@@ -813,11 +1474,16 @@ nohup {home}/lighthouse_bin beacon_node \
                 format!(
                     r#"\
     --boot-nodes='{cl_bn_bootnodes}' \
-    --trusted-peers='{cl_bn_trusted_peers}' \
     "#
                 )
             };
 
+            // Empty if only the external peers are available
+            if !cl_bn_trusted_peers.is_empty() {
+                cmd_run_part_1
+                    .push_str(&format!(" --trusted-peers='{cl_bn_trusted_peers}'"));
+            }
+
             if node_sync_from_genesis() || checkpoint_sync_url.is_empty() {
                 cmd_run_part_1.push_str(" --allow-insecure-genesis-sync");
             } else {
@@ -825,9 +1491,8 @@ nohup {home}/lighthouse_bin beacon_node \
                     .push_str(&format!(" --checkpoint-sync-url={checkpoint_sync_url}"));
             }
 
-            let cmd_run_part_2 = " >/dev/null 2>&1 &";
-
-            cmd_run_part_0 + &cmd_run_part_1 + cmd_run_part_2
+            cmd_prepare_part
+                + &launch("cl-bn", CL_BN_ERR_NAME, &(cmd_run_part_0 + &cmd_run_part_1))
         };
 
         let cl_vc_cmd = {
@@ -856,9 +1521,12 @@ fi "#
             let cmd_run_part_1 = format!(
                 r#"
 mkdir -p {cl_vc_dir} || exit 1
-sleep 1
+sleep 1 "#
+            );
 
-nohup {home}/lighthouse_bin validator_client \
+            let cmd_run_part_2 = format!(
+                r#"
+{home}/lighthouse_bin validator_client \
     --testnet-dir={cl_genesis} \
     --datadir={cl_vc_dir} \
     --logfile={cl_vc_dir}/logs/{CL_VC_LOG_NAME} \
@@ -873,12 +1541,12 @@ nohup {home}/lighthouse_bin validator_client \
     --http --http-address="127.0.0.1" \
     --http-port={cl_vc_rpc_port} --http-allow-origin='*' \
     --metrics --metrics-address={local_ip} \
-    --metrics-port={cl_vc_metric_port} --metrics-allow-origin='*' \
-    >/dev/null 2>&1 &
-     "#
+    --metrics-port={cl_vc_metric_port} --metrics-allow-origin='*' "#
             );
 
-            cmd_run_part_0 + &cmd_run_part_1
+            cmd_run_part_0
+                + &cmd_run_part_1
+                + &launch("cl-vc", CL_VC_ERR_NAME, &cmd_run_part_2)
         };
 
         ////////////////////////////////////////////////
@@ -890,6 +1558,8 @@ nohup {home}/lighthouse_bin validator_client \
 
             {prepare_cmd}
 
+            {cmd_write_keys}
+
             {el_cmd}
 
             {cl_bn_cmd}
@@ -903,15 +1573,14 @@ nohup {home}/lighthouse_bin validator_client \
     fn cmd_for_stop(
         &self,
         n: &Node<Ports>,
-        _e: &EnvMeta<CustomInfo, Node<Ports>>,
+        e: &EnvMeta<CustomInfo, Node<Ports>>,
         force: bool,
     ) -> String {
-        format!(
-            "for i in \
-            $(ps ax -o pid,args|grep '{}'|grep -v grep|sed -r 's/(^ *)|( +)/ /g'|cut -d ' ' -f 2); \
-            do kill {} $i; done",
+        process::cmd_for_stop(
+            &e.custom_data.process_backend,
             &n.home,
-            alt!(force, "-9", ""),
+            force,
+            e.custom_data.stop_timeout_secs,
         )
     }
 
@@ -972,6 +1641,37 @@ fn parse_cfg(json_path_or_expr: &str) -> Result<Hosts> {
     }
 }
 
+fn env_hosts_arg() -> std::result::Result<Option<Hosts>, CfgError> {
+    env_hosts().map_err(|_| {
+        let var = alt!(
+            env::var("EXP_DDEV_HOSTS_JSON").is_ok(),
+            "$EXP_DDEV_HOSTS_JSON",
+            "$EXP_DDEV_HOSTS"
+        );
+        CfgError::InvalidHosts(var.to_owned())
+    })
+}
+
+// The value of `--hosts` first, then the env vars
+fn hosts_arg(hosts: Option<&str>) -> std::result::Result<Option<Hosts>, CfgError> {
+    if let Some(h) = hosts {
+        parse_cfg(h)
+            .map(Some)
+            .map_err(|_| CfgError::InvalidHosts(h.to_owned()))
+    } else {
+        env_hosts_arg()
+    }
+}
+
+fn host_addr_arg(
+    addr: Option<String>,
+) -> std::result::Result<Option<HostAddr>, CfgError> {
+    addr.map(|a| {
+        HostAddr::from_str(&a).map_err(|_| CfgError::InvalidHostAddr(a.clone()))
+    })
+    .transpose()
+}
+
 //////////////////////////////////////////////////
 //////////////////////////////////////////////////
 
@@ -983,6 +1683,7 @@ enum ExtraOp {
         wallet_seckey_path: Option<String>,
         withdraw_0x01_addr: Option<String>,
         async_wait: bool,
+        dry_run: bool,
     },
     ValidatorExit {
         nodes: String, /*comma separated node IDs*/
@@ -991,6 +1692,7 @@ enum ExtraOp {
     Show {
         clean_up: bool,
         write_back: bool,
+        output: Option<OutputFormat>,
     },
     ShowHosts {
         hosts: Option<HostExpression>,
@@ -1004,6 +1706,25 @@ enum ExtraOp {
         cl_bn_metric: bool,
         cl_vc: bool,
         cl_vc_metric: bool,
+        output: Option<OutputFormat>,
+    },
+    List {
+        output: OutputFormat,
+    },
+    DebugFailedNodes {
+        output: OutputFormat,
+    },
+    Health {
+        output: Option<OutputFormat>,
+    },
+    Topology {
+        dot: Option<String>,
+        output: Option<OutputFormat>,
+    },
+    Supervise {
+        interval_secs: u64,
+        max_backoff_secs: u64,
+        webhook: Option<String>,
     },
     GetLogs {
         local_dir: Option<String>,
@@ -1016,51 +1737,94 @@ enum ExtraOp {
     },
     SwitchELToGeth {
         nodes: BTreeSet<NodeID>,
+        dry_run: bool,
     },
     SwitchELToReth {
         nodes: BTreeSet<NodeID>,
+        dry_run: bool,
     },
-    Git {
-        remote_url: Option<String>,
-        push: bool,
+    Label {
+        nodes: String,
+        remove: bool,
+        labels: BTreeSet<String>,
+        notes: BTreeMap<String, String>,
     },
-}
-
-impl CustomOps for ExtraOp {
-    fn exec(&self, en: &EnvName) -> Result<()> {
-        match self {
-            Self::Deposit {
-                nodes,
-                num_per_node,
+    SnapshotCreate {
+        name: String,
+    },
+    SnapshotRestore {
+        name: String,
+    },
+    SnapshotList,
+    SnapshotDelete {
+        name: String,
+    },
+    ChaosPartition {
+        groups: String,
+        duration_secs: u64,
+    },
+    ChaosNetem {
+        nodes: String,
+        netem: Netem,
+    },
+    ChaosClear,
+    ChaosMonkey {
+        nodes: String,
+        interval_secs: u64,
+        max_down: MaxDown,
+        duration_secs: u64,
+        seed: Option<u64>,
+    },
+    Clone {
+        src: EnvName,
+        with_data: bool,
+    },
+    Export {
+        path: String,
+        redact_hosts: bool,
+    },
+    Import {
+        path: String,
+        hosts: Hosts,
+    },
+    History {
+        filter: HistoryFilter,
+        output: OutputFormat,
+    },
+    Hook {
+        webhooks: BTreeSet<String>,
+        scripts: BTreeSet<String>,
+        remove: bool,
+    },
+    ExportJoinKit {
+        dir: String,
+    },
+    Git {
+        remote_url: Option<String>,
+        push: bool,
+    },
+    Apply {
+        spec_path: String,
+        plan_only: bool,
+    },
+}
+
+impl CustomOps for ExtraOp {
+    fn exec(&self, en: &EnvName) -> Result<()> {
+        match self {
+            Self::Deposit {
+                nodes,
+                num_per_node,
                 wallet_seckey_path,
                 withdraw_0x01_addr,
                 async_wait,
+                dry_run,
             } => {
                 let nodes = nodes.trim();
                 let withdraw_addr = withdraw_0x01_addr.as_ref().map(|addr| addr.trim());
                 let mut env = load_sysenv(en).c(d!())?;
 
-                // All non-fuhrer nodes
-                let nodes = if "all" == nodes {
-                    env.meta.nodes.values().cloned().collect::<Vec<_>>()
-                } else {
-                    let ids = parse_nodes(nodes).c(d!())?;
-                    let mut nodes = vec![];
-                    for id in ids.iter() {
-                        if env.meta.fuhrers.contains_key(id) {
-                            return Err(eg!(
-                                "Fuhrer node(id: {}) does not accept deposits",
-                                id
-                            ));
-                        }
-                        if let Some(n) = env.meta.nodes.get(id).cloned() {
-                            nodes.push(n);
-                        } else {
-                            return Err(eg!("The node(id: {}) does not exist", id));
-                        }
-                    }
-                    nodes
-                };
+                let nodes = env_select_non_fuhrer_nodes(&env, nodes).c(d!())?;
 
                 if nodes.is_empty() {
                     return Err(eg!("No target nodes found!"));
@@ -1082,26 +1846,23 @@ impl CustomOps for ExtraOp {
                         .meta
                         .premined_accounts
                         .as_object()
-                        .unwrap()
+                        .c(d!())?
                         .iter()
                         .next()
-                        .c(d!())?;
+                        .c(d!("No premined accounts, use `--wallet-seckey-path`"))?;
                     let addr = withdraw_addr.unwrap_or(addr).to_owned();
-                    let key = obj.as_object().unwrap()["secretKey"]
+                    // Not available for the external networks
+                    let key = obj["secretKey"]
                         .as_str()
-                        .unwrap()
+                        .filter(|k| !k.is_empty())
+                        .c(d!("No secret key of the premined account, use `--wallet-seckey-path`"))?
                         .to_owned();
                     (addr, key)
                 };
 
                 let testnet_dir =
                     format!("{}/{NODE_HOME_GENESIS_DIR_DST}", env.meta.home);
-                let config_yml = format!("{}/config.yaml", testnet_dir);
-                let cfg = fs::read_to_string(config_yml)
-                    .c(d!())
-                    .and_then(|s| serde_yml::from_str::<serde_yml::Value>(&s).c(d!()))?;
-                let deposit_contract =
-                    cfg["DEPOSIT_CONTRACT_ADDRESS"].as_str().unwrap().to_owned();
+                let deposit_contract = genesis_deposit_contract(&testnet_dir).c(d!())?;
 
                 let runtime = crate::common::new_sb_runtime();
 
@@ -1114,8 +1875,6 @@ impl CustomOps for ExtraOp {
 
                 for n in nodes.into_iter() {
                     let tmp_dir = format!("/tmp/{}_{}", ts!(), rand_jwt());
-                    omit!(fs::remove_dir_all(&tmp_dir));
-                    fs::create_dir_all(&tmp_dir).c(d!())?;
 
                     let mnemonic_path = format!("{tmp_dir}/mnemonic.txt");
 
@@ -1124,7 +1883,6 @@ impl CustomOps for ExtraOp {
                     let node_validators_json = format!("{}/validators.json", n.home);
 
                     let mnemonic = create_mnemonic_words();
-                    fs::write(&mnemonic_path, &mnemonic).c(d!())?;
 
                     let node_testnet_dir =
                         format!("{}/{NODE_HOME_GENESIS_DIR_DST}", n.home);
@@ -1158,12 +1916,6 @@ impl CustomOps for ExtraOp {
                             --output-path {tmp_dir}
                         "#
                     );
-                    cmd::exec_output(&cmd).c(d!())?;
-
-                    let remote = Remote::from(&n.host);
-                    remote
-                        .put_file(&validators_json, &node_validators_json)
-                        .c(d!())?;
                     let node_cmd = format!(
                         r#"
                         lighthouse validator-manager import \
@@ -1175,6 +1927,39 @@ impl CustomOps for ExtraOp {
                         rm -f {node_validators_json}
                         "#
                     );
+
+                    if *dry_run {
+                        let target = format!("node {} @ {}", n.id, n.host.host_id());
+                        dry_run_note(
+                            "localhost",
+                            &format!("write a new mnemonic into {mnemonic_path}"),
+                        );
+                        dry_run_script("localhost", &cmd);
+                        dry_run_transfer(
+                            &target,
+                            &validators_json,
+                            &format!("{}:{node_validators_json}", n.host.host_id()),
+                        );
+                        dry_run_script(&target, &node_cmd);
+                        dry_run_note(
+                            "localhost",
+                            &format!(
+                                "send the deposits in {deposits_json} to {deposit_contract} via {el_rpc_endpoint}"
+                            ),
+                        );
+                        continue;
+                    }
+
+                    omit!(fs::remove_dir_all(&tmp_dir));
+                    fs::create_dir_all(&tmp_dir).c(d!())?;
+                    fs::write(&mnemonic_path, &mnemonic).c(d!())?;
+
+                    cmd::exec_output(&cmd).c(d!())?;
+
+                    let remote = Remote::from(&n.host);
+                    remote
+                        .put_file(&validators_json, &node_validators_json)
+                        .c(d!())?;
                     remote.exec_cmd(&node_cmd).c(d!())?;
 
                     let deposits_json = fs::read_to_string(deposits_json).c(d!())?;
@@ -1203,27 +1988,7 @@ impl CustomOps for ExtraOp {
                 let nodes = nodes.trim();
                 let mut env = load_sysenv(en).c(d!())?;
 
-                // All non-fuhrer nodes
-                let nodes = if "all" == nodes {
-                    env.meta.nodes.values().cloned().collect::<Vec<_>>()
-                } else {
-                    let ids = parse_nodes(nodes).c(d!())?;
-                    let mut nodes = vec![];
-                    for id in ids.iter() {
-                        if env.meta.fuhrers.contains_key(id) {
-                            return Err(eg!(
-                                "Fuhrer node(id: {}) does not accept deposits",
-                                id
-                            ));
-                        }
-                        if let Some(n) = env.meta.nodes.get(id).cloned() {
-                            nodes.push(n);
-                        } else {
-                            return Err(eg!("The node(id: {}) does not exist", id));
-                        }
-                    }
-                    nodes
-                };
+                let nodes = env_select_non_fuhrer_nodes(&env, nodes).c(d!())?;
 
                 if nodes.is_empty() {
                     return Err(eg!("No target nodes found!"));
@@ -1277,6 +2042,7 @@ impl CustomOps for ExtraOp {
             Self::Show {
                 clean_up,
                 write_back,
+                output,
             } => {
                 let mut env = load_sysenv(en).c(d!())?;
 
@@ -1296,51 +2062,40 @@ impl CustomOps for ExtraOp {
                     }
                 }
 
-                let mut ret = pnk!(serde_json::to_value(&env));
-
-                ret.as_object_mut()
-                    .unwrap()
-                    .remove("node_cmdline_generator");
-
-                let meta = ret["meta"].as_object_mut().unwrap();
-
-                meta.remove("genesis");
-                meta.remove("genesis_vkeys");
-                meta.remove("genesis_mnemonic_words");
-                meta.remove("genesis_validator_num");
-                meta.remove("nodes_should_be_online");
-                meta.remove("next_node_id");
-
-                let mut list_to_cnt = |field: &str| {
-                    for ids in meta[field]
-                        .as_object_mut()
-                        .unwrap()
-                        .values_mut()
-                        .flat_map(|v| {
-                            v.as_object_mut().unwrap()["custom_data"]
-                                .as_object_mut()
-                                .unwrap()["deposits"]
-                                .as_object_mut()
-                                .unwrap()
-                                .values_mut()
+                if let Some(f @ (OutputFormat::Table | OutputFormat::Plain)) = output {
+                    let nodes = env
+                        .meta
+                        .fuhrers
+                        .values()
+                        .chain(env.meta.nodes.values())
+                        .map(|n| {
+                            Ok(NodeSummary {
+                                node: node_brief(n).c(d!())?,
+                                validators: json_deposits_cnt(&n.custom_data).c(d!())?,
+                                should_be_online: env
+                                    .meta
+                                    .nodes_should_be_online
+                                    .contains_key(&n.id),
+                                netem: env.meta.custom_data.netem.get(&n.id).cloned(),
+                            })
                         })
-                    {
-                        mem::swap(
-                            ids,
-                            &mut JsonValue::Number(ids.as_array().unwrap().len().into()),
-                        );
-                    }
-                };
+                        .collect::<Result<Vec<_>>>()?;
+                    print_nodes(*f, &nodes);
+                    return Ok(());
+                }
 
-                list_to_cnt("fuhrer_nodes");
-                list_to_cnt("nodes");
+                let mut ret = serde_json::to_value(&env)
+                    .c(d!())
+                    .and_then(|env| env_show_json(env).c(d!()))?;
+
+                let meta = ret["meta"].as_object_mut().c(d!())?;
 
-                let mut hosts = meta.remove("remote_hosts").unwrap();
+                let mut hosts = meta.remove("remote_hosts").c(d!())?;
                 meta.insert(
                     "remote_hosts".to_string(),
                     hosts
                         .as_object_mut()
-                        .unwrap()
+                        .c(d!())?
                         .iter_mut()
                         .map(|(id, v)| {
                             let mut v = v.take();
@@ -1356,10 +2111,9 @@ impl CustomOps for ExtraOp {
                 Ok(())
             }
             Self::ShowHosts { hosts, json } => {
-                let hosts = pnk!(hosts
-                    .as_ref()
-                    .map(|h| pnk!(parse_cfg(h)))
-                    .or_else(|| pnk!(env_hosts())));
+                let hosts = hosts_arg(hosts.as_deref())
+                    .c(d!())?
+                    .c(d!("{}", CfgError::NoHosts))?;
                 if *json {
                     let s = serde_json::to_string_pretty(&hosts).unwrap();
                     println!("{s}");
@@ -1393,6 +2147,7 @@ impl CustomOps for ExtraOp {
                 cl_bn_metric,
                 cl_vc,
                 cl_vc_metric,
+                output,
             } => {
                 let default = !(*el_web3
                     || *el_web3_ws
@@ -1403,120 +2158,141 @@ impl CustomOps for ExtraOp {
                     || *cl_vc_metric);
                 let env = load_sysenv(en).c(d!())?;
 
-                let mut buf_el_web3 = vec![];
-                let mut buf_el_web3_ws = vec![];
-                let mut buf_el_metric = vec![];
-                let mut buf_cl_bn = vec![];
-                let mut buf_cl_bn_metric = vec![];
-                let mut buf_cl_vc = vec![];
-                let mut buf_cl_vc_metric = vec![];
-
-                env.meta
+                let nodes = env
+                    .meta
                     .fuhrers
                     .values()
                     .chain(env.meta.nodes.values())
-                    .for_each(|n| {
-                        if *el_web3 || default {
-                            buf_el_web3.push(format!(
-                                "    http://{}:{}",
-                                n.host.addr.connection_addr(),
-                                n.ports.el_rpc
-                            ));
-                        }
-                        if *el_web3_ws || default {
-                            buf_el_web3_ws.push(format!(
-                                "    http://{}:{}",
-                                n.host.addr.connection_addr(),
-                                n.ports.el_rpc_ws
-                            ));
-                        }
-                        if *el_metric || default {
-                            buf_el_metric.push(format!(
-                                "    http://{}:{}",
-                                n.host.addr.connection_addr(),
-                                n.ports.el_metric
-                            ));
-                        }
-                        if *cl_bn || default {
-                            buf_cl_bn.push(format!(
-                                "    http://{}:{}",
-                                n.host.addr.connection_addr(),
-                                n.ports.cl_bn_rpc
-                            ));
-                        }
-                        if *cl_bn_metric || default {
-                            buf_cl_bn_metric.push(format!(
-                                "    http://{}:{}",
-                                n.host.addr.connection_addr(),
-                                n.ports.cl_bn_metric
-                            ));
-                        }
-                        if *cl_vc || default {
-                            buf_cl_vc.push(format!(
-                                "    http://{}:{}",
-                                n.host.addr.connection_addr(),
-                                n.ports.cl_vc_rpc
-                            ));
-                        }
-                        if *cl_vc_metric || default {
-                            buf_cl_vc_metric.push(format!(
-                                "    http://{}:{}",
-                                n.host.addr.connection_addr(),
-                                n.ports.cl_vc_metric
-                            ));
-                        }
-                    });
-
-                if !buf_el_web3.is_empty() {
-                    println!("\x1b[33;1mEL WEB3 RPCs:\x1b[0m");
-                    buf_el_web3.iter().for_each(|l| {
-                        println!("{l}");
-                    });
-                }
-
-                if !buf_el_web3_ws.is_empty() {
-                    println!("\x1b[33;1mEL WEB3 WS RPCs:\x1b[0m");
-                    buf_el_web3_ws.iter().for_each(|l| {
-                        println!("{l}");
-                    });
-                }
-
-                if !buf_el_metric.is_empty() {
-                    println!("\x1b[33;1mEL METRIC RPCs:\x1b[0m");
-                    buf_el_metric.iter().for_each(|l| {
-                        println!("{l}");
-                    });
-                }
+                    .map(|n| {
+                        let url = |wanted: bool, port: u16| {
+                            (wanted || default).then(|| {
+                                format!(
+                                    "http://{}:{port}",
+                                    n.host.addr.connection_addr()
+                                )
+                            })
+                        };
+                        Ok(NodeRpcs {
+                            node: node_brief(n).c(d!())?,
+                            el_web3: url(*el_web3, n.ports.el_rpc),
+                            el_web3_ws: url(*el_web3_ws, n.ports.el_rpc_ws),
+                            el_metric: url(*el_metric, n.ports.el_metric),
+                            cl_bn: url(*cl_bn, n.ports.cl_bn_rpc),
+                            cl_bn_metric: url(*cl_bn_metric, n.ports.cl_bn_metric),
+                            cl_vc: url(*cl_vc, n.ports.cl_vc_rpc),
+                            cl_vc_metric: url(*cl_vc_metric, n.ports.cl_vc_metric),
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
 
-                if !buf_cl_bn.is_empty() {
-                    println!("\x1b[33;1mCL BEACON RPCs:\x1b[0m");
-                    buf_cl_bn.iter().for_each(|l| {
-                        println!("{l}");
-                    });
+                print_rpcs(output.unwrap_or(OutputFormat::Table), &nodes)
+            }
+            Self::List { output } => {
+                SysEnv::<CustomInfo, Ports, CmdGenerator>::get_env_list()
+                    .c(d!())
+                    .and_then(|list| print_env_list(*output, &list))
+            }
+            Self::DebugFailedNodes { output } => {
+                let env = load_sysenv(en).c(d!())?;
+                let (failed_cases, errlist) = env.collect_failed_nodes();
+                let nodes = failed_cases
+                    .values()
+                    .flatten()
+                    .map(|id| {
+                        env.meta
+                            .nodes
+                            .get(id)
+                            .or_else(|| env.meta.fuhrers.get(id))
+                            .c(d!())
+                            .and_then(|n| {
+                                Ok(FailedNode {
+                                    node: node_brief(n).c(d!())?,
+                                    err_tails: node_err_tails(n).c(d!())?,
+                                })
+                            })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let errors = errlist.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+                print_failed_nodes(*output, &nodes, &errors)
+            }
+            Self::Health { output } => {
+                let env = load_sysenv(en).c(d!())?;
+                let nodes = env
+                    .meta
+                    .fuhrers
+                    .values()
+                    .chain(env.meta.nodes.values())
+                    .collect::<Vec<_>>();
+
+                let mut health = vec![];
+                for chunk in nodes.chunks(24) {
+                    thread::scope(|s| {
+                        chunk
+                            .iter()
+                            .map(|n| s.spawn(|| node_health(&env, n)))
+                            .collect::<Vec<_>>()
+                            .into_iter()
+                            .try_for_each(|hdr| {
+                                hdr.join()
+                                    .map_err(|_| eg!("The health checker panicked"))
+                                    .and_then(|h| h.c(d!()))
+                                    .map(|h| health.push(h))
+                            })
+                    })?;
                 }
 
-                if !buf_cl_bn_metric.is_empty() {
-                    println!("\x1b[33;1mCL BEACON METRIC RPCs:\x1b[0m");
-                    buf_cl_bn_metric.iter().for_each(|l| {
-                        println!("{l}");
-                    });
+                print_health(output.unwrap_or(OutputFormat::Table), &health)
+            }
+            Self::Topology { dot, output } => {
+                let env = load_sysenv(en).c(d!())?;
+                let mut nodes = vec![];
+                for n in env
+                    .meta
+                    .fuhrers
+                    .values()
+                    .chain(env.meta.nodes.values())
+                    .filter(|n| env.meta.nodes_should_be_online.contains_key(&n.id))
+                {
+                    if json_bootnode(&n.custom_data).c(d!())?.is_none() {
+                        nodes.push(n);
+                    }
                 }
 
-                if !buf_cl_vc.is_empty() {
-                    println!("\x1b[33;1mCL VALIDATOR RPCs:\x1b[0m");
-                    buf_cl_vc.iter().for_each(|l| {
-                        println!("{l}");
-                    });
+                let seed = &env.meta.custom_data.identity_seed;
+                let mut probes = BTreeMap::new();
+                for chunk in nodes.chunks(24) {
+                    thread::scope(|s| {
+                        chunk
+                            .iter()
+                            .map(|n| {
+                                s.spawn(|| {
+                                    let addr = n.host.addr.connection_addr();
+                                    let identity = NodeIdentity::derive(seed, n.id);
+                                    topology::probe(
+                                        &format!("http://{addr}:{}", n.ports.el_rpc),
+                                        &format!("http://{addr}:{}", n.ports.cl_bn_rpc),
+                                        identity.as_ref(),
+                                    )
+                                })
+                            })
+                            .collect::<Vec<_>>()
+                            .into_iter()
+                            .zip(chunk.iter())
+                            .try_for_each(|(hdr, n)| {
+                                hdr.join()
+                                    .map_err(|_| eg!("The topology prober panicked"))
+                                    .map(|p| {
+                                        probes.insert(n.id, p);
+                                    })
+                            })
+                    })?;
                 }
 
-                if !buf_cl_vc_metric.is_empty() {
-                    println!("\x1b[33;1mCL VALIDATOR METRIC RPCs:\x1b[0m");
-                    buf_cl_vc_metric.iter().for_each(|l| {
-                        println!("{l}");
-                    });
+                let t = Topology::build(en, probes);
+                if let Some(path) = dot {
+                    fs::write(path, t.to_dot()).c(d!(path))?;
                 }
-
-                Ok(())
+                print_topology(output.unwrap_or(OutputFormat::Table), &t)
             }
             Self::GetLogs {
                 local_dir,
@@ -1526,7 +2302,10 @@ impl CustomOps for ExtraOp {
                 let env = load_sysenv(en).c(d!())?;
 
                 let mut ids = if let Some(s) = nodes {
-                    parse_nodes_to_vec(s).map(Some).c(d!())?
+                    let sel = s.parse::<Selector>().c(d!(s))?;
+                    env_select_nodes(&env, &sel, true)
+                        .c(d!())
+                        .map(|ids| Some(ids.into_iter().collect::<Vec<_>>()))?
                 } else {
                     None
                 };
@@ -1553,11 +2332,35 @@ impl CustomOps for ExtraOp {
                         &format!("{CL_BN_DIR}/logs/{CL_BN_LOG_NAME}"),
                         &format!("{CL_VC_DIR}/logs/{CL_VC_LOG_NAME}"),
                         MGMT_LOG_NAME,
+                        EL_ERR_NAME,
+                        CL_BN_ERR_NAME,
+                        CL_VC_ERR_NAME,
                     ],
                     local_dir.as_deref(),
                 )
                 .c(d!())?;
 
+                // Show the last lines of the captured stdout/stderr
+                if nodes.is_none() && *failed {
+                    let nodes = ids
+                        .iter()
+                        .flatten()
+                        .map(|id| {
+                            let n = env
+                                .meta
+                                .nodes
+                                .get(id)
+                                .or_else(|| env.meta.fuhrers.get(id))
+                                .c(d!())?;
+                            Ok(FailedNode {
+                                node: node_brief(n).c(d!())?,
+                                err_tails: node_err_tails(n).c(d!())?,
+                            })
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    print_failed_nodes(OutputFormat::Table, &nodes, &[]).c(d!())?;
+                }
+
                 if errlist.is_empty() {
                     Ok(())
                 } else {
@@ -1569,22 +2372,149 @@ impl CustomOps for ExtraOp {
                 }
             }
             Self::DumpVcData { local_dir, nodes } => {
+                let env = load_sysenv(en).c(d!())?;
                 let ids = if let Some(s) = nodes {
-                    parse_nodes_to_vec(s).map(Some).c(d!())?
+                    let sel = s.parse::<Selector>().c(d!(s))?;
+                    env_select_nodes(&env, &sel, true)
+                        .c(d!())
+                        .map(|ids| Some(ids.into_iter().collect::<Vec<_>>()))?
                 } else {
                     None
                 };
-                load_sysenv(en).c(d!()).and_then(|env| {
-                    env_collect_tgz(
-                        &env,
-                        ids.as_deref(),
-                        &[CL_VC_DIR],
-                        local_dir.as_deref(),
-                    )
+                env_collect_tgz(&env, ids.as_deref(), &[CL_VC_DIR], local_dir.as_deref())
                     .c(d!())
-                })
             }
-            Self::SwitchELToGeth { nodes } => {
+            Self::Supervise {
+                interval_secs,
+                max_backoff_secs,
+                webhook,
+            } => {
+                let mut backoff = Backoff::new(*interval_secs, *max_backoff_secs);
+                println!("Supervising the ENV '{en}', checking every {interval_secs}s");
+
+                loop {
+                    // Reload each round to follow the changes made by other operations
+                    let env = load_sysenv(en).c(d!())?;
+                    let nodes = env
+                        .meta
+                        .fuhrers
+                        .values()
+                        .chain(env.meta.nodes.values())
+                        .filter(|n| env.meta.nodes_should_be_online.contains_key(&n.id))
+                        .collect::<Vec<_>>();
+
+                    for n in nodes {
+                        let process_cnt = match node_process_cnt(&env, n) {
+                            Ok(cnt) => cnt,
+                            Err(e) => {
+                                eprintln!("Node {}: {}", n.id, e.get_lowest_msg());
+                                continue;
+                            }
+                        };
+                        if 3 <= process_cnt {
+                            backoff.reset(n.id);
+                            continue;
+                        }
+                        let Some(attempt) = backoff.try_attempt(n.id, ts!()) else {
+                            continue;
+                        };
+
+                        // Always wait, the supervisor should not give up for a busy ENV
+                        let lock = match EnvLock::acquire(
+                            &lock_path("ddev", en),
+                            "supervise_restart",
+                            true,
+                        ) {
+                            Ok(lock) => lock,
+                            Err(e) => {
+                                eprintln!("Node {}: {}", n.id, e.get_lowest_msg());
+                                continue;
+                            }
+                        };
+
+                        // Checked again under the lock, the node may have been
+                        // stopped, kicked or restarted by others in the meantime
+                        let env = match load_sysenv(en) {
+                            Ok(env) => env,
+                            Err(e) => {
+                                eprintln!("Node {}: {}", n.id, e.get_lowest_msg());
+                                continue;
+                            }
+                        };
+                        let Some(n) = env
+                            .meta
+                            .fuhrers
+                            .get(&n.id)
+                            .or_else(|| env.meta.nodes.get(&n.id))
+                            .filter(|n| {
+                                env.meta.nodes_should_be_online.contains_key(&n.id)
+                            })
+                        else {
+                            continue;
+                        };
+                        let process_cnt = match node_process_cnt(&env, n) {
+                            Ok(cnt) if 3 <= cnt => {
+                                backoff.reset(n.id);
+                                continue;
+                            }
+                            Ok(cnt) => cnt,
+                            Err(e) => {
+                                eprintln!("Node {}: {}", n.id, e.get_lowest_msg());
+                                continue;
+                            }
+                        };
+
+                        let res = SysCfg {
+                            name: en.clone(),
+                            op: Op::<CustomInfo, Ports, ExtraOp>::Restart {
+                                nodes: Some([n.id].into()),
+                                ignore_failed: false,
+                                realloc_ports: false,
+                                wait_itv_secs: 1,
+                            },
+                        }
+                        .exec(CmdGenerator);
+
+                        let ev = RestartEvent {
+                            env: en.to_string(),
+                            node: n.id,
+                            host: n.host.host_id(),
+                            process_cnt,
+                            attempt,
+                            timestamp: ts!(),
+                            error: res.err().map(|e| e.get_lowest_msg().to_string()),
+                        };
+                        println!("{}", ev.log_line().trim());
+
+                        let audit_ev = OpEvent::new(
+                            en,
+                            "supervise_restart",
+                            Some([n.id].into()),
+                            &ev.error.clone().map_or(Ok(()), |e| Err(eg!(e))),
+                        );
+                        if let Err(e) = audit::append(&env.meta.home, &audit_ev) {
+                            eprintln!("Audit log: {}", e.get_lowest_msg());
+                        }
+                        let hooks = &env.meta.custom_data.hooks;
+                        if !hooks.is_empty() {
+                            hooks.fire(&audit_ev);
+                        }
+                        drop(lock);
+
+                        if let Err(e) = node_mgmt_log(n, &ev.log_line()) {
+                            eprintln!("Node {}: {}", n.id, e.get_lowest_msg());
+                        }
+                        if let Some(url) = webhook {
+                            if let Err(e) = ev.notify(url) {
+                                eprintln!("Webhook: {}", e.get_lowest_msg());
+                            }
+                        }
+                    }
+
+                    sleep_ms!(1000 * *interval_secs);
+                }
+            }
+            Self::SwitchELToGeth { nodes, dry_run } => {
                 let mut env = load_sysenv(en).c(d!())?;
 
                 let mut ns = vec![];
@@ -1602,6 +2532,19 @@ impl CustomOps for ExtraOp {
                     );
                 }
 
+                if *dry_run {
+                    for n in ns.iter() {
+                        let target = format!("node {} @ {}", n.id, n.host.host_id());
+                        dry_run_script(
+                            &target,
+                            &CmdGenerator.cmd_for_stop(n, &env.meta, false),
+                        );
+                        dry_run_script(&target, &format!("rm -rf {}/{EL_DIR}", n.home));
+                        dry_run_note(&target, "set the EL kind to geth in the ENV meta");
+                    }
+                    return Ok(());
+                }
+
                 SysCfg {
                     name: en.clone(),
                     op: Op::<CustomInfo, Ports, ExtraOp>::Stop {
@@ -1612,7 +2555,17 @@ impl CustomOps for ExtraOp {
                 .exec(CmdGenerator)
                 .c(d!())?;
 
-                sleep_ms!(3000); // wait for the graceful exiting process
+                // The stop script waits for the exiting of all processes,
+                // this is a double check before removing the data
+                for n in ns.iter() {
+                    let cnt = node_process_cnt(&env, n).c(d!())?;
+                    if 0 < cnt {
+                        return Err(eg!(
+                            "{cnt} processes of the node(id: {}) are still alive",
+                            n.id
+                        ));
+                    }
+                }
 
                 for (i, n) in ns.iter().enumerate() {
                     let remote = Remote::from(&n.host);
@@ -1647,7 +2600,7 @@ impl CustomOps for ExtraOp {
 
                 env.write_cfg().c(d!())
             }
-            Self::SwitchELToReth { nodes } => {
+            Self::SwitchELToReth { nodes, dry_run } => {
                 let mut env = load_sysenv(en).c(d!())?;
 
                 let mut ns = vec![];
@@ -1665,6 +2618,19 @@ impl CustomOps for ExtraOp {
                     );
                 }
 
+                if *dry_run {
+                    for n in ns.iter() {
+                        let target = format!("node {} @ {}", n.id, n.host.host_id());
+                        dry_run_script(
+                            &target,
+                            &CmdGenerator.cmd_for_stop(n, &env.meta, false),
+                        );
+                        dry_run_script(&target, &format!("rm -rf {}/{EL_DIR}", n.home));
+                        dry_run_note(&target, "set the EL kind to reth in the ENV meta");
+                    }
+                    return Ok(());
+                }
+
                 SysCfg {
                     name: en.clone(),
                     op: Op::<CustomInfo, Ports, ExtraOp>::Stop {
@@ -1675,7 +2641,17 @@ impl CustomOps for ExtraOp {
                 .exec(CmdGenerator)
                 .c(d!())?;
 
-                sleep_ms!(3000); // wait for the graceful exiting process
+                // The stop script waits for the exiting of all processes,
+                // this is a double check before removing the data
+                for n in ns.iter() {
+                    let cnt = node_process_cnt(&env, n).c(d!())?;
+                    if 0 < cnt {
+                        return Err(eg!(
+                            "{cnt} processes of the node(id: {}) are still alive",
+                            n.id
+                        ));
+                    }
+                }
 
                 for (i, n) in ns.iter().enumerate() {
                     let remote = Remote::from(&n.host);
@@ -1710,6 +2686,329 @@ impl CustomOps for ExtraOp {
 
                 env.write_cfg().c(d!())
             }
+            Self::Label {
+                nodes,
+                remove,
+                labels,
+                notes,
+            } => {
+                let mut env = load_sysenv(en).c(d!())?;
+                let sel = nodes.parse::<Selector>().c(d!(nodes))?;
+                let ids = env_select_nodes(&env, &sel, true).c(d!())?;
+                if ids.is_empty() {
+                    return Err(eg!("No target nodes found!"));
+                }
+
+                for id in ids.iter() {
+                    let n = env
+                        .meta
+                        .nodes
+                        .get_mut(id)
+                        .or_else(|| env.meta.fuhrers.get_mut(id))
+                        .unwrap();
+                    json_labels_update(&mut n.custom_data, *remove, labels, notes)
+                        .c(d!())?;
+                }
+
+                env.write_cfg().c(d!())
+            }
+            Self::Clone { src, with_data } => env_clone(src, en, *with_data).c(d!()),
+            Self::Export { path, redact_hosts } => {
+                env_export(en, path, *redact_hosts).c(d!())
+            }
+            Self::Import { path, hosts } => env_import(en, path, hosts).c(d!()),
+            Self::SnapshotCreate { name } => {
+                let env = load_sysenv(en).c(d!())?;
+                let home = env.meta.home.clone();
+                let online = online_nodes(&env);
+                snapshot::init(&home, name).c(d!())?;
+                let dir = snapshot::snapshot_dir(&home, name);
+
+                let res = fs::copy(format!("{home}/CONFIG"), format!("{dir}/CONFIG"))
+                    .c(d!())
+                    .and_then(|_| stop_all_nodes(en).c(d!()))
+                    .and_then(|_| {
+                        let nodes_dir = snapshot::nodes_dir(&home, name);
+                        env_collect_tgz(
+                            &env,
+                            None,
+                            &snapshot::SNAPSHOT_PATHS,
+                            Some(&nodes_dir),
+                        )
+                        .c(d!())
+                    })
+                    .and_then(|_| {
+                        snapshot::Manifest {
+                            name: name.clone(),
+                            created_at: ts!(),
+                            online: online.clone(),
+                            nodes: node_locations(&env),
+                        }
+                        .save(&home)
+                        .c(d!())
+                    });
+                if res.is_err() {
+                    // Do not leave a broken snapshot behind
+                    let _ = fs::remove_dir_all(&dir);
+                }
+
+                // The nodes are started again even if the snapshot failed
+                let started = start_nodes(en, &online).c(d!());
+                res.and(started)
+                    .map(|_| println!("Snapshot '{name}' is created at: {dir}"))
+            }
+            Self::SnapshotRestore { name } => {
+                let env = load_sysenv(en).c(d!())?;
+                let home = env.meta.home.clone();
+                let m = snapshot::Manifest::load(&home, name).c(d!())?;
+                m.check_nodes(&node_locations(&env)).c(d!())?;
+                let snap_env = snapshot_env(&home, name).c(d!())?;
+
+                let online = online_nodes(&env);
+
+                stop_all_nodes(en).c(d!())?;
+                let res = thread::scope(|s| {
+                    env.meta
+                        .fuhrers
+                        .values()
+                        .chain(env.meta.nodes.values())
+                        .map(|n| s.spawn(|| snapshot_restore_node(n, &home, name)))
+                        .collect::<Vec<_>>()
+                        .into_iter()
+                        .try_for_each(|hdr| {
+                            hdr.join()
+                                .map_err(|_| eg!("The restorer panicked"))
+                                .and_then(|r| r.c(d!()))
+                        })
+                });
+                if res.is_err() {
+                    // The nodes are started again even if the restoring failed
+                    let started = start_nodes(en, &online).c(d!());
+                    return res.and(started);
+                }
+                snapshot_restore_meta(en, snap_env).c(d!())?;
+
+                start_nodes(en, &m.online)
+                    .c(d!())
+                    .map(|_| println!("Snapshot '{name}' is restored"))
+            }
+            Self::SnapshotList => {
+                let env = load_sysenv(en).c(d!())?;
+                for m in snapshot::list(&env.meta.home).c(d!())? {
+                    println!(
+                        "{}\t{}\t{} nodes\t{} online",
+                        m.name,
+                        datetime!(m.created_at),
+                        m.nodes.len(),
+                        m.online.len()
+                    );
+                }
+                Ok(())
+            }
+            Self::SnapshotDelete { name } => {
+                let env = load_sysenv(en).c(d!())?;
+                snapshot::remove(&env.meta.home, name).c(d!())
+            }
+            Self::ChaosPartition {
+                groups,
+                duration_secs,
+            } => {
+                let env = load_sysenv(en).c(d!())?;
+                let partition = env_partition(&env, groups).c(d!())?;
+                env_hosts_exec(&env, partition.cmds_for_start(en, *duration_secs))
+                    .c(d!())?;
+                println!(
+                    "The P2P traffic between the groups is cut for {duration_secs}s, it heals automatically at the end"
+                );
+                Ok(())
+            }
+            Self::ChaosNetem { nodes, netem } => {
+                let mut env = load_sysenv(en).c(d!())?;
+                let sel = nodes.parse::<Selector>().c(d!(nodes))?;
+                let ids = env_select_nodes(&env, &sel, true).c(d!())?;
+                if ids.is_empty() {
+                    return Err(eg!("No target nodes found!"));
+                }
+                let hosts = env
+                    .meta
+                    .fuhrers
+                    .values()
+                    .chain(env.meta.nodes.values())
+                    .filter(|n| ids.contains(&n.id))
+                    .map(|n| n.host.host_id())
+                    .collect::<BTreeSet<_>>();
+                for id in ids {
+                    env.meta.custom_data.netem.insert(id, netem.clone());
+                }
+
+                // Recorded first, so `chaos clear` can always revert it
+                env.write_cfg().c(d!())?;
+                env_apply_netem(&env, &hosts).c(d!())
+            }
+            Self::ChaosClear => {
+                let mut env = load_sysenv(en).c(d!())?;
+                env.meta.custom_data.netem.clear();
+                env.write_cfg().c(d!())?;
+                let cmds = env_host_metas(&env)
+                    .into_iter()
+                    .map(|(host, h)| (host, chaos::cmd_for_clear(en, &h.addr.local_ip)))
+                    .collect();
+                env_hosts_exec(&env, cmds).c(d!())
+            }
+            Self::ChaosMonkey {
+                nodes,
+                interval_secs,
+                max_down,
+                duration_secs,
+                seed,
+            } => {
+                let env = load_sysenv(en).c(d!())?;
+                let sel = nodes.parse::<Selector>().c(d!(nodes))?;
+                let ids = env_select_nodes(&env, &sel, true).c(d!())?;
+                if ids.is_empty() {
+                    return Err(eg!("No target nodes found!"));
+                }
+                let cap = max_down.cap(env.meta.fuhrers.len() + env.meta.nodes.len());
+                if 0 == cap {
+                    return Err(eg!("No node can be down under the cap"));
+                }
+                let seed = seed.unwrap_or_else(chaos::new_monkey_seed);
+                let mut monkey = Monkey::new(seed, *interval_secs, cap, ids);
+                println!(
+                    "Chaos monkey on the ENV '{en}'(seed: {seed}), acting every {interval_secs}s, at most {cap} node(s) down, for {duration_secs}s"
+                );
+
+                let end = ts!() + duration_secs;
+                let mut next_ts = ts!() + interval_secs;
+                loop {
+                    let now = ts!();
+                    for id in monkey.due(now) {
+                        monkey_exec(en, id, "start", false);
+                    }
+                    if end <= now {
+                        break;
+                    }
+                    if next_ts <= now {
+                        next_ts += interval_secs;
+                        // Reload each round to follow the changes made by other operations
+                        let env = load_sysenv(en).c(d!())?;
+                        let offline = env
+                            .meta
+                            .fuhrers
+                            .keys()
+                            .chain(env.meta.nodes.keys())
+                            .filter(|id| {
+                                !env.meta.nodes_should_be_online.contains_key(id)
+                            })
+                            .copied()
+                            .collect::<BTreeSet<_>>();
+                        match monkey.next(now, &offline) {
+                            Some((id, action)) => {
+                                let force = chaos::MonkeyAction::Kill == action;
+                                monkey_exec(en, id, action.name(), force);
+                            }
+                            None => println!(
+                                "[{}] skipped, {} node(s) are down already",
+                                datetime!(),
+                                offline.len()
+                            ),
+                        }
+                    }
+                    sleep_ms!(1000);
+                }
+
+                for id in monkey.release_all() {
+                    monkey_exec(en, id, "start", false);
+                }
+                Ok(())
+            }
+            Self::History { filter, output } => {
+                let env = load_sysenv(en).c(d!())?;
+                let evs = audit::load(&env.meta.home).c(d!())?;
+                print_history(*output, &filter.apply(evs))
+            }
+            Self::Hook {
+                webhooks,
+                scripts,
+                remove,
+            } => {
+                let mut env = load_sysenv(en).c(d!())?;
+                let hooks = &mut env.meta.custom_data.hooks;
+                if *remove {
+                    hooks.webhooks.retain(|i| !webhooks.contains(i));
+                    hooks.scripts.retain(|i| !scripts.contains(i));
+                } else {
+                    hooks.webhooks.extend(webhooks.iter().cloned());
+                    hooks.scripts.extend(scripts.iter().cloned());
+                }
+                if !webhooks.is_empty() || !scripts.is_empty() {
+                    env.write_cfg().c(d!())?;
+                }
+
+                let hooks = &env.meta.custom_data.hooks;
+                hooks.webhooks.iter().for_each(|i| println!("webhook\t{i}"));
+                hooks.scripts.iter().for_each(|i| println!("script\t{i}"));
+                Ok(())
+            }
+            Self::ExportJoinKit { dir } => {
+                let env = load_sysenv(en).c(d!())?;
+                let (el_rpc_endpoints, cl_bn_rpc_endpoints): (Vec<_>, Vec<_>) = env
+                    .meta
+                    .fuhrers
+                    .values()
+                    .chain(env.meta.nodes.values())
+                    .filter(|n| env.meta.nodes_should_be_online.contains_key(&n.id))
+                    .map(|n| {
+                        (
+                            format!(
+                                "http://{}:{}",
+                                n.host.addr.connection_addr(),
+                                n.ports.el_rpc
+                            ),
+                            format!(
+                                "http://{}:{}",
+                                n.host.addr.connection_addr(),
+                                n.ports.cl_bn_rpc
+                            ),
+                        )
+                    })
+                    .unzip();
+
+                let el_rpc_endpoints = el_rpc_endpoints
+                    .iter()
+                    .map(|i| i.as_str())
+                    .collect::<Vec<_>>();
+                let cl_bn_rpc_endpoints = cl_bn_rpc_endpoints
+                    .iter()
+                    .map(|i| i.as_str())
+                    .collect::<Vec<_>>();
+                let enodes = el_get_boot_nodes(&el_rpc_endpoints)
+                    .c(d!("Failed to get the enodes, are the nodes running?"))?;
+                let (_, enrs, _) = cl_get_boot_nodes(&cl_bn_rpc_endpoints)
+                    .c(d!("Failed to get the ENRs, are the nodes running?"))?;
+                let split = |s: String| s.split(',').map(|i| i.to_owned()).collect();
+                // The dedicated bootnodes go first, no node is excluded
+                let (enodes, enrs) = if let Some((e, r, _)) =
+                    dedicated_bootnodes(&env.meta, NodeID::MAX)
+                {
+                    (format!("{e},{enodes}"), format!("{r},{enrs}"))
+                } else {
+                    (enodes, enrs)
+                };
+
+                let genesis_dir =
+                    format!("{}/{NODE_HOME_GENESIS_DIR_DST}", env.meta.home);
+                JoinKit {
+                    env_name: en.to_string(),
+                    chain_id: genesis_chain_id(&genesis_dir).c(d!())?,
+                    deposit_contract: genesis_deposit_contract(&genesis_dir).c(d!())?,
+                    enodes: split(enodes),
+                    enrs: split(enrs),
+                }
+                .write(&genesis_dir, dir)
+                .c(d!())
+                .map(|_| println!("The join kit is exported to: {dir}"))
+            }
             Self::Git { remote_url, push } => {
                 let env_home = load_sysenv(en).c(d!())?.meta.home;
 
@@ -1726,6 +3025,13 @@ impl CustomOps for ExtraOp {
                     cmd.push_str(&piece);
                 }
 
+                // The audit log is not committed by the ENV itself
+                cmd.push_str(&format!(
+                    "if [ -n \"$(git status --porcelain {0})\" ]; then \
+                    git add {0} && git commit -m 'audit log' >/dev/null || exit 1; fi;",
+                    audit::AUDIT_LOG_NAME
+                ));
+
                 if *push {
                     cmd.push_str("git push expchain HEAD:master")
                 }
@@ -1734,8 +3040,1051 @@ impl CustomOps for ExtraOp {
                     println!("{s}");
                 })
             }
+            Self::Apply {
+                spec_path,
+                plan_only,
+            } => {
+                let spec = EnvSpec::from_file(spec_path).c(d!())?;
+                let spec_hosts =
+                    spec.hosts.as_deref().map(parse_cfg).transpose().c(d!())?;
+
+                let env = load_sysenv(en).c(d!())?;
+                let plan = env_plan(&env, &spec, spec_hosts.as_ref()).c(d!())?;
+
+                print!("{plan}");
+
+                if *plan_only || plan.is_empty() {
+                    return Ok(());
+                }
+
+                let exec = |op: Op<CustomInfo, Ports, ExtraOp>| {
+                    SysCfg {
+                        name: en.clone(),
+                        op,
+                    }
+                    .exec(CmdGenerator)
+                };
+
+                if let Some(mut hosts) = spec_hosts {
+                    hosts
+                        .as_mut()
+                        .retain(|id, _| plan.hosts_to_push.contains(id));
+                    if !hosts.as_ref().is_empty() {
+                        exec(Op::PushHosts { hosts }).c(d!())?;
+                    }
+                }
+
+                if !plan.binaries.is_empty() {
+                    let mut env = load_sysenv(en).c(d!())?;
+                    for (field, v) in plan.binaries.iter().cloned() {
+                        match field {
+                            "el_geth_bin" => env.meta.custom_data.el_geth_bin = v,
+                            "el_reth_bin" => env.meta.custom_data.el_reth_bin = v,
+                            "cl_bin" => env.meta.custom_data.cl_bin = v,
+                            _ => unreachable!(),
+                        }
+                    }
+                    env.write_cfg().c(d!())?;
+                }
+
+                for (nodes, to_geth) in
+                    [(&plan.switch_to_geth, true), (&plan.switch_to_reth, false)]
+                {
+                    if nodes.is_empty() {
+                        continue;
+                    }
+                    let op = alt!(
+                        to_geth,
+                        Self::SwitchELToGeth {
+                            nodes: nodes.clone(),
+                            dry_run: false,
+                        },
+                        Self::SwitchELToReth {
+                            nodes: nodes.clone(),
+                            dry_run: false,
+                        }
+                    );
+                    op.exec(en).c(d!())?;
+                    // The switched nodes are left stopped
+                    exec(Op::Start {
+                        nodes: Some(nodes.clone()),
+                        ignore_failed: false,
+                        realloc_ports: false,
+                    })
+                    .c(d!())?;
+                }
+
+                if !plan.kick.is_empty() {
+                    exec(Op::KickNodes {
+                        nodes: Some(plan.kick.clone()),
+                        num: 0,
+                        force: false,
+                    })
+                    .c(d!())?;
+                }
+
+                for (g, mut n) in plan.push.iter().map(|(g, n)| (*g, *n)) {
+                    let custom_data = alt!(
+                        NodeGroup::Reth == g,
+                        NodeCustomData::new_with_reth(),
+                        NodeCustomData::new_with_geth()
+                    );
+                    while 0 < n {
+                        let num = n.min(u8::MAX as usize);
+                        exec(Op::PushNodes {
+                            host: None,
+                            custom_data: custom_data.to_json_value(),
+                            fullnode: NodeGroup::FullNode == g,
+                            num: num as u8,
+                        })
+                        .c(d!())?;
+                        n -= num;
+                    }
+                }
+
+                // Re-plan with the latest state,
+                // the new nodes have been assigned with their IDs now
+                let env = load_sysenv(en).c(d!())?;
+                let plan = env_plan(&env, &spec, None).c(d!())?;
+                for (num_per_node, nodes) in plan.deposits.iter() {
+                    Self::Deposit {
+                        nodes: nodes
+                            .iter()
+                            .map(|id| id.to_string())
+                            .collect::<Vec<_>>()
+                            .join(","),
+                        num_per_node: *num_per_node,
+                        wallet_seckey_path: None,
+                        withdraw_0x01_addr: None,
+                        async_wait: false,
+                        dry_run: false,
+                    }
+                    .exec(en)
+                    .c(d!())?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+fn env_plan(
+    env: &SysEnv<CustomInfo, Ports, CmdGenerator>,
+    spec: &EnvSpec,
+    spec_hosts: Option<&Hosts>,
+) -> Result<Plan> {
+    let nodes = env
+        .meta
+        .nodes
+        .values()
+        .map(|n| {
+            Ok(NodeState {
+                id: n.id,
+                group: NodeGroup::new(&n.kind, json_el_kind(&n.custom_data).c(d!())?),
+                validators: json_deposits_cnt(&n.custom_data).c(d!())?,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let existing_hosts = env.meta.hosts.as_ref().keys().cloned().collect();
+    let spec_hosts = spec_hosts
+        .map(|h| h.as_ref().keys().cloned().collect())
+        .unwrap_or_default();
+
+    let c = &env.meta.custom_data;
+    let b = &spec.binaries;
+    let binaries = [
+        (
+            "el_geth_bin",
+            c.el_geth_bin.as_str(),
+            b.el_geth_bin.as_deref(),
+        ),
+        (
+            "el_reth_bin",
+            c.el_reth_bin.as_str(),
+            b.el_reth_bin.as_deref(),
+        ),
+        ("cl_bin", c.cl_bin.as_str(), b.cl_bin.as_deref()),
+    ];
+
+    Ok(Plan::new(
+        spec,
+        &nodes,
+        &existing_hosts,
+        &spec_hosts,
+        &binaries,
+    ))
+}
+
+fn node_brief(n: &Node<Ports>) -> Result<NodeBrief> {
+    let kind = if json_bootnode(&n.custom_data).c(d!())?.is_some() {
+        "Bootnode".to_owned()
+    } else {
+        n.kind.to_string()
+    };
+    Ok(NodeBrief {
+        id: n.id,
+        kind,
+        el: json_el_kind(&n.custom_data).c(d!())?.to_string(),
+        host: n.host.host_id(),
+        labels: json_labels(&n.custom_data).c(d!())?,
+        notes: json_notes(&n.custom_data).c(d!())?,
+    })
+}
+
+fn node_process_cnt(
+    env: &SysEnv<CustomInfo, Ports, CmdGenerator>,
+    n: &Node<Ports>,
+) -> Result<u64> {
+    Remote::from(&n.host)
+        .exec_cmd(&CmdGenerator.cmd_cnt_running(n, &env.meta))
+        .c(d!())?
+        .trim()
+        .parse::<u64>()
+        .c(d!())
+}
+
+// The RPC queries are best-effort,
+// and are skipped if no process of the node is alive
+fn node_health(
+    env: &SysEnv<CustomInfo, Ports, CmdGenerator>,
+    n: &Node<Ports>,
+) -> Result<NodeHealth> {
+    let process_cnt = node_process_cnt(env, n).c(d!())?;
+    let should_be_online = env.meta.nodes_should_be_online.contains_key(&n.id);
+
+    let (el_block_number, (cl_head_slot, cl_is_syncing)) = if 0 < process_cnt {
+        let addr = n.host.addr.connection_addr();
+        (
+            el_get_block_number(&format!("http://{addr}:{}", n.ports.el_rpc)).ok(),
+            cl_get_sync_status(&format!("http://{addr}:{}", n.ports.cl_bn_rpc))
+                .ok()
+                .unzip(),
+        )
+    } else {
+        (None, (None, None))
+    };
+
+    Ok(NodeHealth {
+        node: node_brief(n).c(d!())?,
+        status: NodeStatus::new(should_be_online, process_cnt),
+        process_cnt,
+        should_be_online,
+        el_block_number,
+        cl_head_slot,
+        cl_is_syncing,
+    })
+}
+
+// An action of the chaos monkey, under the lock of the ENV,
+// `action` is one of 'stop', 'kill', 'restart' and 'start';
+// failures are reported and recorded, they do not stop the monkey
+fn monkey_exec(en: &EnvName, id: NodeID, action: &str, force: bool) {
+    let operation = format!("chaos_monkey_{action}");
+    let stop = Op::<CustomInfo, Ports, ExtraOp>::Stop {
+        nodes: Some([id].into()),
+        force,
+    };
+    let start = Op::<CustomInfo, Ports, ExtraOp>::Start {
+        nodes: Some([id].into()),
+        ignore_failed: false,
+        realloc_ports: false,
+    };
+    let ops = match action {
+        "start" => vec![start],
+        "restart" => vec![stop, start],
+        _ => vec![stop],
+    };
+
+    // Always wait, like the supervisor
+    let res = EnvLock::acquire(&lock_path("ddev", en), &operation, true)
+        .c(d!())
+        .and_then(|lock| {
+            let res = ops.into_iter().try_for_each(|op| {
+                SysCfg {
+                    name: en.clone(),
+                    op,
+                }
+                .exec(CmdGenerator)
+                .c(d!())
+            });
+            let ev = OpEvent::new(en, &operation, Some([id].into()), &res);
+            match load_sysenv(en) {
+                Ok(env) => {
+                    if let Err(e) = audit::append(&env.meta.home, &ev) {
+                        eprintln!("Audit log: {}", e.get_lowest_msg());
+                    }
+                    let hooks = &env.meta.custom_data.hooks;
+                    if !hooks.is_empty() {
+                        hooks.fire(&ev);
+                    }
+                }
+                Err(e) => eprintln!("Audit log: {}", e.get_lowest_msg()),
+            }
+            drop(lock);
+            res
+        });
+
+    match res {
+        Ok(_) => println!("[{}] node {id}: {action}", datetime!()),
+        Err(e) => println!(
+            "[{}] node {id}: {action}, failed: {}",
+            datetime!(),
+            e.get_lowest_msg()
+        ),
+    }
+}
+
+fn env_partition(
+    env: &SysEnv<CustomInfo, Ports, CmdGenerator>,
+    groups: &str,
+) -> Result<Partition> {
+    let groups = chaos::parse_groups(groups)
+        .c(d!())?
+        .iter()
+        .map(|sel| {
+            env_select_nodes(env, sel, true).c(d!()).map(|ids| {
+                env.meta
+                    .fuhrers
+                    .values()
+                    .chain(env.meta.nodes.values())
+                    .filter(|n| ids.contains(&n.id))
+                    .map(node_p2p)
+                    .collect()
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Partition::new(groups).c(d!())
+}
+
+// Rebuild the shaping of the hosts from the impairments in the ENV meta
+fn env_apply_netem(
+    env: &SysEnv<CustomInfo, Ports, CmdGenerator>,
+    hosts: &BTreeSet<String>,
+) -> Result<()> {
+    let metas = env_host_metas(env);
+    let mut nodes = hosts
+        .iter()
+        .map(|h| (h.clone(), vec![]))
+        .collect::<BTreeMap<_, _>>();
+    for n in env.meta.fuhrers.values().chain(env.meta.nodes.values()) {
+        if let (Some(netem), Some(ns)) = (
+            env.meta.custom_data.netem.get(&n.id),
+            nodes.get_mut(&n.host.host_id()),
+        ) {
+            ns.push((node_p2p(n), netem.clone()));
+        }
+    }
+    let cmds = nodes
+        .into_iter()
+        .map(|(host, ns)| {
+            chaos::cmd_for_netem(&metas[&host].addr.local_ip, &ns)
+                .c(d!(host))
+                .map(|cmd| (host, cmd))
+        })
+        .collect::<Result<BTreeMap<_, _>>>()?;
+    env_hosts_exec(env, cmds).c(d!())
+}
+
+// Run the cmds on the hosts concurrently, <host ID, cmd>
+fn env_hosts_exec(
+    env: &SysEnv<CustomInfo, Ports, CmdGenerator>,
+    cmds: BTreeMap<String, String>,
+) -> Result<()> {
+    let metas = env_host_metas(env);
+    let cmds = cmds.into_iter().collect::<Vec<_>>();
+
+    let mut errlist = vec![];
+    for chunk in cmds.chunks(24) {
+        thread::scope(|s| {
+            chunk
+                .iter()
+                .map(|(host, cmd)| {
+                    let h = metas[host];
+                    s.spawn(move || Remote::from(h).exec_cmd(cmd).c(d!(host)))
+                })
+                .collect::<Vec<_>>()
+                .into_iter()
+                .for_each(|hdr| match hdr.join() {
+                    Ok(Ok(_)) => {}
+                    Ok(Err(e)) => errlist.push(e),
+                    Err(_) => errlist.push(eg!("The executor panicked")),
+                });
+        });
+    }
+
+    if errlist.is_empty() {
+        Ok(())
+    } else {
+        Err(eg!(errlist
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")))
+    }
+}
+
+fn node_p2p(n: &Node<Ports>) -> P2pNode {
+    let addr = &n.host.addr;
+    let addrs = [Some(&addr.local_ip), addr.ext_ip.as_ref()]
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect::<BTreeSet<_>>();
+    P2pNode::new(
+        n.id,
+        &n.host.host_id(),
+        addrs.into_iter().collect(),
+        &n.home,
+        &n.ports,
+    )
+}
+
+// <host ID, host meta> of the hosts that have nodes of the ENV
+fn env_host_metas(
+    env: &SysEnv<CustomInfo, Ports, CmdGenerator>,
+) -> BTreeMap<String, &HostMeta> {
+    env.meta
+        .fuhrers
+        .values()
+        .chain(env.meta.nodes.values())
+        .map(|n| (n.host.host_id(), &n.host))
+        .collect()
+}
+
+fn node_facts(
+    env: &SysEnv<CustomInfo, Ports, CmdGenerator>,
+    n: &Node<Ports>,
+) -> Result<NodeFacts> {
+    Ok(NodeFacts {
+        id: n.id,
+        hosts: [
+            Some(n.host.host_id()),
+            Some(n.host.addr.local_ip.clone()),
+            n.host.addr.ext_ip.clone(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect(),
+        el: json_el_kind(&n.custom_data).c(d!())?,
+        kind: n.kind.to_string(),
+        fuhrer: env.meta.fuhrers.contains_key(&n.id),
+        labels: json_labels(&n.custom_data).c(d!())?,
+        validators: json_deposits_cnt(&n.custom_data).c(d!())?,
+    })
+}
+
+// Append to the 'mgmt.log' of the node
+fn node_mgmt_log(n: &Node<Ports>, log: &str) -> Result<()> {
+    Remote::from(&n.host)
+        .append_file(format!("{}/{MGMT_LOG_NAME}", n.home), log.as_bytes())
+        .c(d!())
+}
+
+// The last lines of the captured stdout/stderr of each process
+fn node_err_tails(n: &Node<Ports>) -> Result<BTreeMap<String, Vec<String>>> {
+    Remote::from(&n.host)
+        .exec_cmd(&process::cmd_tails(&n.home, &ERR_FILES, ERR_TAIL_LINES))
+        .c(d!())
+        .map(|out| process::parse_tails(&out))
+}
+
+fn env_failed_nodes(
+    env: &SysEnv<CustomInfo, Ports, CmdGenerator>,
+) -> Result<BTreeSet<NodeID>> {
+    let (failed_cases, errlist) = env.collect_failed_nodes();
+    if errlist.is_empty() {
+        Ok(failed_cases.into_values().flatten().collect())
+    } else {
+        Err(eg!(errlist
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")))
+    }
+}
+
+// <node ID, location>, see `snapshot::Manifest`
+fn node_locations(
+    env: &SysEnv<CustomInfo, Ports, CmdGenerator>,
+) -> BTreeMap<NodeID, String> {
+    env.meta
+        .fuhrers
+        .values()
+        .chain(env.meta.nodes.values())
+        .map(|n| {
+            (
+                n.id,
+                format!("{}:{}", n.host.addr.connection_addr(), n.home),
+            )
+        })
+        .collect()
+}
+
+fn online_nodes(env: &SysEnv<CustomInfo, Ports, CmdGenerator>) -> BTreeSet<NodeID> {
+    env.meta
+        .fuhrers
+        .keys()
+        .chain(env.meta.nodes.keys())
+        .filter(|id| env.meta.nodes_should_be_online.contains_key(id))
+        .copied()
+        .collect()
+}
+
+fn stop_all_nodes(en: &EnvName) -> Result<()> {
+    SysCfg {
+        name: en.clone(),
+        op: Op::<CustomInfo, Ports, ExtraOp>::Stop {
+            nodes: None,
+            force: false,
+        },
+    }
+    .exec(CmdGenerator)
+    .c(d!())
+}
+
+fn start_nodes(en: &EnvName, nodes: &BTreeSet<NodeID>) -> Result<()> {
+    if nodes.is_empty() {
+        return Ok(());
+    }
+    SysCfg {
+        name: en.clone(),
+        op: Op::<CustomInfo, Ports, ExtraOp>::Start {
+            nodes: Some(nodes.clone()),
+            ignore_failed: false,
+            realloc_ports: false,
+        },
+    }
+    .exec(CmdGenerator)
+    .c(d!())
+}
+
+// The ENV meta in the snapshot, migrated to the current schema
+// Only the parts that go with the node data are taken from the snapshot,
+// i.e. the nodes(except their labels and notes) and the online set,
+// the rest of the meta, e.g. the hooks and the chaos records, is kept as it is
+fn snapshot_restore_meta(
+    en: &EnvName,
+    snap_env: SysEnv<CustomInfo, Ports, CmdGenerator>,
+) -> Result<()> {
+    let mut env = load_sysenv(en).c(d!())?;
+    let mut snap = snap_env.meta;
+    for n in snap.fuhrers.values_mut().chain(snap.nodes.values_mut()) {
+        if let Some(cur) = env
+            .meta
+            .fuhrers
+            .get(&n.id)
+            .or_else(|| env.meta.nodes.get(&n.id))
+        {
+            json_labels_copy(&mut n.custom_data, &cur.custom_data).c(d!())?;
+        }
+    }
+    env.meta.fuhrers = snap.fuhrers;
+    env.meta.nodes = snap.nodes;
+    env.meta.nodes_should_be_online = snap.nodes_should_be_online;
+    env.write_cfg().c(d!())
+}
+
+fn snapshot_env(
+    env_home: &str,
+    name: &str,
+) -> Result<SysEnv<CustomInfo, Ports, CmdGenerator>> {
+    let p = format!("{}/CONFIG", snapshot::snapshot_dir(env_home, name));
+    let mut cfg = fs::read(&p)
+        .c(d!(p))
+        .and_then(|c| serde_json::from_slice(&c).c(d!()))?;
+    schema::migrate(&mut cfg).c(d!())?;
+    serde_json::from_value(cfg).c(d!())
+}
+
+// Put the packages to the host of the node, then replace the node data with them
+fn snapshot_restore_node(n: &Node<Ports>, env_home: &str, name: &str) -> Result<()> {
+    let remote = Remote::from(&n.host);
+    let mut remote_tgzs = vec![];
+    for tgz in snapshot::node_packages(env_home, name, n.id).c(d!())? {
+        let remote_tgz = format!("/tmp/{}", tgz.rsplit('/').next().c(d!())?);
+        remote.put_file(&tgz, &remote_tgz).c(d!())?;
+        remote_tgzs.push(remote_tgz);
+    }
+    let cmd = format!(
+        "{} rm -f {}",
+        snapshot::cmd_unpack(&n.home, &remote_tgzs),
+        remote_tgzs.join(" ")
+    );
+    remote.exec_cmd(&cmd).c(d!(cmd)).map(|_| ())
+}
+
+// The enodes and ENRs of the dedicated bootnodes, and a checkpoint sync URL,
+// `None` if there are no bootnodes in the ENV
+fn dedicated_bootnodes(
+    e: &EnvMeta<CustomInfo, Node<Ports>>,
+    id: NodeID,
+) -> Option<(String, String, String)> {
+    let mut enodes = vec![];
+    let mut enrs = vec![];
+    for o in e.fuhrers.values().chain(e.nodes.values()) {
+        let Some(b) = pnk!(json_bootnode(&o.custom_data)) else {
+            continue;
+        };
+        if o.id == id {
+            continue;
+        }
+        match b.peer(
+            o.id,
+            o.host.addr.connection_addr(),
+            o.ports.el_discovery,
+            o.ports.cl_discovery,
+        ) {
+            Ok((enode, enr)) => {
+                enodes.push(enode);
+                enrs.push(enr);
+            }
+            Err(err) => info_omit!(Err::<(), _>(err)),
+        }
+    }
+    if enodes.is_empty() {
+        return None;
+    }
+
+    // A deterministic choice, the first online regular node
+    let url = e
+        .nodes_should_be_online
+        .iter()
+        .map(|(k, _)| k)
+        .filter(|k| *k != id)
+        .filter_map(|k| e.fuhrers.get(&k).or_else(|| e.nodes.get(&k)))
+        .find(|o| matches!(json_bootnode(&o.custom_data), Ok(None)))
+        .map(|o| {
+            format!(
+                "http://{}:{}",
+                o.host.addr.connection_addr(),
+                o.ports.cl_bn_rpc
+            )
+        })
+        .unwrap_or_default();
+
+    Some((enodes.join(","), enrs.join(","), url))
+}
+
+// Record the enodes and ENRs of the dedicated bootnodes, for reference
+fn record_bootnodes(en: &EnvName) -> Result<()> {
+    let mut env = load_sysenv(en).c(d!())?;
+    let mut changed = false;
+    for n in env.meta.nodes.values_mut() {
+        let Some(mut b) = json_bootnode(&n.custom_data).c(d!())? else {
+            continue;
+        };
+        let (enode, enr) = b
+            .peer(
+                n.id,
+                n.host.addr.connection_addr(),
+                n.ports.el_discovery,
+                n.ports.cl_discovery,
+            )
+            .c(d!())?;
+        if b.enode != enode || b.enr != enr {
+            b.enode = enode;
+            b.enr = enr;
+            json_bootnode_set(&mut n.custom_data, b).c(d!())?;
+            changed = true;
+        }
+    }
+    alt!(changed, env.write_cfg().c(d!()), Ok(()))
+}
+
+fn record_genesis_deposits(en: &EnvName) -> Result<()> {
+    let mut env = load_sysenv(en).c(d!())?;
+    let fuhrer = env
+        .meta
+        .fuhrers
+        .values_mut()
+        .next()
+        .c(d!("no fuhrer node"))?;
+    let map = map! {B
+        env.meta.genesis_mnemonic_words.clone() => (0..env.meta.genesis_validator_num).collect()
+    };
+    json_deposits_append(&mut fuhrer.custom_data, map)
+        .c(d!())
+        .and_then(|_| env.write_cfg().c(d!()))
+}
+
+// `clone`, re-create the shape of `src_name` as `dst_name`,
+// the node pairs of <source node ID, new node ID> are returned
+fn env_clone_shape(
+    src: &SysEnv<CustomInfo, Ports, CmdGenerator>,
+    dst_name: &EnvName,
+    with_data: bool,
+) -> Result<Vec<(NodeID, NodeID)>> {
+    let exec = |op: Op<CustomInfo, Ports, ExtraOp>| {
+        SysCfg {
+            name: dst_name.clone(),
+            op,
+        }
+        .exec(CmdGenerator)
+        .c(d!())
+    };
+
+    // The same genesis is needed by the same chain state,
+    // and by the same external network
+    let tmp = format!("/tmp/exp_clone_{}_{}", dst_name, ts!());
+    let reuse_genesis = with_data || src.meta.custom_data.external.is_some();
+    let (genesis_tgz_path, genesis_vkeys_tgz_path) = if reuse_genesis {
+        let genesis = format!("{tmp}/genesis.tar.gz");
+        let vkeys = format!("{tmp}/vcdata.tar.gz");
+        fs::create_dir_all(&tmp)
+            .c(d!())
+            .and_then(|_| fs::write(&genesis, &src.meta.genesis).c(d!()))
+            .and_then(|_| fs::write(&vkeys, &src.meta.genesis_vkeys).c(d!()))?;
+        (Some(genesis), Some(vkeys))
+    } else {
+        (None, None)
+    };
+
+    let created = exec(Op::Create {
+        opts: SysOpts {
+            hosts: {
+                let mut hosts = src.meta.hosts.clone();
+                hosts.as_mut().values_mut().for_each(|h| h.node_cnt = 0);
+                hosts
+            },
+            block_itv: src.meta.block_itv,
+            genesis_pre_settings: src.meta.genesis_pre_settings.clone(),
+            genesis_tgz_path,
+            genesis_vkeys_tgz_path,
+            custom_data: CustomInfo {
+                // Hooks are not a part of the network shape
+                hooks: Default::default(),
+                schema_version: SCHEMA_VERSION,
+                // The nodes of the new ENV have their own identities
+                identity_seed: p2p::new_seed(),
+                // Faults are not a part of the network shape either
+                netem: Default::default(),
+                ..src.meta.custom_data.clone()
+            },
+            force_create: false,
+        },
+    });
+    omit!(fs::remove_dir_all(&tmp));
+    created.and_then(|_| record_genesis_deposits(dst_name).c(d!()))?;
+
+    let dst = load_sysenv(dst_name).c(d!())?;
+    let mut pairs = src
+        .meta
+        .fuhrers
+        .keys()
+        .zip(dst.meta.fuhrers.keys())
+        .map(|(s, d)| (*s, *d))
+        .collect::<Vec<_>>();
+
+    for n in src.meta.nodes.values() {
+        let custom_data = NodeCustomData {
+            el_kind: json_el_kind(&n.custom_data).c(d!())?,
+            labels: json_labels(&n.custom_data).c(d!())?,
+            notes: json_notes(&n.custom_data).c(d!())?,
+            // The same role with new keys
+            bootnode: json_bootnode(&n.custom_data).c(d!())?.map(|b| b.renew()),
+            ..Default::default()
+        };
+        exec(Op::PushNodes {
+            host: Some(n.host.addr.clone()),
+            custom_data: custom_data.to_json_value(),
+            fullnode: matches!(n.kind, NodeKind::FullNode),
+            num: 1,
+        })?;
+        let id = load_sysenv(dst_name)
+            .c(d!())?
+            .meta
+            .nodes
+            .keys()
+            .max()
+            .copied()
+            .c(d!())?;
+        pairs.push((n.id, id));
+    }
+
+    Ok(pairs)
+}
+
+fn env_clone(src_name: &EnvName, dst_name: &EnvName, with_data: bool) -> Result<()> {
+    schema::ensure("ddev", src_name, false).c(d!())?;
+    let _lock =
+        EnvLock::acquire(&lock_path("ddev", src_name), "clone", false).c(d!())?;
+    let src = load_sysenv(src_name).c(d!())?;
+
+    let pairs = env_clone_shape(&src, dst_name, with_data).c(d!())?;
+    for (s, d) in pairs.iter() {
+        println!("node {s} => node {d}");
+    }
+    if !with_data {
+        return Ok(());
+    }
+
+    let src_online = online_nodes(&src);
+    stop_all_nodes(dst_name).c(d!())?;
+
+    // A consistent copy needs the source nodes to be stopped
+    let copied = stop_all_nodes(src_name).c(d!()).and_then(|_| {
+        let mut dst = load_sysenv(dst_name).c(d!())?;
+        let node = |env: &SysEnv<CustomInfo, Ports, CmdGenerator>, id: &NodeID| {
+            env.meta
+                .fuhrers
+                .get(id)
+                .or_else(|| env.meta.nodes.get(id))
+                .cloned()
+                .c(d!())
+        };
+        let nodes = pairs
+            .iter()
+            .map(|(s, d)| Ok((node(&src, s)?, node(&dst, d)?)))
+            .collect::<Result<Vec<_>>>()?;
+
+        thread::scope(|s| {
+            nodes
+                .iter()
+                .map(|(sn, dn)| s.spawn(|| clone_node_data(sn, dn)))
+                .collect::<Vec<_>>()
+                .into_iter()
+                .try_for_each(|hdr| {
+                    hdr.join()
+                        .map_err(|_| eg!("The copier panicked"))
+                        .and_then(|r| r.c(d!()))
+                })
+        })?;
+
+        // The deposits are a part of the chain state,
+        // bootnodes keep their new keys
+        for (sn, dn) in nodes.iter() {
+            if json_bootnode(&dn.custom_data).c(d!())?.is_some() {
+                continue;
+            }
+            if let Some(n) = dst.meta.fuhrers.get_mut(&dn.id) {
+                n.custom_data = sn.custom_data.clone();
+            } else if let Some(n) = dst.meta.nodes.get_mut(&dn.id) {
+                n.custom_data = sn.custom_data.clone();
+            }
         }
+        dst.write_cfg().c(d!())
+    });
+
+    // The source nodes are started again even if the copy failed
+    let restarted = start_nodes(src_name, &src_online).c(d!());
+    copied.and(restarted)?;
+
+    // The ports of the new nodes may have been occupied during the copy
+    SysCfg {
+        name: dst_name.clone(),
+        op: Op::<CustomInfo, Ports, ExtraOp>::Start {
+            nodes: Some(pairs.iter().map(|(_, d)| *d).collect()),
+            ignore_failed: false,
+            realloc_ports: true,
+        },
+    }
+    .exec(CmdGenerator)
+    .c(d!())
+}
+
+// Copy the data of `src` to `dst` through the localhost,
+// they may be located on different hosts
+fn clone_node_data(src: &Node<Ports>, dst: &Node<Ports>) -> Result<()> {
+    let name = format!("exp_clone_N{}_N{}_{}.tgz", src.id, dst.id, ts!());
+    let tgz = format!("/tmp/{name}");
+
+    let src_remote = Remote::from(&src.host);
+    let dst_remote = Remote::from(&dst.host);
+
+    let res = src_remote
+        .exec_cmd(&snapshot::cmd_pack_relative(&src.home, &tgz))
+        .c(d!())
+        .and_then(|_| src_remote.get_file(&tgz, &tgz).c(d!()))
+        .and_then(|_| dst_remote.put_file(&tgz, &tgz).c(d!()))
+        .and_then(|_| {
+            dst_remote
+                .exec_cmd(&snapshot::cmd_unpack_as_clone(&dst.home, &tgz))
+                .c(d!())
+        });
+
+    omit!(fs::remove_file(&tgz));
+    omit!(src_remote.exec_cmd(&format!("rm -f {tgz}")));
+
+    res.map(|_| ())
+}
+
+// `export`, the ENV meta, the genesis, the validator keys,
+// the host inventory and the checksums of the client binaries
+fn env_export(en: &EnvName, path: &str, redact: bool) -> Result<()> {
+    let mut env = load_sysenv(en).c(d!())?;
+    let binaries = binary_checksums(&env).c(d!())?;
+    if redact {
+        redact_hosts(&mut env);
+    }
+
+    let manifest = bundle::Manifest {
+        format_version: bundle::BUNDLE_FORMAT_VERSION,
+        env_name: en.to_string(),
+        exported_at: ts!(),
+        redacted: redact,
+        hosts: env.meta.hosts.clone(),
+        binaries,
+    };
+
+    let tmp = format!("/tmp/exp_export_{}_{}", en, ts!());
+    let file = |name: &str| format!("{tmp}/{name}");
+    let res = fs::create_dir_all(&tmp)
+        .c(d!())
+        .and_then(|_| manifest.save(&tmp).c(d!()))
+        .and_then(|_| serde_json::to_vec_pretty(&env).c(d!()))
+        .and_then(|cfg| fs::write(file(bundle::CONFIG_NAME), cfg).c(d!()))
+        .and_then(|_| fs::write(file(bundle::GENESIS_NAME), &env.meta.genesis).c(d!()))
+        .and_then(|_| {
+            fs::write(file(bundle::VKEYS_NAME), &env.meta.genesis_vkeys).c(d!())
+        })
+        .and_then(|_| bundle::pack(&tmp, path).c(d!()));
+    omit!(fs::remove_dir_all(&tmp));
+
+    res.map(|_| println!("ENV '{en}' is exported to: {path}"))
+}
+
+// Replace the host addresses and the ssh settings with placeholders,
+// in the order of the host IDs
+fn redact_hosts(env: &mut SysEnv<CustomInfo, Ports, CmdGenerator>) {
+    let ids = env
+        .meta
+        .hosts
+        .as_ref()
+        .keys()
+        .cloned()
+        .chain(
+            env.meta
+                .fuhrers
+                .values()
+                .chain(env.meta.nodes.values())
+                .map(|n| n.host.host_id()),
+        )
+        .collect::<BTreeSet<_>>();
+    let addrs = ids
+        .into_iter()
+        .enumerate()
+        .map(|(idx, id)| (id, bundle::redacted_addr(idx)))
+        .collect::<BTreeMap<_, _>>();
+
+    let redact = |h: &mut HostMeta| {
+        h.addr = addrs[&h.host_id()].clone();
+        h.ssh_user = String::new();
+        h.ssh_port = 0;
+        h.ssh_sk_path = PathBuf::new();
+    };
+
+    let hosts = mem::take(env.meta.hosts.as_mut());
+    for mut h in hosts.into_values() {
+        redact(&mut h.meta);
+        env.meta.hosts.as_mut().insert(h.host_id(), h);
+    }
+    env.meta
+        .fuhrers
+        .values_mut()
+        .chain(env.meta.nodes.values_mut())
+        .for_each(|n| redact(&mut n.host));
+}
+
+// `import`, create a new ENV with the genesis and the shape of the bundle
+fn env_import(en: &EnvName, path: &str, local_hosts: &Hosts) -> Result<()> {
+    let tmp = format!("/tmp/exp_import_{}_{}", en, ts!());
+    let res = import_bundle(en, path, local_hosts, &tmp);
+    omit!(fs::remove_dir_all(&tmp));
+    res
+}
+
+fn import_bundle(
+    en: &EnvName,
+    path: &str,
+    local_hosts: &Hosts,
+    tmp: &str,
+) -> Result<()> {
+    let m = bundle::unpack(path, tmp).c(d!())?;
+    let host_map = bundle::map_hosts(&m, local_hosts).c(d!())?;
+
+    let p = format!("{tmp}/{}", bundle::CONFIG_NAME);
+    let mut cfg = fs::read(&p)
+        .c(d!(p))
+        .and_then(|c| serde_json::from_slice(&c).c(d!()))?;
+    schema::migrate(&mut cfg).c(d!())?;
+    let mut src: SysEnv<CustomInfo, Ports, CmdGenerator> =
+        serde_json::from_value(cfg).c(d!())?;
+    src.meta.genesis = fs::read(format!("{tmp}/{}", bundle::GENESIS_NAME)).c(d!())?;
+    src.meta.genesis_vkeys =
+        fs::read(format!("{tmp}/{}", bundle::VKEYS_NAME)).c(d!())?;
+
+    // Place the nodes on the mapped local hosts
+    *src.meta.hosts.as_mut() = host_map
+        .values()
+        .map(|h| (h.host_id(), h.clone()))
+        .collect();
+    for n in src
+        .meta
+        .fuhrers
+        .values_mut()
+        .chain(src.meta.nodes.values_mut())
+    {
+        n.host = host_map
+            .get(&n.host.host_id())
+            .c(d!("The host of node {} is not in the bundle", n.id))?
+            .meta
+            .clone();
+    }
+
+    let pairs = env_clone_shape(&src, en, true).c(d!())?;
+    for (s, d) in pairs.iter() {
+        println!("node {s} => node {d}");
+    }
+
+    let dst = load_sysenv(en).c(d!())?;
+    let binaries = binary_checksums(&dst).c(d!())?;
+    for diff in bundle::diff_binaries(&m.binaries, &binaries) {
+        eprintln!(
+            "\x1b[33;01mWARNING\x1b[00m: the binaries differ from the bundle, {diff}"
+        );
+    }
+
+    Ok(())
+}
+
+// <binary name, the sha256 checksums found on the nodes>
+fn binary_checksums(
+    env: &SysEnv<CustomInfo, Ports, CmdGenerator>,
+) -> Result<BTreeMap<String, BTreeSet<String>>> {
+    let nodes = env
+        .meta
+        .fuhrers
+        .values()
+        .chain(env.meta.nodes.values())
+        .collect::<Vec<_>>();
+
+    let outputs = thread::scope(|s| {
+        nodes
+            .iter()
+            .map(|n| {
+                s.spawn(|| {
+                    Remote::from(&n.host)
+                        .exec_cmd(&bundle::cmd_checksums(&n.home))
+                        .c(d!())
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|hdr| {
+                hdr.join()
+                    .map_err(|_| eg!("The checksum task panicked"))
+                    .and_then(|r| r.c(d!()))
+            })
+            .collect::<Result<Vec<_>>>()
+    })?;
+
+    let mut ret = BTreeMap::<String, BTreeSet<String>>::new();
+    for (name, sum) in outputs.iter().flat_map(|o| bundle::parse_checksums(o)) {
+        ret.entry(name).or_default().insert(sum);
     }
+    Ok(ret)
 }
 
 fn load_sysenv(en: &EnvName) -> Result<SysEnv<CustomInfo, Ports, CmdGenerator>> {
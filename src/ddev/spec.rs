@@ -0,0 +1,353 @@
+//!
+//! The declarative description of an ENV,
+//! used by `exp ddev apply`.
+//!
+
+use chaindev::{beacon_ddev::NodeKind, common::hosts::HostID, NodeID};
+use ruc::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt, fs,
+};
+
+use crate::common::Eth1Kind;
+
+/// The desired shape of an ENV,
+/// the fuhrer nodes are not covered by the spec.
+///
+/// ```yaml
+/// hosts: "10.0.0.2#bob,10.0.0.3#bob"
+/// binaries:
+///   el_geth_bin: /usr/local/bin/geth
+/// nodes:
+///   geth: 4
+///   reth: 2
+///   fullnode: 1
+/// deposits_per_node: 2
+/// ```
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EnvSpec {
+    /// A json cfg path or a host expression,
+    /// hosts that do not exist in the ENV will be added
+    #[serde(default)]
+    pub hosts: Option<String>,
+
+    #[serde(default)]
+    pub binaries: BinSpec,
+
+    #[serde(default)]
+    pub nodes: NodesSpec,
+
+    /// The minimal number of validators on each non-fuhrer node
+    #[serde(default)]
+    pub deposits_per_node: u8,
+}
+
+impl EnvSpec {
+    pub fn from_file(path: &str) -> Result<Self> {
+        fs::read_to_string(path)
+            .c(d!(path))
+            .and_then(|s| serde_yml::from_str(&s).c(d!(path)))
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BinSpec {
+    pub el_geth_bin: Option<String>,
+    pub el_reth_bin: Option<String>,
+    pub cl_bin: Option<String>,
+}
+
+/// Node counts of each kind, exclude the fuhrer nodes
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NodesSpec {
+    /// Archive nodes with the geth el
+    #[serde(default)]
+    pub geth: u32,
+
+    /// Archive nodes with the reth el
+    #[serde(default)]
+    pub reth: u32,
+
+    /// Full nodes, always use the geth el
+    #[serde(default)]
+    pub fullnode: u32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NodeGroup {
+    Geth,
+    Reth,
+    FullNode,
+}
+
+impl NodeGroup {
+    pub fn new(kind: &NodeKind, el_kind: Eth1Kind) -> Self {
+        match (kind, el_kind) {
+            (NodeKind::FullNode, _) => Self::FullNode,
+            (_, Eth1Kind::Geth) => Self::Geth,
+            (_, Eth1Kind::Reth) => Self::Reth,
+        }
+    }
+
+    fn wanted(&self, spec: &NodesSpec) -> usize {
+        match self {
+            Self::Geth => spec.geth as usize,
+            Self::Reth => spec.reth as usize,
+            Self::FullNode => spec.fullnode as usize,
+        }
+    }
+}
+
+impl fmt::Display for NodeGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Geth => "archive node(geth)",
+            Self::Reth => "archive node(reth)",
+            Self::FullNode => "full node(geth)",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// What the planner needs to know about an existing non-fuhrer node
+#[derive(Clone, Debug)]
+pub struct NodeState {
+    pub id: NodeID,
+    pub group: NodeGroup,
+    pub validators: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Plan {
+    pub hosts_to_push: BTreeSet<HostID>,
+    pub binaries: Vec<(&'static str, String)>,
+    pub switch_to_geth: BTreeSet<NodeID>,
+    pub switch_to_reth: BTreeSet<NodeID>,
+    pub kick: BTreeSet<NodeID>,
+    pub push: BTreeMap<NodeGroup, usize>,
+
+    /// Validator number => target nodes
+    pub deposits: BTreeMap<u8, BTreeSet<NodeID>>,
+
+    /// Deposits on the nodes that will be pushed by this plan
+    pub deposits_on_new_nodes: u8,
+}
+
+impl Plan {
+    /// `existing_hosts` and `spec_hosts` are IDs of the hosts,
+    /// `binaries` are triples of <field name, current value, desired value>.
+    pub fn new(
+        spec: &EnvSpec,
+        nodes: &[NodeState],
+        existing_hosts: &BTreeSet<HostID>,
+        spec_hosts: &BTreeSet<HostID>,
+        binaries: &[(&'static str, &str, Option<&str>)],
+    ) -> Self {
+        let mut plan = Plan {
+            hosts_to_push: spec_hosts.difference(existing_hosts).cloned().collect(),
+            binaries: binaries
+                .iter()
+                .filter_map(|(field, cur, want)| {
+                    want.filter(|w| w != cur).map(|w| (*field, w.to_owned()))
+                })
+                .collect(),
+            ..Default::default()
+        };
+
+        let mut groups: BTreeMap<NodeGroup, Vec<&NodeState>> = BTreeMap::new();
+        for n in nodes.iter() {
+            groups.entry(n.group).or_default().push(n);
+        }
+        for ns in groups.values_mut() {
+            ns.sort_by_key(|n| n.id);
+        }
+
+        let surplus = |g: NodeGroup, groups: &BTreeMap<NodeGroup, Vec<&NodeState>>| {
+            groups.get(&g).map(|ns| ns.len()).unwrap_or(0) as i64
+                - g.wanted(&spec.nodes) as i64
+        };
+
+        // Archive nodes can be converted between geth and reth in place,
+        // which is much cheaper than a kick followed by a push;
+        // the nodes with the biggest IDs are selected first.
+        let geth_surplus = surplus(NodeGroup::Geth, &groups);
+        let reth_surplus = surplus(NodeGroup::Reth, &groups);
+        if 0 < geth_surplus && 0 > reth_surplus {
+            let n = geth_surplus.min(-reth_surplus) as usize;
+            let ns = groups.get_mut(&NodeGroup::Geth).unwrap();
+            for n in ns.split_off(ns.len() - n) {
+                plan.switch_to_reth.insert(n.id);
+                groups.entry(NodeGroup::Reth).or_default().push(n);
+            }
+        } else if 0 < reth_surplus && 0 > geth_surplus {
+            let n = reth_surplus.min(-geth_surplus) as usize;
+            let ns = groups.get_mut(&NodeGroup::Reth).unwrap();
+            for n in ns.split_off(ns.len() - n) {
+                plan.switch_to_geth.insert(n.id);
+                groups.entry(NodeGroup::Geth).or_default().push(n);
+            }
+        }
+
+        for g in [NodeGroup::Geth, NodeGroup::Reth, NodeGroup::FullNode] {
+            let s = surplus(g, &groups);
+            if 0 < s {
+                let ns = groups.get_mut(&g).unwrap();
+                ns.sort_by_key(|n| n.id);
+                for n in ns.split_off(ns.len() - s as usize) {
+                    plan.kick.insert(n.id);
+                }
+            } else if 0 > s {
+                plan.push.insert(g, (-s) as usize);
+            }
+        }
+
+        let want = spec.deposits_per_node as usize;
+        for n in groups.values().flatten() {
+            if n.validators < want {
+                plan.deposits
+                    .entry((want - n.validators) as u8)
+                    .or_default()
+                    .insert(n.id);
+            }
+        }
+        if !plan.push.is_empty() {
+            plan.deposits_on_new_nodes = spec.deposits_per_node;
+        }
+
+        plan
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hosts_to_push.is_empty()
+            && self.binaries.is_empty()
+            && self.switch_to_geth.is_empty()
+            && self.switch_to_reth.is_empty()
+            && self.kick.is_empty()
+            && self.push.is_empty()
+            && self.deposits.is_empty()
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "Nothing to do, the ENV matches the spec.");
+        }
+
+        let ids = |ids: &BTreeSet<NodeID>| {
+            ids.iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+
+        writeln!(f, "\x1b[33;1mPlan:\x1b[0m")?;
+        for h in self.hosts_to_push.iter() {
+            writeln!(f, "  + push host: {h}")?;
+        }
+        for (field, v) in self.binaries.iter() {
+            writeln!(f, "  ~ set {field}: {v}")?;
+        }
+        if !self.switch_to_geth.is_empty() {
+            writeln!(f, "  ~ switch EL to geth: {}", ids(&self.switch_to_geth))?;
+        }
+        if !self.switch_to_reth.is_empty() {
+            writeln!(f, "  ~ switch EL to reth: {}", ids(&self.switch_to_reth))?;
+        }
+        if !self.kick.is_empty() {
+            writeln!(f, "  - kick nodes: {}", ids(&self.kick))?;
+        }
+        for (g, n) in self.push.iter() {
+            writeln!(f, "  + push {n} {g}[s]")?;
+        }
+        for (n, nodes) in self.deposits.iter() {
+            writeln!(f, "  + deposit {n} validator[s] on nodes: {}", ids(nodes))?;
+        }
+        if 0 < self.deposits_on_new_nodes {
+            writeln!(
+                f,
+                "  + deposit {} validator[s] on each new node",
+                self.deposits_on_new_nodes
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn state(id: NodeID, group: NodeGroup, validators: usize) -> NodeState {
+        NodeState {
+            id,
+            group,
+            validators,
+        }
+    }
+
+    #[test]
+    fn plan_prefers_switching() {
+        let spec = EnvSpec {
+            nodes: NodesSpec {
+                geth: 1,
+                reth: 2,
+                fullnode: 0,
+            },
+            deposits_per_node: 1,
+            ..Default::default()
+        };
+        let nodes = [
+            state(1, NodeGroup::Geth, 1),
+            state(2, NodeGroup::Geth, 0),
+            state(3, NodeGroup::Geth, 1),
+            state(4, NodeGroup::FullNode, 1),
+        ];
+        let hosts = set! {B};
+        let plan = Plan::new(&spec, &nodes, &hosts, &hosts, &[]);
+
+        assert_eq!(plan.switch_to_reth, set! {B 2, 3});
+        assert!(plan.switch_to_geth.is_empty());
+        assert_eq!(plan.kick, set! {B 4});
+        assert!(plan.push.is_empty());
+        assert_eq!(plan.deposits, map! {B 1 => set!{B 2}});
+    }
+
+    #[test]
+    fn plan_push_and_hosts() {
+        let spec = EnvSpec {
+            nodes: NodesSpec {
+                geth: 2,
+                reth: 0,
+                fullnode: 1,
+            },
+            deposits_per_node: 2,
+            ..Default::default()
+        };
+        let nodes = [state(1, NodeGroup::Geth, 2)];
+        let existing = set! {B "10.0.0.2".to_owned()};
+        let wanted = set! {B "10.0.0.2".to_owned(), "10.0.0.3".to_owned()};
+        let plan = Plan::new(
+            &spec,
+            &nodes,
+            &existing,
+            &wanted,
+            &[("cl_bin", "lighthouse", Some("lighthouse"))],
+        );
+
+        assert_eq!(plan.hosts_to_push, set! {B "10.0.0.3".to_owned()});
+        assert!(plan.binaries.is_empty());
+        assert_eq!(
+            plan.push,
+            map! {B NodeGroup::Geth => 1, NodeGroup::FullNode => 1}
+        );
+        assert!(plan.deposits.is_empty());
+        assert_eq!(2, plan.deposits_on_new_nodes);
+    }
+}
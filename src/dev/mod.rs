@@ -7,17 +7,33 @@
 //!
 
 use crate::{
-    cfg::{DevCfg, DevOp},
+    audit::{self, HistoryFilter},
+    bootnode::{self, Bootnode},
+    cfg::{ChaosOp, DevCfg, DevOp, HookOp, LabelOp, SnapshotOp},
+    chaos::{self, MaxDown, Monkey, Netem, P2pNode, Partition},
     common::*,
     def_select_nodes,
+    external::ExternalNetwork,
+    hooks::OpEvent,
+    join_kit::JoinKit,
+    lock::{lock_path, EnvLock},
+    output::*,
+    p2p::{self, NodeIdentity},
     pos::{create_mnemonic_words, deposit::do_deposit, exit::exit_by_mnemonic},
+    process::{self, ProcessBackend, SystemdLimits},
+    schema::{self, SCHEMA_VERSION},
     select_nodes_by_el_kind,
+    selector::{NodeFacts, Selector},
+    snapshot,
+    supervise::{Backoff, RestartEvent},
+    topology::{self, Topology},
 };
 use alloy::{
     primitives::{hex, Address},
     signers::k256::ecdsa::SigningKey,
 };
 use chaindev::{
+    beacon_based::common::NodePorts,
     beacon_dev::{
         Env as SysEnv, EnvCfg as SysCfg, EnvMeta, EnvOpts as SysOpts, Node, NodeKind,
         Op, NODE_HOME_GENESIS_DIR_DST, NODE_HOME_GENESIS_DST, NODE_HOME_VCDATA_DST,
@@ -27,21 +43,28 @@ use chaindev::{
 };
 use ruc::*;
 use serde::{Deserialize, Serialize};
-use serde_json::Value as JsonValue;
 use std::{
-    collections::{BTreeSet, HashSet},
-    fs, mem,
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fs,
+    io::Write,
 };
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct EnvCfg {
     sys_cfg: SysCfg<CustomInfo, Ports, ExtraOp>,
+    dry_run: bool,
+    wait: bool,
 }
 
 def_select_nodes!();
 
-impl From<DevCfg> for EnvCfg {
-    fn from(cfg: DevCfg) -> Self {
+impl TryFrom<DevCfg> for EnvCfg {
+    type Error = CfgError;
+
+    fn try_from(cfg: DevCfg) -> std::result::Result<Self, CfgError> {
+        let dry_run = cfg.dry_run;
+        let wait = cfg.wait;
+        let output = cfg.output;
         let mut en = cfg
             .env_name
             .as_deref()
@@ -50,25 +73,43 @@ impl From<DevCfg> for EnvCfg {
         let op = match cfg.op.unwrap_or_default() {
             DevOp::Create(copts) => {
                 let (genesis_tgz_path, genesis_vkeys_tgz_path) =
-                    if let Some(s) = copts.genesis_data_pre_created {
-                        let paths = s.split('+').collect::<Vec<_>>();
-                        if 2 != paths.len() {
-                            pnk!(Err(eg!("Invalid value")));
-                        }
-                        for p in paths.iter() {
-                            if fs::metadata(p).is_err() {
-                                pnk!(Err(eg!("File not accessible")));
-                            }
-                        }
-                        (Some(paths[0].to_owned()), Some(paths[1].to_owned()))
-                    } else {
-                        (None, None)
-                    };
+                    parse_genesis_data_arg(copts.genesis_data_pre_created)?;
+
+                let external = copts
+                    .external_network
+                    .as_deref()
+                    .map(|dir| {
+                        ExternalNetwork::new(
+                            dir,
+                            copts.external_bootnodes.as_deref(),
+                            copts.external_enrs.as_deref(),
+                            copts.checkpoint_sync_url.clone(),
+                        )
+                    })
+                    .transpose()
+                    .map_err(|e| CfgError::InvalidExternalNetwork(e.get_lowest_msg()))?;
 
                 let custom_data = CustomInfo {
                     el_geth_bin: copts.el_geth_bin.unwrap_or("geth".to_owned()),
                     el_reth_bin: copts.el_reth_bin.unwrap_or("reth".to_owned()),
                     cl_bin: copts.cl_bin.unwrap_or_else(|| "lighthouse".to_owned()),
+                    process_backend: if copts.systemd {
+                        ProcessBackend::Systemd(SystemdLimits {
+                            restart_sec: copts.systemd_restart_sec.unwrap_or(5),
+                            memory_max: copts.systemd_memory_max,
+                            cpu_quota: copts.systemd_cpu_quota,
+                        })
+                    } else {
+                        ProcessBackend::Nohup
+                    },
+                    stop_timeout_secs: copts
+                        .stop_timeout_secs
+                        .unwrap_or(process::DEFAULT_STOP_TIMEOUT_SECS),
+                    hooks: Default::default(),
+                    schema_version: SCHEMA_VERSION,
+                    external,
+                    identity_seed: p2p::new_seed(),
+                    netem: Default::default(),
                 };
 
                 if let Some(n) = copts.env_name {
@@ -106,6 +147,7 @@ impl From<DevCfg> for EnvCfg {
                     wallet_seckey_path,
                     withdraw_0x01_addr,
                     async_wait,
+                    dry_run,
                 })
             }
             DevOp::ValidatorExit {
@@ -191,17 +233,23 @@ impl From<DevCfg> for EnvCfg {
                 env_name,
                 reth,
                 fullnode,
+                bootnode,
+                el_bootnode_bin,
                 num,
             } => {
                 if let Some(n) = env_name {
                     en = n.into();
                 }
+                let mut custom_data = alt!(
+                    reth,
+                    NodeCustomData::new_with_reth(),
+                    NodeCustomData::new_with_geth()
+                );
+                if bootnode {
+                    custom_data.bootnode = Some(Bootnode::new(el_bootnode_bin));
+                }
                 Op::PushNodes {
-                    custom_data: alt!(
-                        reth,
-                        NodeCustomData::new_with_reth().to_json_value(),
-                        NodeCustomData::new_with_geth().to_json_value()
-                    ),
+                    custom_data: custom_data.to_json_value(),
                     fullnode,
                     num,
                 }
@@ -239,15 +287,15 @@ impl From<DevCfg> for EnvCfg {
                 if let Some(n) = env_name {
                     en = n.into();
                 }
-                let nodes = pnk!(parse_nodes(&nodes));
-                Op::Custom(ExtraOp::SwitchELToGeth { nodes })
+                let nodes = parse_nodes_arg(&nodes)?;
+                Op::Custom(ExtraOp::SwitchELToGeth { nodes, dry_run })
             }
             DevOp::SwitchELToReth { env_name, nodes } => {
                 if let Some(n) = env_name {
                     en = n.into();
                 }
-                let nodes = pnk!(parse_nodes(&nodes));
-                Op::Custom(ExtraOp::SwitchELToReth { nodes })
+                let nodes = parse_nodes_arg(&nodes)?;
+                Op::Custom(ExtraOp::SwitchELToReth { nodes, dry_run })
             }
             DevOp::Show {
                 env_name,
@@ -260,6 +308,7 @@ impl From<DevCfg> for EnvCfg {
                 Op::Custom(ExtraOp::Show {
                     clean_up,
                     write_back,
+                    output,
                 })
             }
             DevOp::ListRpcs {
@@ -283,15 +332,213 @@ impl From<DevCfg> for EnvCfg {
                     cl_bn_metric,
                     cl_vc,
                     cl_vc_metric,
+                    output,
                 })
             }
             DevOp::DebugFailedNodes { env_name } => {
                 if let Some(n) = env_name {
                     en = n.into();
                 }
-                Op::DebugFailedNodes
+                if let Some(output) = output {
+                    Op::Custom(ExtraOp::DebugFailedNodes { output })
+                } else {
+                    Op::DebugFailedNodes
+                }
+            }
+            DevOp::Health { env_name } => {
+                if let Some(n) = env_name {
+                    en = n.into();
+                }
+                Op::Custom(ExtraOp::Health { output })
+            }
+            DevOp::Topology { env_name, dot } => {
+                if let Some(n) = env_name {
+                    en = n.into();
+                }
+                Op::Custom(ExtraOp::Topology { dot, output })
+            }
+            DevOp::Supervise {
+                env_name,
+                interval_secs,
+                max_backoff_secs,
+                webhook,
+            } => {
+                if let Some(n) = env_name {
+                    en = n.into();
+                }
+                Op::Custom(ExtraOp::Supervise {
+                    interval_secs,
+                    max_backoff_secs,
+                    webhook,
+                })
+            }
+            DevOp::List => {
+                if let Some(output) = output {
+                    Op::Custom(ExtraOp::List { output })
+                } else {
+                    Op::List
+                }
+            }
+            DevOp::Label { op } => {
+                let (env_name, nodes, items, remove) = match op {
+                    LabelOp::Add {
+                        env_name,
+                        nodes,
+                        items,
+                    } => (env_name, nodes, items, false),
+                    LabelOp::Remove {
+                        env_name,
+                        nodes,
+                        items,
+                    } => (env_name, nodes, items, true),
+                };
+                if let Some(n) = env_name {
+                    en = n.into();
+                }
+                let (labels, notes) = parse_label_items(&items, remove)?;
+                Op::Custom(ExtraOp::Label {
+                    nodes,
+                    remove,
+                    labels,
+                    notes,
+                })
+            }
+            DevOp::History {
+                env_name,
+                operator,
+                operation,
+                node,
+                failed,
+                since,
+                last,
+            } => {
+                if let Some(n) = env_name {
+                    en = n.into();
+                }
+                Op::Custom(ExtraOp::History {
+                    filter: HistoryFilter {
+                        operator,
+                        operation,
+                        node,
+                        failed_only: failed,
+                        since,
+                        last,
+                    },
+                    output: output.unwrap_or(OutputFormat::Table),
+                })
+            }
+            DevOp::Hook { op } => {
+                let (env_name, webhooks, scripts, remove) = match op {
+                    HookOp::Add {
+                        env_name,
+                        webhook,
+                        script,
+                    } => (env_name, webhook, script, false),
+                    HookOp::Remove {
+                        env_name,
+                        webhook,
+                        script,
+                    } => (env_name, webhook, script, true),
+                    HookOp::List { env_name } => (env_name, vec![], vec![], false),
+                };
+                if let Some(n) = env_name {
+                    en = n.into();
+                }
+                Op::Custom(ExtraOp::Hook {
+                    webhooks: webhooks.into_iter().collect(),
+                    scripts: scripts.into_iter().collect(),
+                    remove,
+                })
+            }
+            DevOp::Snapshot { op } => {
+                let (env_name, op) = match op {
+                    SnapshotOp::Create { env_name, name } => {
+                        (env_name, ExtraOp::SnapshotCreate { name })
+                    }
+                    SnapshotOp::Restore { env_name, name } => {
+                        (env_name, ExtraOp::SnapshotRestore { name })
+                    }
+                    SnapshotOp::List { env_name } => (env_name, ExtraOp::SnapshotList),
+                    SnapshotOp::Delete { env_name, name } => {
+                        (env_name, ExtraOp::SnapshotDelete { name })
+                    }
+                };
+                if let Some(n) = env_name {
+                    en = n.into();
+                }
+                Op::Custom(op)
+            }
+            DevOp::Chaos { op } => {
+                let (env_name, op) = match op {
+                    ChaosOp::Partition {
+                        env_name,
+                        groups,
+                        duration,
+                    } => (
+                        env_name,
+                        ExtraOp::ChaosPartition {
+                            groups: groups.join(" "),
+                            duration_secs: duration,
+                        },
+                    ),
+                    ChaosOp::Netem {
+                        env_name,
+                        nodes,
+                        delay,
+                        jitter,
+                        loss,
+                    } => (
+                        env_name,
+                        ExtraOp::ChaosNetem {
+                            nodes,
+                            netem: Netem {
+                                delay,
+                                jitter,
+                                loss,
+                            },
+                        },
+                    ),
+                    ChaosOp::Monkey {
+                        env_name,
+                        nodes,
+                        rate,
+                        max_down,
+                        duration,
+                        seed,
+                    } => (
+                        env_name,
+                        ExtraOp::ChaosMonkey {
+                            nodes,
+                            interval_secs: rate,
+                            max_down,
+                            duration_secs: duration,
+                            seed,
+                        },
+                    ),
+                    ChaosOp::Clear { env_name } => (env_name, ExtraOp::ChaosClear),
+                };
+                if let Some(n) = env_name {
+                    en = n.into();
+                }
+                Op::Custom(op)
+            }
+            DevOp::Clone {
+                src,
+                dst,
+                with_data,
+            } => {
+                en = dst.into();
+                Op::Custom(ExtraOp::Clone {
+                    src: src.into(),
+                    with_data,
+                })
+            }
+            DevOp::ExportJoinKit { env_name, dir } => {
+                if let Some(n) = env_name {
+                    en = n.into();
+                }
+                Op::Custom(ExtraOp::ExportJoinKit { dir })
             }
-            DevOp::List => Op::List,
             DevOp::Git {
                 env_name,
                 remote_url,
@@ -304,33 +551,296 @@ impl From<DevCfg> for EnvCfg {
             }
         };
 
-        Self {
+        Ok(Self {
             sys_cfg: SysCfg { name: en, op },
-        }
+            dry_run,
+            wait,
+        })
     }
 }
 
 impl EnvCfg {
     pub fn exec(&self) -> Result<()> {
-        self.sys_cfg.exec(CmdGenerator).c(d!())
-            .and_then(|_| match &self.sys_cfg.op {
-                Op::Create { opts: _ } => {
-                    let mut env = load_sysenv(&self.sys_cfg.name).c(d!())?;
-                    let fuhrer = env.meta.fuhrers.values_mut().next().unwrap();
-                    let map = map!{B
-                        env.meta.genesis_mnemonic_words.clone() => (0..env.meta.genesis_validator_num).collect()
+        if self.dry_run {
+            return self.exec_dry_run().c(d!());
+        }
+
+        schema::ensure("dev", &self.sys_cfg.name, self.wait).c(d!())?;
+
+        let Some(operation) = self.operation() else {
+            return self.exec_op();
+        };
+
+        let en = &self.sys_cfg.name;
+
+        // Held until the audit log and the hooks are done
+        let _lock =
+            EnvLock::acquire(&lock_path("dev", en), operation, self.wait).c(d!())?;
+
+        let pre = load_sysenv(en).ok();
+        let res = self.exec_op();
+        let post = load_sysenv(en).ok();
+
+        // The ENV does not exist before `create` or after `destroy`
+        let Some(env) = post.as_ref().or(pre.as_ref()) else {
+            return res;
+        };
+
+        let nodes = self.affected_nodes(pre.as_ref(), post.as_ref());
+        let ev = OpEvent::new(en, operation, nodes, &res);
+
+        if let Err(e) = audit::append(&env.meta.home, &ev) {
+            eprintln!("Audit log: {}", e.get_lowest_msg());
+        }
+
+        let hooks = &env.meta.custom_data.hooks;
+        if !hooks.is_empty() {
+            hooks.fire(&ev);
+        }
+
+        res
+    }
+
+    fn exec_op(&self) -> Result<()> {
+        let res = match &self.sys_cfg.op {
+            Op::Create { opts } if opts.custom_data.external.is_some() => {
+                self.exec_create_external(opts).c(d!())
+            }
+            _ => self.sys_cfg.exec(CmdGenerator).c(d!()),
+        };
+
+        res.and_then(|_| match &self.sys_cfg.op {
+            Op::Create { opts: _ } => {
+                record_genesis_deposits(&self.sys_cfg.name).c(d!())
+            }
+            Op::PushNodes { .. } => record_bootnodes(&self.sys_cfg.name).c(d!()),
+            _ => Ok(()),
+        })
+    }
+
+    // The network config of the external network is packed as the genesis data
+    fn exec_create_external(&self, opts: &SysOpts<CustomInfo>) -> Result<()> {
+        let ext = opts.custom_data.external.as_ref().c(d!())?;
+        let tmp = format!("/tmp/exp_external_{}_{}", self.sys_cfg.name, ts!());
+
+        let res = ext.pack_genesis(&tmp).c(d!()).and_then(|(genesis, vkeys)| {
+            let mut opts = opts.clone();
+            opts.genesis_tgz_path = Some(genesis);
+            opts.genesis_vkeys_tgz_path = Some(vkeys);
+            SysCfg {
+                name: self.sys_cfg.name.clone(),
+                op: Op::<CustomInfo, Ports, ExtraOp>::Create { opts },
+            }
+            .exec(CmdGenerator)
+            .c(d!())
+        });
+        omit!(fs::remove_dir_all(&tmp));
+
+        res
+    }
+
+    // The name of a mutating operation, `None` for the read-only ones
+    fn operation(&self) -> Option<&'static str> {
+        let name = match &self.sys_cfg.op {
+            Op::Create { .. } => "create",
+            Op::Destroy { .. } => "destroy",
+            Op::PushNodes { .. } => "push_nodes",
+            Op::KickNodes { .. } => "kick_nodes",
+            Op::Protect => "protect",
+            Op::Unprotect => "unprotect",
+            Op::Start { .. } => "start",
+            Op::Stop { .. } => "stop",
+            Op::Restart { .. } => "restart",
+            Op::Custom(op) => match op {
+                ExtraOp::Deposit { .. } => "deposit",
+                ExtraOp::ValidatorExit { .. } => "validator_exit",
+                ExtraOp::SwitchELToGeth { .. } => "switch_el_to_geth",
+                ExtraOp::SwitchELToReth { .. } => "switch_el_to_reth",
+                ExtraOp::Label { .. } => "label",
+                // Only listing them
+                ExtraOp::Hook {
+                    webhooks, scripts, ..
+                } if webhooks.is_empty() && scripts.is_empty() => return None,
+                ExtraOp::Hook { .. } => "hook",
+                ExtraOp::SnapshotCreate { .. } => "snapshot_create",
+                ExtraOp::SnapshotRestore { .. } => "snapshot_restore",
+                ExtraOp::SnapshotDelete { .. } => "snapshot_delete",
+                ExtraOp::ChaosPartition { .. } => "chaos_partition",
+                ExtraOp::ChaosNetem { .. } => "chaos_netem",
+                ExtraOp::ChaosClear => "chaos_clear",
+                ExtraOp::Clone { .. } => "clone",
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(name)
+    }
+
+    // The nodes that are affected by the operation, best effort
+    fn affected_nodes(
+        &self,
+        pre: Option<&SysEnv<CustomInfo, Ports, CmdGenerator>>,
+        post: Option<&SysEnv<CustomInfo, Ports, CmdGenerator>>,
+    ) -> Option<BTreeSet<NodeID>> {
+        let ids = |env: Option<&SysEnv<CustomInfo, Ports, CmdGenerator>>| {
+            env.map(|env| {
+                env.meta
+                    .nodes
+                    .keys()
+                    .chain(env.meta.fuhrers.keys())
+                    .copied()
+                    .collect::<BTreeSet<_>>()
+            })
+        };
+        match &self.sys_cfg.op {
+            Op::Start { nodes, .. }
+            | Op::Stop { nodes, .. }
+            | Op::Restart { nodes, .. }
+            | Op::KickNodes { nodes, .. }
+                if nodes.is_some() =>
+            {
+                nodes.clone()
+            }
+            Op::PushNodes { .. } | Op::KickNodes { .. } => {
+                let (pre, post) = (ids(pre)?, ids(post)?);
+                Some(pre.symmetric_difference(&post).copied().collect())
+            }
+            Op::Custom(
+                ExtraOp::SwitchELToGeth { nodes, .. }
+                | ExtraOp::SwitchELToReth { nodes, .. },
+            ) => Some(nodes.clone()),
+            Op::Custom(
+                ExtraOp::Deposit { nodes, .. }
+                | ExtraOp::ValidatorExit { nodes, .. }
+                | ExtraOp::Label { nodes, .. }
+                | ExtraOp::ChaosNetem { nodes, .. },
+            ) => {
+                let sel = nodes.trim().parse::<Selector>().ok()?;
+                env_select_nodes(pre?, &sel, true).ok()
+            }
+            Op::Custom(ExtraOp::ChaosPartition { groups, .. }) => {
+                env_partition(pre?, groups).ok().map(|p| p.nodes())
+            }
+            _ => None,
+        }
+    }
+
+    // Print what the operation would do,
+    // nothing will be executed and the ENV meta will not be changed
+    fn exec_dry_run(&self) -> Result<()> {
+        let en = &self.sys_cfg.name;
+        match &self.sys_cfg.op {
+            Op::Start { nodes, .. } => {
+                let env = load_sysenv(en).c(d!())?;
+                for n in dry_run_nodes(&env, nodes.as_ref()).c(d!())? {
+                    dry_run_start(&env, &n);
+                }
+                Ok(())
+            }
+            Op::Restart {
+                nodes,
+                wait_itv_secs,
+                ..
+            } => {
+                let env = load_sysenv(en).c(d!())?;
+                for n in dry_run_nodes(&env, nodes.as_ref()).c(d!())? {
+                    let target = format!("node {}", n.id);
+                    dry_run_script(
+                        &target,
+                        &CmdGenerator.cmd_for_stop(&n, &env.meta, false),
+                    );
+                    dry_run_note(&target, &format!("sleep {wait_itv_secs} seconds"));
+                    dry_run_start(&env, &n);
+                }
+                Ok(())
+            }
+            Op::PushNodes {
+                custom_data,
+                fullnode,
+                num,
+            } => {
+                let env = load_sysenv(en).c(d!())?;
+
+                let next_id = serde_json::to_value(&env.meta).c(d!())?["next_node_id"]
+                    .as_u64()
+                    .c(d!())? as NodeID;
+                let ports = Ports::try_create(&Ports::reserved()).c(d!())?;
+
+                dry_run_note(
+                    "localhost",
+                    "the ports of each new node are allocated at runtime, placeholders are used below",
+                );
+
+                for id in next_id..(next_id + *num as NodeID) {
+                    let n = Node {
+                        id,
+                        home: format!("{}/{id}", env.meta.home),
+                        ports: ports.clone(),
+                        kind: alt!(*fullnode, NodeKind::FullNode, NodeKind::ArchiveNode),
+                        custom_data: Some(custom_data.clone()),
                     };
-                    json_deposits_append(&mut fuhrer.custom_data, map)
-                        .c(d!())
-                        .and_then(|_|
-                    env.write_cfg().c(d!())
-                            )
+                    let target = format!("node {}", n.id);
+                    dry_run_note(&target, &format!("create the node home {}", n.home));
+                    dry_run_transfer(
+                        &target,
+                        &format!(
+                            "<genesis data of the ENV, {} bytes>",
+                            env.meta.genesis.len()
+                        ),
+                        &format!("{}/{NODE_HOME_GENESIS_DST}", n.home),
+                    );
+                    dry_run_start(&env, &n);
                 }
-                _ => Ok(()),
+
+                Ok(())
+            }
+            Op::Custom(
+                op @ (ExtraOp::Deposit { .. }
+                | ExtraOp::SwitchELToGeth { .. }
+                | ExtraOp::SwitchELToReth { .. }),
+            ) => op.exec(en).c(d!()),
+            _ => Err(eg!(
+                "The `--dry-run` mode is not supported by this operation"
+            )),
+        }
+    }
+}
+
+fn dry_run_nodes(
+    env: &SysEnv<CustomInfo, Ports, CmdGenerator>,
+    ids: Option<&BTreeSet<NodeID>>,
+) -> Result<Vec<Node<Ports>>> {
+    if let Some(ids) = ids {
+        ids.iter()
+            .map(|id| {
+                env.meta
+                    .nodes
+                    .get(id)
+                    .or_else(|| env.meta.fuhrers.get(id))
+                    .cloned()
+                    .c(d!("The node(id: {id}) does not exist"))
             })
+            .collect()
+    } else {
+        Ok(env
+            .meta
+            .fuhrers
+            .values()
+            .chain(env.meta.nodes.values())
+            .cloned()
+            .collect())
     }
 }
 
+fn dry_run_start(env: &SysEnv<CustomInfo, Ports, CmdGenerator>, n: &Node<Ports>) {
+    dry_run_start_node(
+        &format!("node {}", n.id),
+        ScriptRunner::Local,
+        &CmdGenerator.cmd_cnt_running(n, &env.meta),
+        &CmdGenerator.cmd_for_start(n, &env.meta),
+    );
+}
+
 #[derive(Clone, Default, Debug, Serialize, Deserialize)]
 struct CmdGenerator;
 
@@ -340,11 +850,17 @@ impl NodeCmdGenerator<Node<Ports>, EnvMeta<CustomInfo, Node<Ports>>> for CmdGene
         n: &Node<Ports>,
         e: &EnvMeta<CustomInfo, Node<Ports>>,
     ) -> String {
+        if pnk!(json_bootnode(&n.custom_data)).is_some() {
+            return bootnode::cmd_cnt_running(&n.home, &e.custom_data.process_backend);
+        }
+        if let ProcessBackend::Systemd(_) = e.custom_data.process_backend {
+            return process::cmd_cnt_running(&n.home);
+        }
+        // The binaries are copied into the node home before starting
         format!(
-            "ps ax -o pid,args | grep -E '({0}.*{3}/)|({1}.*{3}/)|({2}.*{3}/)' | grep -v 'grep' | wc -l",
-            e.custom_data.el_geth_bin, e.custom_data.el_reth_bin, e.custom_data.cl_bin, n.home
+            "ps ax -o pid=,args= | {} | wc -l",
+            process::cmd_grep_bins(&n.home, &["geth", "reth", "lighthouse"])
         )
-        .replace('+', r"\+")
     }
 
     fn cmd_for_start(
@@ -355,8 +871,12 @@ impl NodeCmdGenerator<Node<Ports>, EnvMeta<CustomInfo, Node<Ports>>> for CmdGene
         let home = &n.home;
         let genesis_dir = format!("{home}/genesis");
 
-        let rand_jwt = ruc::algo::rand::rand_jwt();
+        // `None` in the ENVs created by old versions,
+        // the clients generate their own keys in that case
+        let identity = NodeIdentity::derive(&e.custom_data.identity_seed, n.id);
+
         let auth_jwt = format!("{home}/auth.jwt");
+        let write_jwt = p2p::cmd_write_jwt(identity.as_ref(), &auth_jwt);
 
         let geth = if e.custom_data.el_geth_bin.contains("/") {
             e.custom_data.el_geth_bin.clone()
@@ -378,7 +898,7 @@ impl NodeCmdGenerator<Node<Ports>, EnvMeta<CustomInfo, Node<Ports>>> for CmdGene
 
         let prepare_cmd = format!(
             r#"
-echo "{rand_jwt}" > {auth_jwt} | tr -d '\n' || exit 1
+{write_jwt}
 
 cp -f {geth} {home}/geth_bin || exit 1
 cp -f {reth} {home}/reth_bin || exit 1
@@ -397,8 +917,39 @@ fi "#
         let local_ip = &e.host_ip;
         let ext_ip = local_ip; // for `ddev` it should be e.external_ip?
 
+        let launch = |component: &str, err_file: &str, cmd: &str| {
+            process::cmd_for_launch(
+                &e.custom_data.process_backend,
+                e.custom_data.stop_timeout_secs,
+                &n.home,
+                component,
+                err_file,
+                cmd,
+            )
+        };
+
+        if let Some(b) = pnk!(json_bootnode(&n.custom_data)) {
+            let cmd = b.cmd_for_start(
+                n.id,
+                home,
+                local_ip,
+                ext_ip,
+                n.ports.el_discovery,
+                n.ports.cl_discovery,
+                launch,
+            );
+            return format!("\n{prepare_cmd}\n{cmd}\n");
+        }
+
+        // Used instead of the online nodes if any
+        let dedicated = dedicated_bootnodes(e, n.id);
+
         let ts_start = ts!();
         let (el_bootnodes, cl_bn_bootnodes, cl_bn_trusted_peers, checkpoint_sync_url) = loop {
+            if let Some((enodes, enrs, url)) = dedicated.clone() {
+                break (enodes, enrs, String::new(), url);
+            }
+
             let online_nodes = e
                 .nodes_should_be_online
                 .iter()
@@ -470,6 +1021,13 @@ fi "#
             );
         };
 
+        let (el_bootnodes, cl_bn_bootnodes, checkpoint_sync_url) =
+            if let Some(ext) = e.custom_data.external.as_ref() {
+                ext.merge_peers(el_bootnodes, cl_bn_bootnodes, checkpoint_sync_url)
+            } else {
+                (el_bootnodes, cl_bn_bootnodes, checkpoint_sync_url)
+            };
+
         ////////////////////////////////////////////////
         // EL
         ////////////////////////////////////////////////
@@ -483,6 +1041,18 @@ fi "#
         let el_engine_port = n.ports.el_engine_api;
         let el_metric_port = n.ports.el_metric;
 
+        let el_key_path = format!("{home}/p2p/el.key");
+        let (cmd_write_keys, geth_key_arg, reth_key_arg) =
+            if let Some(i) = identity.as_ref() {
+                (
+                    i.cmd_write_keys(&el_key_path, &format!("{home}/{CL_BN_DIR}")),
+                    format!(" --nodekey={el_key_path}"),
+                    format!(" --p2p-secret-key={el_key_path}"),
+                )
+            } else {
+                Default::default()
+            };
+
         let el_cmd = if Eth1Kind::Geth == el_kind {
             let el_gc_mode = if matches!(n.kind, NodeKind::FullNode) {
                 "full"
@@ -501,7 +1071,7 @@ fi "#
 
             let cmd_run_part_0 = format!(
                 r#"
-nohup {home}/geth_bin \
+{home}/geth_bin{geth_key_arg} \
     --syncmode=full \
     --gcmode={el_gc_mode} \
     --networkid=$(grep -Po '(?<="chainId":)\s*\d+' {el_genesis} | tr -d ' ') \
@@ -533,9 +1103,8 @@ nohup {home}/geth_bin \
                 format!(" --bootnodes='{el_bootnodes}'")
             };
 
-            let cmd_run_part_2 = " >/dev/null 2>&1 &";
-
-            cmd_init_part + &cmd_run_part_0 + &cmd_run_part_1 + cmd_run_part_2
+            cmd_init_part
+                + &launch("el", EL_ERR_NAME, &(cmd_run_part_0 + &cmd_run_part_1))
         } else if Eth1Kind::Reth == el_kind {
             let cmd_init_part = format!(
                 r#"
@@ -549,7 +1118,7 @@ fi "#
 
             let cmd_run_part_0 = format!(
                 r#"
-nohup {home}/reth_bin node \
+{home}/reth_bin node{reth_key_arg} \
     --chain={el_genesis} \
     --datadir={el_dir} \
     --log.file.directory={el_dir}/logs \
@@ -572,6 +1141,9 @@ nohup {home}/reth_bin node \
 
             let cmd_run_part_1 = if el_bootnodes.is_empty() {
                 String::new()
+            } else if dedicated.is_some() {
+                // The dedicated bootnodes do not accept connections
+                format!(" --bootnodes='{el_bootnodes}'")
             } else {
                 format!(" --bootnodes='{el_bootnodes}' --trusted-peers='{el_bootnodes}'")
             };
@@ -584,9 +1156,8 @@ nohup {home}/reth_bin node \
             //     cmd_run_part_1.push_str(" --full");
             // }
 
-            let cmd_run_part_2 = " >/dev/null 2>&1 &";
-
-            cmd_init_part + &cmd_run_part_0 + &cmd_run_part_1 + cmd_run_part_2
+            cmd_init_part
+                + &launch("el", EL_ERR_NAME, &(cmd_run_part_0 + &cmd_run_part_1))
         } else {
             pnk!(Err(eg!("The fuhrering world is over!")))
         };
@@ -613,12 +1184,15 @@ nohup {home}/reth_bin node \
             };
 
         let cl_bn_cmd = {
-            let cmd_run_part_0 = format!(
+            let cmd_prepare_part = format!(
                 r#"
 mkdir -p {cl_bn_dir} || exit 1
-sleep 0.5
+sleep 0.5 "#
+            );
 
-nohup {home}/lighthouse_bin beacon_node \
+            let cmd_run_part_0 = format!(
+                r#"
+{home}/lighthouse_bin beacon_node \
     --testnet-dir={cl_genesis} \
     --datadir={cl_bn_dir} \
     --logfile={cl_bn_dir}/logs/{CL_BN_LOG_NAME} \
@@ -651,9 +1225,15 @@ nohup {home}/lighthouse_bin beacon_node \
             let mut cmd_run_part_1 = if cl_bn_bootnodes.is_empty() {
                 String::new()
             } else {
-                format!(" --boot-nodes='{cl_bn_bootnodes}' --trusted-peers='{cl_bn_trusted_peers}'")
+                format!(" --boot-nodes='{cl_bn_bootnodes}'")
             };
 
+            // Empty if only the external peers are available
+            if !cl_bn_trusted_peers.is_empty() {
+                cmd_run_part_1
+                    .push_str(&format!(" --trusted-peers='{cl_bn_trusted_peers}'"));
+            }
+
             if node_sync_from_genesis() || checkpoint_sync_url.is_empty() {
                 cmd_run_part_1.push_str(" --allow-insecure-genesis-sync");
             } else {
@@ -661,9 +1241,8 @@ nohup {home}/lighthouse_bin beacon_node \
                     .push_str(&format!(" --checkpoint-sync-url={checkpoint_sync_url}"));
             }
 
-            let cmd_run_part_2 = " >/dev/null 2>&1 &";
-
-            cmd_run_part_0 + &cmd_run_part_1 + cmd_run_part_2
+            cmd_prepare_part
+                + &launch("cl-bn", CL_BN_ERR_NAME, &(cmd_run_part_0 + &cmd_run_part_1))
         };
 
         let cl_vc_cmd = {
@@ -692,9 +1271,12 @@ fi "#
             let cmd_run_part_1 = format!(
                 r#"
 mkdir -p {cl_vc_dir} || exit 1
-sleep 1
+sleep 1 "#
+            );
 
-nohup {home}/lighthouse_bin validator_client \
+            let cmd_run_part_2 = format!(
+                r#"
+{home}/lighthouse_bin validator_client \
     --testnet-dir={cl_genesis} \
     --datadir={cl_vc_dir}\
     --logfile={cl_vc_dir}/logs/{CL_VC_LOG_NAME} \
@@ -709,12 +1291,12 @@ nohup {home}/lighthouse_bin validator_client \
     --http --http-address="127.0.0.1" \
     --http-port={cl_vc_rpc_port} --http-allow-origin='*' \
     --metrics --metrics-address={local_ip} \
-    --metrics-port={cl_vc_metric_port} --metrics-allow-origin='*' \
-    >/dev/null 2>&1 &
-     "#
+    --metrics-port={cl_vc_metric_port} --metrics-allow-origin='*' "#
             );
 
-            cmd_run_part_0 + &cmd_run_part_1
+            cmd_run_part_0
+                + &cmd_run_part_1
+                + &launch("cl-vc", CL_VC_ERR_NAME, &cmd_run_part_2)
         };
 
         ////////////////////////////////////////////////
@@ -726,6 +1308,8 @@ nohup {home}/lighthouse_bin validator_client \
 
             {prepare_cmd}
 
+            {cmd_write_keys}
+
             {el_cmd}
 
             {cl_bn_cmd}
@@ -739,15 +1323,14 @@ nohup {home}/lighthouse_bin validator_client \
     fn cmd_for_stop(
         &self,
         n: &Node<Ports>,
-        _e: &EnvMeta<CustomInfo, Node<Ports>>,
+        e: &EnvMeta<CustomInfo, Node<Ports>>,
         force: bool,
     ) -> String {
-        format!(
-            "for i in \
-            $(ps ax -o pid,args|grep '{}'|grep -v grep|sed -r 's/(^ *)|( +)/ /g'|cut -d ' ' -f 2); \
-            do kill {} $i; done",
+        process::cmd_for_stop(
+            &e.custom_data.process_backend,
             &n.home,
-            alt!(force, "-9", ""),
+            force,
+            e.custom_data.stop_timeout_secs,
         )
     }
 }
@@ -763,6 +1346,7 @@ enum ExtraOp {
         wallet_seckey_path: Option<String>,
         withdraw_0x01_addr: Option<String>,
         async_wait: bool,
+        dry_run: bool,
     },
     ValidatorExit {
         nodes: String, /*comma separated node IDs*/
@@ -771,6 +1355,7 @@ enum ExtraOp {
     Show {
         clean_up: bool,
         write_back: bool,
+        output: Option<OutputFormat>,
     },
     ListRpcs {
         el_web3: bool,
@@ -780,12 +1365,81 @@ enum ExtraOp {
         cl_bn_metric: bool,
         cl_vc: bool,
         cl_vc_metric: bool,
+        output: Option<OutputFormat>,
+    },
+    List {
+        output: OutputFormat,
+    },
+    DebugFailedNodes {
+        output: OutputFormat,
+    },
+    Health {
+        output: Option<OutputFormat>,
+    },
+    Topology {
+        dot: Option<String>,
+        output: Option<OutputFormat>,
+    },
+    Supervise {
+        interval_secs: u64,
+        max_backoff_secs: u64,
+        webhook: Option<String>,
     },
     SwitchELToGeth {
         nodes: BTreeSet<NodeID>,
+        dry_run: bool,
     },
     SwitchELToReth {
         nodes: BTreeSet<NodeID>,
+        dry_run: bool,
+    },
+    Label {
+        nodes: String,
+        remove: bool,
+        labels: BTreeSet<String>,
+        notes: BTreeMap<String, String>,
+    },
+    SnapshotCreate {
+        name: String,
+    },
+    SnapshotRestore {
+        name: String,
+    },
+    SnapshotList,
+    SnapshotDelete {
+        name: String,
+    },
+    ChaosPartition {
+        groups: String,
+        duration_secs: u64,
+    },
+    ChaosNetem {
+        nodes: String,
+        netem: Netem,
+    },
+    ChaosClear,
+    ChaosMonkey {
+        nodes: String,
+        interval_secs: u64,
+        max_down: MaxDown,
+        duration_secs: u64,
+        seed: Option<u64>,
+    },
+    Clone {
+        src: EnvName,
+        with_data: bool,
+    },
+    History {
+        filter: HistoryFilter,
+        output: OutputFormat,
+    },
+    Hook {
+        webhooks: BTreeSet<String>,
+        scripts: BTreeSet<String>,
+        remove: bool,
+    },
+    ExportJoinKit {
+        dir: String,
     },
     Git {
         remote_url: Option<String>,
@@ -795,6 +1449,18 @@ enum ExtraOp {
 
 impl CustomOps for ExtraOp {
     fn exec(&self, en: &EnvName) -> Result<()> {
+        // The ENV does not exist yet
+        if let Self::Clone { src, with_data } = self {
+            return env_clone(src, en, *with_data).c(d!());
+        }
+
+        // No ENV is needed
+        if let Self::List { output } = self {
+            return SysEnv::<CustomInfo, Ports, CmdGenerator>::get_env_list()
+                .c(d!())
+                .and_then(|list| print_env_list(*output, &list));
+        }
+
         let mut env = load_sysenv(en).c(d!())?;
 
         match self {
@@ -804,32 +1470,13 @@ impl CustomOps for ExtraOp {
                 wallet_seckey_path,
                 withdraw_0x01_addr,
                 async_wait,
+                dry_run,
             } => {
                 let nodes = nodes.trim();
                 let withdraw_addr = withdraw_0x01_addr.as_ref().map(|addr| addr.trim());
                 let mut env = load_sysenv(en).c(d!())?;
 
-                // All non-fuhrer nodes
-                let nodes = if "all" == nodes {
-                    env.meta.nodes.values().cloned().collect::<Vec<_>>()
-                } else {
-                    let ids = parse_nodes(nodes).c(d!())?;
-                    let mut nodes = vec![];
-                    for id in ids.iter() {
-                        if env.meta.fuhrers.contains_key(id) {
-                            return Err(eg!(
-                                "Fuhrer node(id: {}) does not accept deposits",
-                                id
-                            ));
-                        }
-                        if let Some(n) = env.meta.nodes.get(id).cloned() {
-                            nodes.push(n);
-                        } else {
-                            return Err(eg!("The node(id: {}) does not exist", id));
-                        }
-                    }
-                    nodes
-                };
+                let nodes = env_select_non_fuhrer_nodes(&env, nodes).c(d!())?;
 
                 if nodes.is_empty() {
                     return Err(eg!("No target nodes found!"));
@@ -851,26 +1498,23 @@ impl CustomOps for ExtraOp {
                         .meta
                         .premined_accounts
                         .as_object()
-                        .unwrap()
+                        .c(d!())?
                         .iter()
                         .next()
-                        .c(d!())?;
+                        .c(d!("No premined accounts, use `--wallet-seckey-path`"))?;
                     let addr = withdraw_addr.unwrap_or(addr).to_owned();
-                    let key = obj.as_object().unwrap()["secretKey"]
+                    // Not available for the external networks
+                    let key = obj["secretKey"]
                         .as_str()
-                        .unwrap()
+                        .filter(|k| !k.is_empty())
+                        .c(d!("No secret key of the premined account, use `--wallet-seckey-path`"))?
                         .to_owned();
                     (addr, key)
                 };
 
                 let testnet_dir =
                     format!("{}/{NODE_HOME_GENESIS_DIR_DST}", env.meta.home);
-                let config_yml = format!("{}/config.yaml", testnet_dir);
-                let cfg = fs::read_to_string(config_yml)
-                    .c(d!())
-                    .and_then(|s| serde_yml::from_str::<serde_yml::Value>(&s).c(d!()))?;
-                let deposit_contract =
-                    cfg["DEPOSIT_CONTRACT_ADDRESS"].as_str().unwrap().to_owned();
+                let deposit_contract = genesis_deposit_contract(&testnet_dir).c(d!())?;
 
                 let runtime = crate::common::new_sb_runtime();
 
@@ -883,8 +1527,6 @@ impl CustomOps for ExtraOp {
                 for n in nodes.into_iter() {
                     let tmp_dir =
                         format!("/tmp/{}_{}", ts!(), ruc::algo::rand::rand_jwt());
-                    omit!(fs::remove_dir_all(&tmp_dir));
-                    fs::create_dir_all(&tmp_dir).c(d!())?;
 
                     let mnemonic_path = format!("{tmp_dir}/mnemonic.txt");
 
@@ -892,7 +1534,6 @@ impl CustomOps for ExtraOp {
                     let validators_json = format!("{tmp_dir}/validators.json");
 
                     let mnemonic = create_mnemonic_words();
-                    fs::write(&mnemonic_path, &mnemonic).c(d!())?;
 
                     let node_vc_data_dir = format!("{}/{CL_VC_DIR}", n.home);
                     let node_vc_api_token =
@@ -921,8 +1562,6 @@ impl CustomOps for ExtraOp {
                             --output-path {tmp_dir}
                         "#
                     );
-                    ruc::cmd::exec_output(&cmd).c(d!())?;
-
                     let node_cmd = format!(
                         r#"
                         lighthouse validator-manager import \
@@ -933,6 +1572,28 @@ impl CustomOps for ExtraOp {
                             --vc-token {node_vc_api_token}
                         "#
                     );
+
+                    if *dry_run {
+                        dry_run_note(
+                            "localhost",
+                            &format!("write a new mnemonic into {mnemonic_path}"),
+                        );
+                        dry_run_script("localhost", &cmd);
+                        dry_run_script(&format!("node {}", n.id), &node_cmd);
+                        dry_run_note(
+                            "localhost",
+                            &format!(
+                                "send the deposits in {deposits_json} to {deposit_contract} via {el_rpc_endpoint}"
+                            ),
+                        );
+                        continue;
+                    }
+
+                    omit!(fs::remove_dir_all(&tmp_dir));
+                    fs::create_dir_all(&tmp_dir).c(d!())?;
+                    fs::write(&mnemonic_path, &mnemonic).c(d!())?;
+
+                    ruc::cmd::exec_output(&cmd).c(d!())?;
                     ruc::cmd::exec_output(&node_cmd).c(d!())?;
 
                     let deposits_json = fs::read_to_string(deposits_json).c(d!())?;
@@ -961,27 +1622,7 @@ impl CustomOps for ExtraOp {
                 let nodes = nodes.trim();
                 let mut env = load_sysenv(en).c(d!())?;
 
-                // All non-fuhrer nodes
-                let nodes = if "all" == nodes {
-                    env.meta.nodes.values().cloned().collect::<Vec<_>>()
-                } else {
-                    let ids = parse_nodes(nodes).c(d!())?;
-                    let mut nodes = vec![];
-                    for id in ids.iter() {
-                        if env.meta.fuhrers.contains_key(id) {
-                            return Err(eg!(
-                                "Fuhrer node(id: {}) does not accept deposits",
-                                id
-                            ));
-                        }
-                        if let Some(n) = env.meta.nodes.get(id).cloned() {
-                            nodes.push(n);
-                        } else {
-                            return Err(eg!("The node(id: {}) does not exist", id));
-                        }
-                    }
-                    nodes
-                };
+                let nodes = env_select_non_fuhrer_nodes(&env, nodes).c(d!())?;
 
                 if nodes.is_empty() {
                     return Err(eg!("No target nodes found!"));
@@ -1032,6 +1673,7 @@ impl CustomOps for ExtraOp {
             Self::Show {
                 clean_up,
                 write_back,
+                output,
             } => {
                 if *clean_up {
                     macro_rules! cl_up {
@@ -1049,44 +1691,31 @@ impl CustomOps for ExtraOp {
                     }
                 }
 
-                let mut ret = pnk!(serde_json::to_value(&env));
-
-                ret.as_object_mut()
-                    .unwrap()
-                    .remove("node_cmdline_generator");
-
-                let meta = ret["meta"].as_object_mut().unwrap();
-
-                meta.remove("genesis");
-                meta.remove("genesis_vkeys");
-                meta.remove("genesis_mnemonic_words");
-                meta.remove("genesis_validator_num");
-                meta.remove("nodes_should_be_online");
-                meta.remove("next_node_id");
-
-                let mut list_to_cnt = |field: &str| {
-                    for ids in meta[field]
-                        .as_object_mut()
-                        .unwrap()
-                        .values_mut()
-                        .flat_map(|v| {
-                            v.as_object_mut().unwrap()["custom_data"]
-                                .as_object_mut()
-                                .unwrap()["deposits"]
-                                .as_object_mut()
-                                .unwrap()
-                                .values_mut()
+                if let Some(f @ (OutputFormat::Table | OutputFormat::Plain)) = output {
+                    let nodes = env
+                        .meta
+                        .fuhrers
+                        .values()
+                        .chain(env.meta.nodes.values())
+                        .map(|n| {
+                            Ok(NodeSummary {
+                                node: node_brief(&env, n).c(d!())?,
+                                validators: json_deposits_cnt(&n.custom_data).c(d!())?,
+                                should_be_online: env
+                                    .meta
+                                    .nodes_should_be_online
+                                    .contains_key(&n.id),
+                                netem: env.meta.custom_data.netem.get(&n.id).cloned(),
+                            })
                         })
-                    {
-                        mem::swap(
-                            ids,
-                            &mut JsonValue::Number(ids.as_array().unwrap().len().into()),
-                        );
-                    }
-                };
+                        .collect::<Result<Vec<_>>>()?;
+                    print_nodes(*f, &nodes);
+                    return Ok(());
+                }
 
-                list_to_cnt("fuhrer_nodes");
-                list_to_cnt("nodes");
+                let ret = serde_json::to_value(&env)
+                    .c(d!())
+                    .and_then(|env| env_show_json(env).c(d!()))?;
 
                 println!("{}", pnk!(serde_json::to_string_pretty(&ret)));
 
@@ -1100,6 +1729,7 @@ impl CustomOps for ExtraOp {
                 cl_bn_metric,
                 cl_vc,
                 cl_vc_metric,
+                output,
             } => {
                 let default = !(*el_web3
                     || *el_web3_ws
@@ -1108,117 +1738,218 @@ impl CustomOps for ExtraOp {
                     || *cl_bn_metric
                     || *cl_vc
                     || *cl_vc_metric);
-                let env = load_sysenv(en).c(d!())?;
 
-                let mut buf_el_web3 = vec![];
-                let mut buf_el_web3_ws = vec![];
-                let mut buf_el_metric = vec![];
-                let mut buf_cl_bn = vec![];
-                let mut buf_cl_bn_metric = vec![];
-                let mut buf_cl_vc = vec![];
-                let mut buf_cl_vc_metric = vec![];
+                let url = |wanted: bool, port: u16| {
+                    (wanted || default)
+                        .then(|| format!("http://{}:{port}", env.meta.host_ip))
+                };
 
-                env.meta
+                let nodes = env
+                    .meta
                     .fuhrers
                     .values()
                     .chain(env.meta.nodes.values())
-                    .for_each(|n| {
-                        if *el_web3 || default {
-                            buf_el_web3.push(format!(
-                                "    http://{}:{}",
-                                env.meta.host_ip, n.ports.el_rpc
-                            ));
-                        }
-                        if *el_web3_ws || default {
-                            buf_el_web3_ws.push(format!(
-                                "    http://{}:{}",
-                                env.meta.host_ip, n.ports.el_rpc_ws
-                            ));
-                        }
-                        if *el_metric || default {
-                            buf_el_metric.push(format!(
-                                "    http://{}:{}",
-                                env.meta.host_ip, n.ports.el_metric
-                            ));
-                        }
-                        if *cl_bn || default {
-                            buf_cl_bn.push(format!(
-                                "    http://{}:{}",
-                                env.meta.host_ip, n.ports.cl_bn_rpc
-                            ));
-                        }
-                        if *cl_bn_metric || default {
-                            buf_cl_bn_metric.push(format!(
-                                "    http://{}:{}",
-                                env.meta.host_ip, n.ports.cl_bn_metric
-                            ));
-                        }
-                        if *cl_vc || default {
-                            buf_cl_vc.push(format!(
-                                "    http://{}:{}",
-                                env.meta.host_ip, n.ports.cl_vc_rpc
-                            ));
-                        }
-                        if *cl_vc_metric || default {
-                            buf_cl_vc_metric.push(format!(
-                                "    http://{}:{}",
-                                env.meta.host_ip, n.ports.cl_vc_metric
-                            ));
-                        }
-                    });
-
-                if !buf_el_web3.is_empty() {
-                    println!("\x1b[33;1mEL WEB3 RPCs:\x1b[0m");
-                    buf_el_web3.iter().for_each(|l| {
-                        println!("{l}");
-                    });
-                }
+                    .map(|n| {
+                        Ok(NodeRpcs {
+                            node: node_brief(&env, n).c(d!())?,
+                            el_web3: url(*el_web3, n.ports.el_rpc),
+                            el_web3_ws: url(*el_web3_ws, n.ports.el_rpc_ws),
+                            el_metric: url(*el_metric, n.ports.el_metric),
+                            cl_bn: url(*cl_bn, n.ports.cl_bn_rpc),
+                            cl_bn_metric: url(*cl_bn_metric, n.ports.cl_bn_metric),
+                            cl_vc: url(*cl_vc, n.ports.cl_vc_rpc),
+                            cl_vc_metric: url(*cl_vc_metric, n.ports.cl_vc_metric),
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
 
-                if !buf_el_web3_ws.is_empty() {
-                    println!("\x1b[33;1mEL WEB3 WS RPCs:\x1b[0m");
-                    buf_el_web3_ws.iter().for_each(|l| {
-                        println!("{l}");
-                    });
+                print_rpcs(output.unwrap_or(OutputFormat::Table), &nodes)
+            }
+            Self::List { .. } | Self::Clone { .. } => unreachable!(),
+            Self::DebugFailedNodes { output } => {
+                let mut nodes = vec![];
+                let mut errors = vec![];
+                for n in env.meta.nodes.values().chain(env.meta.fuhrers.values()) {
+                    match node_process_cnt(&env, n) {
+                        Ok(cnt) if 3 > cnt => nodes.push(FailedNode {
+                            node: node_brief(&env, n).c(d!())?,
+                            err_tails: node_err_tails(n).c(d!())?,
+                        }),
+                        Ok(_) => {}
+                        Err(e) => errors.push(e.to_string()),
+                    }
                 }
-
-                if !buf_el_metric.is_empty() {
-                    println!("\x1b[33;1mEL METRIC RPCs:\x1b[0m");
-                    buf_el_metric.iter().for_each(|l| {
-                        println!("{l}");
-                    });
+                print_failed_nodes(*output, &nodes, &errors)
+            }
+            Self::Health { output } => {
+                let health = env
+                    .meta
+                    .fuhrers
+                    .values()
+                    .chain(env.meta.nodes.values())
+                    .map(|n| node_health(&env, n).c(d!()))
+                    .collect::<Result<Vec<_>>>()?;
+                print_health(output.unwrap_or(OutputFormat::Table), &health)
+            }
+            Self::Topology { dot, output } => {
+                let ip = &env.meta.host_ip;
+                let mut probes = BTreeMap::new();
+                for n in env
+                    .meta
+                    .fuhrers
+                    .values()
+                    .chain(env.meta.nodes.values())
+                    .filter(|n| env.meta.nodes_should_be_online.contains_key(&n.id))
+                {
+                    if json_bootnode(&n.custom_data).c(d!())?.is_some() {
+                        continue;
+                    }
+                    let identity =
+                        NodeIdentity::derive(&env.meta.custom_data.identity_seed, n.id);
+                    let p = topology::probe(
+                        &format!("http://{ip}:{}", n.ports.el_rpc),
+                        &format!("http://{ip}:{}", n.ports.cl_bn_rpc),
+                        identity.as_ref(),
+                    );
+                    probes.insert(n.id, p);
                 }
 
-                if !buf_cl_bn.is_empty() {
-                    println!("\x1b[33;1mCL BEACON RPCs:\x1b[0m");
-                    buf_cl_bn.iter().for_each(|l| {
-                        println!("{l}");
-                    });
+                let t = Topology::build(en, probes);
+                if let Some(path) = dot {
+                    fs::write(path, t.to_dot()).c(d!(path))?;
                 }
+                print_topology(output.unwrap_or(OutputFormat::Table), &t)
+            }
+            Self::Supervise {
+                interval_secs,
+                max_backoff_secs,
+                webhook,
+            } => {
+                let mut backoff = Backoff::new(*interval_secs, *max_backoff_secs);
+                println!("Supervising the ENV '{en}', checking every {interval_secs}s");
 
-                if !buf_cl_bn_metric.is_empty() {
-                    println!("\x1b[33;1mCL BEACON METRIC RPCs:\x1b[0m");
-                    buf_cl_bn_metric.iter().for_each(|l| {
-                        println!("{l}");
-                    });
-                }
+                loop {
+                    // Reload each round to follow the changes made by other operations
+                    let env = load_sysenv(en).c(d!())?;
+                    let nodes = env
+                        .meta
+                        .fuhrers
+                        .values()
+                        .chain(env.meta.nodes.values())
+                        .filter(|n| env.meta.nodes_should_be_online.contains_key(&n.id))
+                        .collect::<Vec<_>>();
+
+                    for n in nodes {
+                        let process_cnt = match node_process_cnt(&env, n) {
+                            Ok(cnt) => cnt,
+                            Err(e) => {
+                                eprintln!("Node {}: {}", n.id, e.get_lowest_msg());
+                                continue;
+                            }
+                        };
+                        if 3 <= process_cnt {
+                            backoff.reset(n.id);
+                            continue;
+                        }
+                        let Some(attempt) = backoff.try_attempt(n.id, ts!()) else {
+                            continue;
+                        };
+
+                        // Always wait, the supervisor should not give up for a busy ENV
+                        let lock = match EnvLock::acquire(
+                            &lock_path("dev", en),
+                            "supervise_restart",
+                            true,
+                        ) {
+                            Ok(lock) => lock,
+                            Err(e) => {
+                                eprintln!("Node {}: {}", n.id, e.get_lowest_msg());
+                                continue;
+                            }
+                        };
+
+                        // Checked again under the lock, the node may have been
+                        // stopped, kicked or restarted by others in the meantime
+                        let env = match load_sysenv(en) {
+                            Ok(env) => env,
+                            Err(e) => {
+                                eprintln!("Node {}: {}", n.id, e.get_lowest_msg());
+                                continue;
+                            }
+                        };
+                        let Some(n) = env
+                            .meta
+                            .fuhrers
+                            .get(&n.id)
+                            .or_else(|| env.meta.nodes.get(&n.id))
+                            .filter(|n| {
+                                env.meta.nodes_should_be_online.contains_key(&n.id)
+                            })
+                        else {
+                            continue;
+                        };
+                        let process_cnt = match node_process_cnt(&env, n) {
+                            Ok(cnt) if 3 <= cnt => {
+                                backoff.reset(n.id);
+                                continue;
+                            }
+                            Ok(cnt) => cnt,
+                            Err(e) => {
+                                eprintln!("Node {}: {}", n.id, e.get_lowest_msg());
+                                continue;
+                            }
+                        };
+
+                        let res = SysCfg {
+                            name: en.clone(),
+                            op: Op::<CustomInfo, Ports, ExtraOp>::Restart {
+                                nodes: Some([n.id].into()),
+                                ignore_failed: false,
+                                wait_itv_secs: 1,
+                            },
+                        }
+                        .exec(CmdGenerator);
+
+                        let ev = RestartEvent {
+                            env: en.to_string(),
+                            node: n.id,
+                            host: env.meta.host_ip.clone(),
+                            process_cnt,
+                            attempt,
+                            timestamp: ts!(),
+                            error: res.err().map(|e| e.get_lowest_msg().to_string()),
+                        };
+                        println!("{}", ev.log_line().trim());
+
+                        let audit_ev = OpEvent::new(
+                            en,
+                            "supervise_restart",
+                            Some([n.id].into()),
+                            &ev.error.clone().map_or(Ok(()), |e| Err(eg!(e))),
+                        );
+                        if let Err(e) = audit::append(&env.meta.home, &audit_ev) {
+                            eprintln!("Audit log: {}", e.get_lowest_msg());
+                        }
+                        let hooks = &env.meta.custom_data.hooks;
+                        if !hooks.is_empty() {
+                            hooks.fire(&audit_ev);
+                        }
+                        drop(lock);
 
-                if !buf_cl_vc.is_empty() {
-                    println!("\x1b[33;1mCL VALIDATOR RPCs:\x1b[0m");
-                    buf_cl_vc.iter().for_each(|l| {
-                        println!("{l}");
-                    });
-                }
+                        if let Err(e) = node_mgmt_log(n, &ev.log_line()) {
+                            eprintln!("Node {}: {}", n.id, e.get_lowest_msg());
+                        }
+                        if let Some(url) = webhook {
+                            if let Err(e) = ev.notify(url) {
+                                eprintln!("Webhook: {}", e.get_lowest_msg());
+                            }
+                        }
+                    }
 
-                if !buf_cl_vc_metric.is_empty() {
-                    println!("\x1b[33;1mCL VALIDATOR METRIC RPCs:\x1b[0m");
-                    buf_cl_vc_metric.iter().for_each(|l| {
-                        println!("{l}");
-                    });
+                    sleep_ms!(1000 * *interval_secs);
                 }
-
-                Ok(())
             }
-            Self::SwitchELToGeth { nodes } => {
+            Self::SwitchELToGeth { nodes, dry_run } => {
                 let mut ns = vec![];
                 for id in nodes.iter() {
                     let n = env
@@ -1234,6 +1965,19 @@ impl CustomOps for ExtraOp {
                     );
                 }
 
+                if *dry_run {
+                    for n in ns.iter() {
+                        let target = format!("node {}", n.id);
+                        dry_run_script(
+                            &target,
+                            &CmdGenerator.cmd_for_stop(n, &env.meta, false),
+                        );
+                        dry_run_script(&target, &format!("rm -rf {}/{EL_DIR}", n.home));
+                        dry_run_note(&target, "set the EL kind to geth in the ENV meta");
+                    }
+                    return Ok(());
+                }
+
                 SysCfg {
                     name: en.clone(),
                     op: Op::<CustomInfo, Ports, ExtraOp>::Stop {
@@ -1244,7 +1988,17 @@ impl CustomOps for ExtraOp {
                 .exec(CmdGenerator)
                 .c(d!())?;
 
-                sleep_ms!(3000); // wait for the graceful exiting process
+                // The stop script waits for the exiting of all processes,
+                // this is a double check before removing the data
+                for n in ns.iter() {
+                    let cnt = node_process_cnt(&env, n).c(d!())?;
+                    if 0 < cnt {
+                        return Err(eg!(
+                            "{cnt} processes of the node(id: {}) are still alive",
+                            n.id
+                        ));
+                    }
+                }
 
                 for (i, n) in ns.iter().enumerate() {
                     // Just remove $EL_DIR.
@@ -1275,7 +2029,7 @@ impl CustomOps for ExtraOp {
 
                 env.write_cfg().c(d!())
             }
-            Self::SwitchELToReth { nodes } => {
+            Self::SwitchELToReth { nodes, dry_run } => {
                 let mut ns = vec![];
                 for id in nodes.iter() {
                     let n = env
@@ -1291,6 +2045,19 @@ impl CustomOps for ExtraOp {
                     );
                 }
 
+                if *dry_run {
+                    for n in ns.iter() {
+                        let target = format!("node {}", n.id);
+                        dry_run_script(
+                            &target,
+                            &CmdGenerator.cmd_for_stop(n, &env.meta, false),
+                        );
+                        dry_run_script(&target, &format!("rm -rf {}/{EL_DIR}", n.home));
+                        dry_run_note(&target, "set the EL kind to reth in the ENV meta");
+                    }
+                    return Ok(());
+                }
+
                 SysCfg {
                     name: en.clone(),
                     op: Op::<CustomInfo, Ports, ExtraOp>::Stop {
@@ -1301,7 +2068,17 @@ impl CustomOps for ExtraOp {
                 .exec(CmdGenerator)
                 .c(d!())?;
 
-                sleep_ms!(3000); // wait for the graceful exiting process
+                // The stop script waits for the exiting of all processes,
+                // this is a double check before removing the data
+                for n in ns.iter() {
+                    let cnt = node_process_cnt(&env, n).c(d!())?;
+                    if 0 < cnt {
+                        return Err(eg!(
+                            "{cnt} processes of the node(id: {}) are still alive",
+                            n.id
+                        ));
+                    }
+                }
 
                 for (i, n) in ns.iter().enumerate() {
                     // Just remove $EL_DIR.
@@ -1332,6 +2109,295 @@ impl CustomOps for ExtraOp {
 
                 env.write_cfg().c(d!())
             }
+            Self::Label {
+                nodes,
+                remove,
+                labels,
+                notes,
+            } => {
+                let sel = nodes.parse::<Selector>().c(d!(nodes))?;
+                let ids = env_select_nodes(&env, &sel, true).c(d!())?;
+                if ids.is_empty() {
+                    return Err(eg!("No target nodes found!"));
+                }
+
+                for id in ids.iter() {
+                    let n = env
+                        .meta
+                        .nodes
+                        .get_mut(id)
+                        .or_else(|| env.meta.fuhrers.get_mut(id))
+                        .unwrap();
+                    json_labels_update(&mut n.custom_data, *remove, labels, notes)
+                        .c(d!())?;
+                }
+
+                env.write_cfg().c(d!())
+            }
+            Self::SnapshotCreate { name } => {
+                let home = env.meta.home.clone();
+                let online = online_nodes(&env);
+                snapshot::init(&home, name).c(d!())?;
+                let dir = snapshot::snapshot_dir(&home, name);
+
+                let res = fs::copy(format!("{home}/CONFIG"), format!("{dir}/CONFIG"))
+                    .c(d!())
+                    .and_then(|_| stop_all_nodes(en).c(d!()))
+                    .and_then(|_| {
+                        let nodes_dir = snapshot::nodes_dir(&home, name);
+                        env.meta
+                            .fuhrers
+                            .values()
+                            .chain(env.meta.nodes.values())
+                            .try_for_each(|n| {
+                                let tgz =
+                                    format!("{nodes_dir}/N{}_{}.tgz", n.id, n.kind);
+                                let cmd = snapshot::cmd_pack(&n.home, &tgz);
+                                cmd::exec_output(&cmd).c(d!(cmd)).map(|_| ())
+                            })
+                    })
+                    .and_then(|_| {
+                        snapshot::Manifest {
+                            name: name.clone(),
+                            created_at: ts!(),
+                            online: online.clone(),
+                            nodes: node_locations(&env),
+                        }
+                        .save(&home)
+                        .c(d!())
+                    });
+                if res.is_err() {
+                    // Do not leave a broken snapshot behind
+                    let _ = fs::remove_dir_all(&dir);
+                }
+
+                // The nodes are started again even if the snapshot failed
+                let started = start_nodes(en, &online).c(d!());
+                res.and(started)
+                    .map(|_| println!("Snapshot '{name}' is created at: {dir}"))
+            }
+            Self::SnapshotRestore { name } => {
+                let home = env.meta.home.clone();
+                let m = snapshot::Manifest::load(&home, name).c(d!())?;
+                m.check_nodes(&node_locations(&env)).c(d!())?;
+                let snap_env = snapshot_env(&home, name).c(d!())?;
+
+                let online = online_nodes(&env);
+
+                stop_all_nodes(en).c(d!())?;
+                let res = env
+                    .meta
+                    .fuhrers
+                    .values()
+                    .chain(env.meta.nodes.values())
+                    .try_for_each(|n| {
+                        let tgzs = snapshot::node_packages(&home, name, n.id).c(d!())?;
+                        let cmd = snapshot::cmd_unpack(&n.home, &tgzs);
+                        cmd::exec_output(&cmd).c(d!(cmd)).map(|_| ())
+                    });
+                if res.is_err() {
+                    // The nodes are started again even if the restoring failed
+                    let started = start_nodes(en, &online).c(d!());
+                    return res.and(started);
+                }
+                snapshot_restore_meta(en, snap_env).c(d!())?;
+
+                start_nodes(en, &m.online)
+                    .c(d!())
+                    .map(|_| println!("Snapshot '{name}' is restored"))
+            }
+            Self::SnapshotList => {
+                for m in snapshot::list(&env.meta.home).c(d!())? {
+                    println!(
+                        "{}\t{}\t{} nodes\t{} online",
+                        m.name,
+                        datetime!(m.created_at),
+                        m.nodes.len(),
+                        m.online.len()
+                    );
+                }
+                Ok(())
+            }
+            Self::SnapshotDelete { name } => {
+                snapshot::remove(&env.meta.home, name).c(d!())
+            }
+            Self::ChaosPartition {
+                groups,
+                duration_secs,
+            } => {
+                let partition = env_partition(&env, groups).c(d!())?;
+                for cmd in partition.cmds_for_start(en, *duration_secs).values() {
+                    cmd::exec_output(cmd).c(d!())?;
+                }
+                println!(
+                    "The P2P traffic between the groups is cut for {duration_secs}s, it heals automatically at the end"
+                );
+                Ok(())
+            }
+            Self::ChaosNetem { nodes, netem } => {
+                let sel = nodes.parse::<Selector>().c(d!(nodes))?;
+                let ids = env_select_nodes(&env, &sel, true).c(d!())?;
+                if ids.is_empty() {
+                    return Err(eg!("No target nodes found!"));
+                }
+                for id in ids {
+                    env.meta.custom_data.netem.insert(id, netem.clone());
+                }
+
+                // Recorded first, so `chaos clear` can always revert it
+                env.write_cfg().c(d!())?;
+                env_apply_netem(&env).c(d!())
+            }
+            Self::ChaosClear => {
+                env.meta.custom_data.netem.clear();
+                env.write_cfg().c(d!())?;
+                cmd::exec_output(&chaos::cmd_for_clear(en, &env.meta.host_ip))
+                    .c(d!())
+                    .map(|_| ())
+            }
+            Self::ChaosMonkey {
+                nodes,
+                interval_secs,
+                max_down,
+                duration_secs,
+                seed,
+            } => {
+                let sel = nodes.parse::<Selector>().c(d!(nodes))?;
+                let ids = env_select_nodes(&env, &sel, true).c(d!())?;
+                if ids.is_empty() {
+                    return Err(eg!("No target nodes found!"));
+                }
+                let cap = max_down.cap(env.meta.fuhrers.len() + env.meta.nodes.len());
+                if 0 == cap {
+                    return Err(eg!("No node can be down under the cap"));
+                }
+                let seed = seed.unwrap_or_else(chaos::new_monkey_seed);
+                let mut monkey = Monkey::new(seed, *interval_secs, cap, ids);
+                println!(
+                    "Chaos monkey on the ENV '{en}'(seed: {seed}), acting every {interval_secs}s, at most {cap} node(s) down, for {duration_secs}s"
+                );
+
+                let end = ts!() + duration_secs;
+                let mut next_ts = ts!() + interval_secs;
+                loop {
+                    let now = ts!();
+                    for id in monkey.due(now) {
+                        monkey_exec(en, id, "start", false);
+                    }
+                    if end <= now {
+                        break;
+                    }
+                    if next_ts <= now {
+                        next_ts += interval_secs;
+                        // Reload each round to follow the changes made by other operations
+                        let env = load_sysenv(en).c(d!())?;
+                        let offline = env
+                            .meta
+                            .fuhrers
+                            .keys()
+                            .chain(env.meta.nodes.keys())
+                            .filter(|id| {
+                                !env.meta.nodes_should_be_online.contains_key(id)
+                            })
+                            .copied()
+                            .collect::<BTreeSet<_>>();
+                        match monkey.next(now, &offline) {
+                            Some((id, action)) => {
+                                let force = chaos::MonkeyAction::Kill == action;
+                                monkey_exec(en, id, action.name(), force);
+                            }
+                            None => println!(
+                                "[{}] skipped, {} node(s) are down already",
+                                datetime!(),
+                                offline.len()
+                            ),
+                        }
+                    }
+                    sleep_ms!(1000);
+                }
+
+                for id in monkey.release_all() {
+                    monkey_exec(en, id, "start", false);
+                }
+                Ok(())
+            }
+            Self::History { filter, output } => {
+                let evs = audit::load(&env.meta.home).c(d!())?;
+                print_history(*output, &filter.apply(evs))
+            }
+            Self::Hook {
+                webhooks,
+                scripts,
+                remove,
+            } => {
+                let hooks = &mut env.meta.custom_data.hooks;
+                if *remove {
+                    hooks.webhooks.retain(|i| !webhooks.contains(i));
+                    hooks.scripts.retain(|i| !scripts.contains(i));
+                } else {
+                    hooks.webhooks.extend(webhooks.iter().cloned());
+                    hooks.scripts.extend(scripts.iter().cloned());
+                }
+                if !webhooks.is_empty() || !scripts.is_empty() {
+                    env.write_cfg().c(d!())?;
+                }
+
+                let hooks = &env.meta.custom_data.hooks;
+                hooks.webhooks.iter().for_each(|i| println!("webhook\t{i}"));
+                hooks.scripts.iter().for_each(|i| println!("script\t{i}"));
+                Ok(())
+            }
+            Self::ExportJoinKit { dir } => {
+                let env = load_sysenv(en).c(d!())?;
+                let (el_rpc_endpoints, cl_bn_rpc_endpoints): (Vec<_>, Vec<_>) = env
+                    .meta
+                    .fuhrers
+                    .values()
+                    .chain(env.meta.nodes.values())
+                    .filter(|n| env.meta.nodes_should_be_online.contains_key(&n.id))
+                    .map(|n| {
+                        (
+                            format!("http://{}:{}", env.meta.host_ip, n.ports.el_rpc),
+                            format!("http://{}:{}", env.meta.host_ip, n.ports.cl_bn_rpc),
+                        )
+                    })
+                    .unzip();
+
+                let el_rpc_endpoints = el_rpc_endpoints
+                    .iter()
+                    .map(|i| i.as_str())
+                    .collect::<Vec<_>>();
+                let cl_bn_rpc_endpoints = cl_bn_rpc_endpoints
+                    .iter()
+                    .map(|i| i.as_str())
+                    .collect::<Vec<_>>();
+                let enodes = el_get_boot_nodes(&el_rpc_endpoints)
+                    .c(d!("Failed to get the enodes, are the nodes running?"))?;
+                let (_, enrs, _) = cl_get_boot_nodes(&cl_bn_rpc_endpoints)
+                    .c(d!("Failed to get the ENRs, are the nodes running?"))?;
+                let split = |s: String| s.split(',').map(|i| i.to_owned()).collect();
+                // The dedicated bootnodes go first, no node is excluded
+                let (enodes, enrs) = if let Some((e, r, _)) =
+                    dedicated_bootnodes(&env.meta, NodeID::MAX)
+                {
+                    (format!("{e},{enodes}"), format!("{r},{enrs}"))
+                } else {
+                    (enodes, enrs)
+                };
+
+                let genesis_dir =
+                    format!("{}/{NODE_HOME_GENESIS_DIR_DST}", env.meta.home);
+                JoinKit {
+                    env_name: en.to_string(),
+                    chain_id: genesis_chain_id(&genesis_dir).c(d!())?,
+                    deposit_contract: genesis_deposit_contract(&genesis_dir).c(d!())?,
+                    enodes: split(enodes),
+                    enrs: split(enrs),
+                }
+                .write(&genesis_dir, dir)
+                .c(d!())
+                .map(|_| println!("The join kit is exported to: {dir}"))
+            }
             Self::Git { remote_url, push } => {
                 let env_home = load_sysenv(en).c(d!())?.meta.home;
 
@@ -1348,6 +2414,13 @@ impl CustomOps for ExtraOp {
                     cmd.push_str(&piece);
                 }
 
+                // The audit log is not committed by the ENV itself
+                cmd.push_str(&format!(
+                    "if [ -n \"$(git status --porcelain {0})\" ]; then \
+                    git add {0} && git commit -m 'audit log' >/dev/null || exit 1; fi;",
+                    audit::AUDIT_LOG_NAME
+                ));
+
                 if *push {
                     cmd.push_str("git push expchain HEAD:master")
                 }
@@ -1360,8 +2433,559 @@ impl CustomOps for ExtraOp {
     }
 }
 
+fn node_brief(
+    env: &SysEnv<CustomInfo, Ports, CmdGenerator>,
+    n: &Node<Ports>,
+) -> Result<NodeBrief> {
+    let kind = if json_bootnode(&n.custom_data).c(d!())?.is_some() {
+        "Bootnode".to_owned()
+    } else {
+        n.kind.to_string()
+    };
+    Ok(NodeBrief {
+        id: n.id,
+        kind,
+        el: json_el_kind(&n.custom_data).c(d!())?.to_string(),
+        host: env.meta.host_ip.clone(),
+        labels: json_labels(&n.custom_data).c(d!())?,
+        notes: json_notes(&n.custom_data).c(d!())?,
+    })
+}
+
+fn node_process_cnt(
+    env: &SysEnv<CustomInfo, Ports, CmdGenerator>,
+    n: &Node<Ports>,
+) -> Result<u64> {
+    let cmd = CmdGenerator.cmd_cnt_running(n, &env.meta);
+    cmd::exec_output(&cmd)
+        .c(d!(&cmd))?
+        .trim()
+        .parse::<u64>()
+        .c(d!())
+}
+
+// The RPC queries are best-effort,
+// and are skipped if no process of the node is alive
+fn node_health(
+    env: &SysEnv<CustomInfo, Ports, CmdGenerator>,
+    n: &Node<Ports>,
+) -> Result<NodeHealth> {
+    let process_cnt = node_process_cnt(env, n).c(d!())?;
+    let should_be_online = env.meta.nodes_should_be_online.contains_key(&n.id);
+
+    let (el_block_number, (cl_head_slot, cl_is_syncing)) = if 0 < process_cnt {
+        let ip = &env.meta.host_ip;
+        (
+            el_get_block_number(&format!("http://{ip}:{}", n.ports.el_rpc)).ok(),
+            cl_get_sync_status(&format!("http://{ip}:{}", n.ports.cl_bn_rpc))
+                .ok()
+                .unzip(),
+        )
+    } else {
+        (None, (None, None))
+    };
+
+    Ok(NodeHealth {
+        node: node_brief(env, n).c(d!())?,
+        status: NodeStatus::new(should_be_online, process_cnt),
+        process_cnt,
+        should_be_online,
+        el_block_number,
+        cl_head_slot,
+        cl_is_syncing,
+    })
+}
+
+// An action of the chaos monkey, under the lock of the ENV,
+// `action` is one of 'stop', 'kill', 'restart' and 'start';
+// failures are reported and recorded, they do not stop the monkey
+fn monkey_exec(en: &EnvName, id: NodeID, action: &str, force: bool) {
+    let operation = format!("chaos_monkey_{action}");
+    let stop = Op::<CustomInfo, Ports, ExtraOp>::Stop {
+        nodes: Some([id].into()),
+        force,
+    };
+    let start = Op::<CustomInfo, Ports, ExtraOp>::Start {
+        nodes: Some([id].into()),
+        ignore_failed: false,
+    };
+    let ops = match action {
+        "start" => vec![start],
+        "restart" => vec![stop, start],
+        _ => vec![stop],
+    };
+
+    // Always wait, like the supervisor
+    let res = EnvLock::acquire(&lock_path("dev", en), &operation, true)
+        .c(d!())
+        .and_then(|lock| {
+            let res = ops.into_iter().try_for_each(|op| {
+                SysCfg {
+                    name: en.clone(),
+                    op,
+                }
+                .exec(CmdGenerator)
+                .c(d!())
+            });
+            let ev = OpEvent::new(en, &operation, Some([id].into()), &res);
+            match load_sysenv(en) {
+                Ok(env) => {
+                    if let Err(e) = audit::append(&env.meta.home, &ev) {
+                        eprintln!("Audit log: {}", e.get_lowest_msg());
+                    }
+                    let hooks = &env.meta.custom_data.hooks;
+                    if !hooks.is_empty() {
+                        hooks.fire(&ev);
+                    }
+                }
+                Err(e) => eprintln!("Audit log: {}", e.get_lowest_msg()),
+            }
+            drop(lock);
+            res
+        });
+
+    match res {
+        Ok(_) => println!("[{}] node {id}: {action}", datetime!()),
+        Err(e) => println!(
+            "[{}] node {id}: {action}, failed: {}",
+            datetime!(),
+            e.get_lowest_msg()
+        ),
+    }
+}
+
+fn env_partition(
+    env: &SysEnv<CustomInfo, Ports, CmdGenerator>,
+    groups: &str,
+) -> Result<Partition> {
+    let ip = &env.meta.host_ip;
+    let groups = chaos::parse_groups(groups)
+        .c(d!())?
+        .iter()
+        .map(|sel| {
+            env_select_nodes(env, sel, true).c(d!()).map(|ids| {
+                env.meta
+                    .fuhrers
+                    .values()
+                    .chain(env.meta.nodes.values())
+                    .filter(|n| ids.contains(&n.id))
+                    .map(|n| P2pNode::new(n.id, ip, vec![ip.clone()], &n.home, &n.ports))
+                    .collect()
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Partition::new(groups).c(d!())
+}
+
+// Rebuild the shaping of the host from the impairments in the ENV meta
+fn env_apply_netem(env: &SysEnv<CustomInfo, Ports, CmdGenerator>) -> Result<()> {
+    let ip = &env.meta.host_ip;
+    let nodes = env
+        .meta
+        .fuhrers
+        .values()
+        .chain(env.meta.nodes.values())
+        .filter_map(|n| {
+            env.meta.custom_data.netem.get(&n.id).map(|netem| {
+                let p2p = P2pNode::new(n.id, ip, vec![ip.clone()], &n.home, &n.ports);
+                (p2p, netem.clone())
+            })
+        })
+        .collect::<Vec<_>>();
+    chaos::cmd_for_netem(ip, &nodes)
+        .c(d!())
+        .and_then(|cmd| cmd::exec_output(&cmd).c(d!()))
+        .map(|_| ())
+}
+
+fn node_facts(
+    env: &SysEnv<CustomInfo, Ports, CmdGenerator>,
+    n: &Node<Ports>,
+) -> Result<NodeFacts> {
+    Ok(NodeFacts {
+        id: n.id,
+        hosts: vec![env.meta.host_ip.clone()],
+        el: json_el_kind(&n.custom_data).c(d!())?,
+        kind: n.kind.to_string(),
+        fuhrer: env.meta.fuhrers.contains_key(&n.id),
+        labels: json_labels(&n.custom_data).c(d!())?,
+        validators: json_deposits_cnt(&n.custom_data).c(d!())?,
+    })
+}
+
+// Append to the 'mgmt.log' of the node
+fn node_mgmt_log(n: &Node<Ports>, log: &str) -> Result<()> {
+    fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(format!("{}/{MGMT_LOG_NAME}", n.home))
+        .c(d!())
+        .and_then(|mut f| f.write_all(log.as_bytes()).c(d!()))
+}
+
+// The last lines of the captured stdout/stderr of each process
+fn node_err_tails(n: &Node<Ports>) -> Result<BTreeMap<String, Vec<String>>> {
+    let cmd = process::cmd_tails(&n.home, &ERR_FILES, ERR_TAIL_LINES);
+    cmd::exec_output(&cmd)
+        .c(d!(&cmd))
+        .map(|out| process::parse_tails(&out))
+}
+
+fn env_failed_nodes(
+    env: &SysEnv<CustomInfo, Ports, CmdGenerator>,
+) -> Result<BTreeSet<NodeID>> {
+    let mut failed = BTreeSet::new();
+    for n in env.meta.nodes.values().chain(env.meta.fuhrers.values()) {
+        if 3 > node_process_cnt(env, n).c(d!())? {
+            failed.insert(n.id);
+        }
+    }
+    Ok(failed)
+}
+
+// <node ID, location>, see `snapshot::Manifest`
+fn node_locations(
+    env: &SysEnv<CustomInfo, Ports, CmdGenerator>,
+) -> BTreeMap<NodeID, String> {
+    env.meta
+        .fuhrers
+        .values()
+        .chain(env.meta.nodes.values())
+        .map(|n| (n.id, n.home.clone()))
+        .collect()
+}
+
+fn online_nodes(env: &SysEnv<CustomInfo, Ports, CmdGenerator>) -> BTreeSet<NodeID> {
+    env.meta
+        .fuhrers
+        .keys()
+        .chain(env.meta.nodes.keys())
+        .filter(|id| env.meta.nodes_should_be_online.contains_key(id))
+        .copied()
+        .collect()
+}
+
+fn stop_all_nodes(en: &EnvName) -> Result<()> {
+    SysCfg {
+        name: en.clone(),
+        op: Op::<CustomInfo, Ports, ExtraOp>::Stop {
+            nodes: None,
+            force: false,
+        },
+    }
+    .exec(CmdGenerator)
+    .c(d!())
+}
+
+fn start_nodes(en: &EnvName, nodes: &BTreeSet<NodeID>) -> Result<()> {
+    if nodes.is_empty() {
+        return Ok(());
+    }
+    SysCfg {
+        name: en.clone(),
+        op: Op::<CustomInfo, Ports, ExtraOp>::Start {
+            nodes: Some(nodes.clone()),
+            ignore_failed: false,
+        },
+    }
+    .exec(CmdGenerator)
+    .c(d!())
+}
+
+// The ENV meta in the snapshot, migrated to the current schema
+// Only the parts that go with the node data are taken from the snapshot,
+// i.e. the nodes(except their labels and notes) and the online set,
+// the rest of the meta, e.g. the hooks and the chaos records, is kept as it is
+fn snapshot_restore_meta(
+    en: &EnvName,
+    snap_env: SysEnv<CustomInfo, Ports, CmdGenerator>,
+) -> Result<()> {
+    let mut env = load_sysenv(en).c(d!())?;
+    let mut snap = snap_env.meta;
+    for n in snap.fuhrers.values_mut().chain(snap.nodes.values_mut()) {
+        if let Some(cur) = env
+            .meta
+            .fuhrers
+            .get(&n.id)
+            .or_else(|| env.meta.nodes.get(&n.id))
+        {
+            json_labels_copy(&mut n.custom_data, &cur.custom_data).c(d!())?;
+        }
+    }
+    env.meta.fuhrers = snap.fuhrers;
+    env.meta.nodes = snap.nodes;
+    env.meta.nodes_should_be_online = snap.nodes_should_be_online;
+    env.write_cfg().c(d!())
+}
+
+fn snapshot_env(
+    env_home: &str,
+    name: &str,
+) -> Result<SysEnv<CustomInfo, Ports, CmdGenerator>> {
+    let p = format!("{}/CONFIG", snapshot::snapshot_dir(env_home, name));
+    let mut cfg = fs::read(&p)
+        .c(d!(p))
+        .and_then(|c| serde_json::from_slice(&c).c(d!()))?;
+    schema::migrate(&mut cfg).c(d!())?;
+    serde_json::from_value(cfg).c(d!())
+}
+
+// The enodes and ENRs of the dedicated bootnodes, and a checkpoint sync URL,
+// `None` if there are no bootnodes in the ENV
+fn dedicated_bootnodes(
+    e: &EnvMeta<CustomInfo, Node<Ports>>,
+    id: NodeID,
+) -> Option<(String, String, String)> {
+    let mut enodes = vec![];
+    let mut enrs = vec![];
+    for o in e.fuhrers.values().chain(e.nodes.values()) {
+        let Some(b) = pnk!(json_bootnode(&o.custom_data)) else {
+            continue;
+        };
+        if o.id == id {
+            continue;
+        }
+        match b.peer(o.id, &e.host_ip, o.ports.el_discovery, o.ports.cl_discovery) {
+            Ok((enode, enr)) => {
+                enodes.push(enode);
+                enrs.push(enr);
+            }
+            Err(err) => info_omit!(Err::<(), _>(err)),
+        }
+    }
+    if enodes.is_empty() {
+        return None;
+    }
+
+    // A deterministic choice, the first online regular node
+    let url = e
+        .nodes_should_be_online
+        .iter()
+        .map(|(k, _)| k)
+        .filter(|k| *k != id)
+        .filter_map(|k| e.fuhrers.get(&k).or_else(|| e.nodes.get(&k)))
+        .find(|o| matches!(json_bootnode(&o.custom_data), Ok(None)))
+        .map(|o| format!("http://{}:{}", &e.host_ip, o.ports.cl_bn_rpc))
+        .unwrap_or_default();
+
+    Some((enodes.join(","), enrs.join(","), url))
+}
+
+// Record the enodes and ENRs of the dedicated bootnodes, for reference
+fn record_bootnodes(en: &EnvName) -> Result<()> {
+    let mut env = load_sysenv(en).c(d!())?;
+    let mut changed = false;
+    for n in env.meta.nodes.values_mut() {
+        let Some(mut b) = json_bootnode(&n.custom_data).c(d!())? else {
+            continue;
+        };
+        let (enode, enr) = b
+            .peer(
+                n.id,
+                &env.meta.host_ip,
+                n.ports.el_discovery,
+                n.ports.cl_discovery,
+            )
+            .c(d!())?;
+        if b.enode != enode || b.enr != enr {
+            b.enode = enode;
+            b.enr = enr;
+            json_bootnode_set(&mut n.custom_data, b).c(d!())?;
+            changed = true;
+        }
+    }
+    alt!(changed, env.write_cfg().c(d!()), Ok(()))
+}
+
+fn record_genesis_deposits(en: &EnvName) -> Result<()> {
+    let mut env = load_sysenv(en).c(d!())?;
+    let fuhrer = env
+        .meta
+        .fuhrers
+        .values_mut()
+        .next()
+        .c(d!("no fuhrer node"))?;
+    let map = map! {B
+        env.meta.genesis_mnemonic_words.clone() => (0..env.meta.genesis_validator_num).collect()
+    };
+    json_deposits_append(&mut fuhrer.custom_data, map)
+        .c(d!())
+        .and_then(|_| env.write_cfg().c(d!()))
+}
+
+// `clone`, re-create the shape of `src_name` as `dst_name`,
+// the node pairs of <source node ID, new node ID> are returned
+fn env_clone_shape(
+    src: &SysEnv<CustomInfo, Ports, CmdGenerator>,
+    dst_name: &EnvName,
+    with_data: bool,
+) -> Result<Vec<(NodeID, NodeID)>> {
+    let exec = |op: Op<CustomInfo, Ports, ExtraOp>| {
+        SysCfg {
+            name: dst_name.clone(),
+            op,
+        }
+        .exec(CmdGenerator)
+        .c(d!())
+    };
+
+    // The same genesis is needed by the same chain state,
+    // and by the same external network
+    let tmp = format!("/tmp/exp_clone_{}_{}", dst_name, ts!());
+    let reuse_genesis = with_data || src.meta.custom_data.external.is_some();
+    let (genesis_tgz_path, genesis_vkeys_tgz_path) = if reuse_genesis {
+        let genesis = format!("{tmp}/genesis.tar.gz");
+        let vkeys = format!("{tmp}/vcdata.tar.gz");
+        fs::create_dir_all(&tmp)
+            .c(d!())
+            .and_then(|_| fs::write(&genesis, &src.meta.genesis).c(d!()))
+            .and_then(|_| fs::write(&vkeys, &src.meta.genesis_vkeys).c(d!()))?;
+        (Some(genesis), Some(vkeys))
+    } else {
+        (None, None)
+    };
+
+    let created = exec(Op::Create {
+        opts: SysOpts {
+            host_ip: src.meta.host_ip.clone(),
+            block_itv: src.meta.block_itv,
+            genesis_pre_settings: src.meta.genesis_pre_settings.clone(),
+            genesis_tgz_path,
+            genesis_vkeys_tgz_path,
+            custom_data: CustomInfo {
+                // Hooks are not a part of the network shape
+                hooks: Default::default(),
+                schema_version: SCHEMA_VERSION,
+                // The nodes of the new ENV have their own identities
+                identity_seed: p2p::new_seed(),
+                // Faults are not a part of the network shape either
+                netem: Default::default(),
+                ..src.meta.custom_data.clone()
+            },
+            force_create: false,
+        },
+    });
+    omit!(fs::remove_dir_all(&tmp));
+    created.and_then(|_| record_genesis_deposits(dst_name).c(d!()))?;
+
+    let dst = load_sysenv(dst_name).c(d!())?;
+    let mut pairs = src
+        .meta
+        .fuhrers
+        .keys()
+        .zip(dst.meta.fuhrers.keys())
+        .map(|(s, d)| (*s, *d))
+        .collect::<Vec<_>>();
+
+    for n in src.meta.nodes.values() {
+        let custom_data = NodeCustomData {
+            el_kind: json_el_kind(&n.custom_data).c(d!())?,
+            labels: json_labels(&n.custom_data).c(d!())?,
+            notes: json_notes(&n.custom_data).c(d!())?,
+            // The same role with new keys
+            bootnode: json_bootnode(&n.custom_data).c(d!())?.map(|b| b.renew()),
+            ..Default::default()
+        };
+        exec(Op::PushNodes {
+            custom_data: custom_data.to_json_value(),
+            fullnode: matches!(n.kind, NodeKind::FullNode),
+            num: 1,
+        })?;
+        let id = load_sysenv(dst_name)
+            .c(d!())?
+            .meta
+            .nodes
+            .keys()
+            .max()
+            .copied()
+            .c(d!())?;
+        pairs.push((n.id, id));
+    }
+
+    Ok(pairs)
+}
+
+fn env_clone(src_name: &EnvName, dst_name: &EnvName, with_data: bool) -> Result<()> {
+    schema::ensure("dev", src_name, false).c(d!())?;
+    let _lock = EnvLock::acquire(&lock_path("dev", src_name), "clone", false).c(d!())?;
+    let src = load_sysenv(src_name).c(d!())?;
+
+    let pairs = env_clone_shape(&src, dst_name, with_data).c(d!())?;
+    for (s, d) in pairs.iter() {
+        println!("node {s} => node {d}");
+    }
+    if !with_data {
+        return Ok(());
+    }
+
+    let src_online = online_nodes(&src);
+    stop_all_nodes(dst_name).c(d!())?;
+
+    // A consistent copy needs the source nodes to be stopped
+    let copied = stop_all_nodes(src_name).c(d!()).and_then(|_| {
+        let mut dst = load_sysenv(dst_name).c(d!())?;
+        for (s, d) in pairs.iter() {
+            let sn = src
+                .meta
+                .fuhrers
+                .get(s)
+                .or_else(|| src.meta.nodes.get(s))
+                .c(d!())?;
+            let dn = if let Some(n) = dst.meta.fuhrers.get_mut(d) {
+                n
+            } else {
+                dst.meta.nodes.get_mut(d).c(d!())?
+            };
+            let cmd = snapshot::cmd_copy_as_clone(&sn.home, &dn.home);
+            cmd::exec_output(&cmd).c(d!(cmd))?;
+            // The deposits are a part of the chain state,
+            // bootnodes keep their new keys
+            if json_bootnode(&dn.custom_data).c(d!())?.is_none() {
+                dn.custom_data = sn.custom_data.clone();
+            }
+        }
+        dst.write_cfg().c(d!())
+    });
+
+    // The source nodes are started again even if the copy failed
+    let restarted = start_nodes(src_name, &src_online).c(d!());
+    copied.and(restarted)?;
+
+    start_nodes(dst_name, &pairs.iter().map(|(_, d)| *d).collect()).c(d!())
+}
+
 fn load_sysenv(en: &EnvName) -> Result<SysEnv<CustomInfo, Ports, CmdGenerator>> {
     SysEnv::load_env_by_name(en)
         .c(d!())?
         .c(d!("ENV does not exist!"))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cfg::{Cfg, Commands};
+    use clap::Parser;
+
+    fn env_cfg(args: &[&str]) -> EnvCfg {
+        let cfg = pnk!(Cfg::try_parse_from(["exp", "dev"].iter().chain(args)));
+        let Commands::Dev(cfg) = cfg.commands else {
+            panic!("not a dev command");
+        };
+        EnvCfg::try_from(cfg).ok().unwrap()
+    }
+
+    // The audit log and the hooks are only touched by the mutating operations
+    #[test]
+    fn hook_list_is_read_only() {
+        assert_eq!(None, env_cfg(&["hook", "list"]).operation());
+        assert_eq!(None, env_cfg(&["history"]).operation());
+        assert_eq!(
+            Some("hook"),
+            env_cfg(&["hook", "add", "-w", "http://127.0.0.1:1"]).operation()
+        );
+        assert_eq!(
+            Some("hook"),
+            env_cfg(&["hook", "remove", "-s", "/bin/true"]).operation()
+        );
+    }
+}
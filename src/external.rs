@@ -0,0 +1,256 @@
+//!
+//! ENVs attached to an external network, i.e. a network operated by others.
+//!
+//! Such an ENV is created from the network config(the genesis dir) of
+//! the external network instead of a new genesis, its fuhrer nodes have
+//! no validators, and all its nodes peer with the external bootnodes.
+//!
+
+use crate::pos::create_mnemonic_words;
+use chaindev::beacon_based::common::{
+    NODE_HOME_GENESIS_DIR_DST, NODE_HOME_GENESIS_DST, NODE_HOME_VCDATA_DST,
+};
+use ruc::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::{fs, path::Path};
+
+/// The files that must be in the genesis dir of an external network
+pub const EXTERNAL_GENESIS_FILES: [&str; 2] = ["genesis.json", "config.yaml"];
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ExternalNetwork {
+    /// Where the network config is imported from
+    pub genesis_dir: String,
+    /// The EL enodes of the external network
+    pub el_bootnodes: Vec<String>,
+    /// The CL ENRs of the external network
+    pub cl_bootnodes: Vec<String>,
+    /// Used instead of the nodes of the ENV if set
+    pub checkpoint_sync_url: Option<String>,
+}
+
+impl ExternalNetwork {
+    /// `el_bootnodes` and `cl_bootnodes` are comma separated lists,
+    /// or files with one item per line, e.g. the ones of a join kit
+    pub fn new(
+        genesis_dir: &str,
+        el_bootnodes: Option<&str>,
+        cl_bootnodes: Option<&str>,
+        checkpoint_sync_url: Option<String>,
+    ) -> Result<Self> {
+        let genesis_dir = fs::canonicalize(genesis_dir)
+            .c(d!("'{}' is not accessible", genesis_dir))?
+            .to_string_lossy()
+            .into_owned();
+        for f in EXTERNAL_GENESIS_FILES {
+            if !Path::new(&format!("{genesis_dir}/{f}")).is_file() {
+                return Err(eg!("'{}' is not found in '{}'", f, genesis_dir));
+            }
+        }
+
+        let el_bootnodes = parse_list(el_bootnodes.unwrap_or_default()).c(d!())?;
+        let cl_bootnodes = parse_list(cl_bootnodes.unwrap_or_default()).c(d!())?;
+        if el_bootnodes.is_empty() || cl_bootnodes.is_empty() {
+            return Err(eg!(
+                "Both the EL bootnodes and the CL ENRs of the external network are needed"
+            ));
+        }
+
+        Ok(Self {
+            genesis_dir,
+            el_bootnodes,
+            cl_bootnodes,
+            checkpoint_sync_url,
+        })
+    }
+
+    /// Add the peers of the external network to the ones in the ENV,
+    /// `(EL bootnodes, CL bootnodes, checkpoint sync URL)`
+    pub fn merge_peers(
+        &self,
+        el_bootnodes: String,
+        cl_bootnodes: String,
+        checkpoint_sync_url: String,
+    ) -> (String, String, String) {
+        let merge = |internal: String, external: &[String]| {
+            (!internal.is_empty())
+                .then_some(internal)
+                .into_iter()
+                .chain(external.iter().cloned())
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        (
+            merge(el_bootnodes, &self.el_bootnodes),
+            merge(cl_bootnodes, &self.cl_bootnodes),
+            self.checkpoint_sync_url
+                .clone()
+                .unwrap_or(checkpoint_sync_url),
+        )
+    }
+
+    /// Pack the network config as the genesis data of chaindev,
+    /// return the paths of `(genesis.tar.gz, vcdata.tar.gz)` in `tmp`
+    pub fn pack_genesis(&self, tmp: &str) -> Result<(String, String)> {
+        let dir = format!("{tmp}/{NODE_HOME_GENESIS_DIR_DST}");
+        fs::create_dir_all(tmp).c(d!())?;
+        let cmd = format!("cp -r {} {dir}", self.genesis_dir);
+        cmd::exec_output(&cmd).c(d!(cmd))?;
+
+        let p = format!("{dir}/genesis.json");
+        let mut genesis = fs::read(&p)
+            .c(d!(p))
+            .and_then(|g| serde_json::from_slice::<JsonValue>(&g).c(d!(p)))?;
+        if normalize_balances(&mut genesis).c(d!())? {
+            serde_json::to_vec_pretty(&genesis)
+                .c(d!())
+                .and_then(|g| fs::write(&p, g).c(d!()))?;
+        }
+
+        // Required by chaindev, no validators are derived from it
+        fs::write(
+            format!("{dir}/mnemonics.yaml"),
+            format!("- mnemonic: \"{}\"\n  count: 0\n", create_mnemonic_words()),
+        )
+        .c(d!())?;
+        let chainspec = format!("{dir}/chainspec.json");
+        if !Path::new(&chainspec).exists() {
+            fs::write(&chainspec, "{}").c(d!())?;
+        }
+
+        // An empty set of validators for the fuhrer nodes
+        fs::create_dir_all(format!("{tmp}/vcdata/validators")).c(d!())?;
+
+        let genesis_tgz = format!("{tmp}/{NODE_HOME_GENESIS_DST}");
+        let vcdata_tgz = format!("{tmp}/{NODE_HOME_VCDATA_DST}");
+        let cmd = format!(
+            "tar -C {tmp} -zcf {genesis_tgz} {NODE_HOME_GENESIS_DIR_DST} || exit 1; \
+            tar -C {tmp} -zcf {vcdata_tgz} vcdata || exit 1"
+        );
+        cmd::exec_output(&cmd).c(d!(cmd))?;
+
+        Ok((genesis_tgz, vcdata_tgz))
+    }
+}
+
+/// A comma separated list, or a file with one item per line
+fn parse_list(v: &str) -> Result<Vec<String>> {
+    let v = v.trim();
+    let items = if Path::new(v).is_file() {
+        fs::read_to_string(v).c(d!(v))?
+    } else {
+        v.replace(',', "\n")
+    };
+    Ok(items
+        .lines()
+        .map(|i| i.trim())
+        .filter(|i| !i.is_empty())
+        .map(|i| i.to_owned())
+        .collect())
+}
+
+// chaindev parses the `alloc` balances as decimal strings,
+// return `true` if any of them is changed
+fn normalize_balances(genesis: &mut JsonValue) -> Result<bool> {
+    let Some(alloc) = genesis["alloc"].as_object_mut() else {
+        return Ok(false);
+    };
+
+    let mut changed = false;
+    for (addr, account) in alloc.iter_mut() {
+        let balance = &mut account["balance"];
+        let decimal = match balance {
+            JsonValue::String(s) if s.parse::<u128>().is_ok() => continue,
+            JsonValue::String(s) => {
+                let hex = s.trim_start_matches("0x").trim_start_matches("0X");
+                u128::from_str_radix(hex, 16).c(d!(
+                    "invalid balance of {}: {}",
+                    addr,
+                    s
+                ))?
+            }
+            JsonValue::Number(n) => {
+                n.as_u64().c(d!("invalid balance of {}", addr))?.into()
+            }
+            JsonValue::Null => 0,
+            _ => return Err(eg!("invalid balance of {}", addr)),
+        };
+        *balance = decimal.to_string().into();
+        changed = true;
+    }
+
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn external_network() {
+        let base = format!("/tmp/exp_external_test_{}", std::process::id());
+        let genesis_dir = format!("{base}/genesis");
+        pnk!(fs::create_dir_all(&genesis_dir));
+        let genesis = json!({
+            "config": { "chainId": 7 },
+            "alloc": {
+                "0x01": { "balance": "0x10" },
+                "0x02": { "balance": "100" }
+            }
+        });
+        pnk!(fs::write(
+            format!("{genesis_dir}/genesis.json"),
+            genesis.to_string()
+        ));
+
+        assert!(ExternalNetwork::new(
+            &genesis_dir,
+            Some("enode://a"),
+            Some("enr:-a"),
+            None
+        )
+        .is_err());
+        pnk!(fs::write(
+            format!("{genesis_dir}/config.yaml"),
+            "PRESET_BASE: minimal\n"
+        ));
+        assert!(ExternalNetwork::new(&genesis_dir, None, Some("enr:-a"), None).is_err());
+
+        let enrs = format!("{base}/enrs.txt");
+        pnk!(fs::write(&enrs, "enr:-a\n\nenr:-b\n"));
+        let ext = pnk!(ExternalNetwork::new(
+            &genesis_dir,
+            Some("enode://a,enode://b"),
+            Some(&enrs),
+            None
+        ));
+        assert_eq!(2, ext.el_bootnodes.len());
+        assert_eq!(vec!["enr:-a", "enr:-b"], ext.cl_bootnodes);
+
+        let (el, cl, url) = ext.merge_peers(
+            "enode://x".to_owned(),
+            String::new(),
+            "http://x".to_owned(),
+        );
+        assert_eq!("enode://x,enode://a,enode://b", el);
+        assert_eq!("enr:-a,enr:-b", cl);
+        assert_eq!("http://x", url);
+
+        let tmp = format!("{base}/tmp");
+        let (genesis_tgz, vcdata_tgz) = pnk!(ext.pack_genesis(&tmp));
+        let list = pnk!(cmd::exec_output(&format!("tar -tf {genesis_tgz}")));
+        assert!(list.contains("genesis/mnemonics.yaml"));
+        assert!(list.contains("genesis/chainspec.json"));
+        let list = pnk!(cmd::exec_output(&format!("tar -tf {vcdata_tgz}")));
+        assert!(list.contains("vcdata/validators"));
+
+        let packed = pnk!(fs::read(format!("{tmp}/genesis/genesis.json")));
+        let packed = pnk!(serde_json::from_slice::<JsonValue>(&packed));
+        assert_eq!("16", packed["alloc"]["0x01"]["balance"]);
+        assert_eq!("100", packed["alloc"]["0x02"]["balance"]);
+
+        pnk!(fs::remove_dir_all(&base));
+    }
+}
@@ -0,0 +1,191 @@
+//!
+//! Notification hooks of the ENV events,
+//! they are fired after every mutating operation.
+//!
+
+use chaindev::{EnvName, NodeID};
+use ruc::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    env, fs,
+    io::Write,
+    process::{Command, Stdio},
+};
+
+/// Stored in the ENV meta, so it is shared with the ENV(e.g., by `git`)
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hooks {
+    /// Each event is POSTed to these URLs as a JSON document
+    pub webhooks: BTreeSet<String>,
+    /// Local executables, each event is passed by the stdin
+    /// and by the `EXP_EVENT` environment variable
+    pub scripts: BTreeSet<String>,
+}
+
+impl Hooks {
+    pub fn is_empty(&self) -> bool {
+        self.webhooks.is_empty() && self.scripts.is_empty()
+    }
+
+    /// Failures are reported to the stderr,
+    /// they never change the result of the operation itself
+    pub fn fire(&self, ev: &OpEvent) {
+        let payload = pnk!(serde_json::to_string(ev));
+
+        for url in self.webhooks.iter() {
+            let res = ruc::http::post(
+                url,
+                payload.as_bytes(),
+                Some(&[("Content-Type", "application/json")]),
+            )
+            .c(d!())
+            .and_then(|(code, _)| {
+                alt!(
+                    code.is_success(),
+                    Ok(()),
+                    Err(eg!("responded with code {}", code))
+                )
+            });
+            if let Err(e) = res {
+                eprintln!("Hook '{url}': {}", e.get_lowest_msg());
+            }
+        }
+
+        for script in self.scripts.iter() {
+            if let Err(e) = run_script(script, &payload) {
+                eprintln!("Hook '{script}': {}", e.get_lowest_msg());
+            }
+        }
+    }
+}
+
+fn run_script(script: &str, payload: &str) -> Result<()> {
+    let mut child = Command::new(script)
+        .env("EXP_EVENT", payload)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .c(d!())?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // The script may not read its stdin at all
+        let _ = stdin.write_all(payload.as_bytes());
+    }
+    let status = child.wait().c(d!())?;
+    alt!(status.success(), Ok(()), Err(eg!("exited with {}", status)))
+}
+
+/// The payload of the hooks
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OpEvent {
+    pub env: String,
+    /// `$EXP_OPERATOR`, or `<user>@<hostname>` if not set
+    pub operator: String,
+    /// The subcommand in the snake case, e.g., 'kick_nodes'
+    pub operation: String,
+    /// The cmdline arguments of `exp`
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// `None` if the operation is not node specific,
+    /// or the nodes can not be determined, e.g., `start` on all nodes
+    pub nodes: Option<BTreeSet<NodeID>>,
+    pub timestamp: u64,
+    pub ok: bool,
+    /// The error message if failed
+    pub error: Option<String>,
+}
+
+impl OpEvent {
+    pub fn new(
+        env: &EnvName,
+        operation: &str,
+        nodes: Option<BTreeSet<NodeID>>,
+        res: &Result<()>,
+    ) -> Self {
+        Self {
+            env: env.to_string(),
+            operator: operator(),
+            operation: operation.to_owned(),
+            args: env::args().skip(1).collect(),
+            nodes,
+            timestamp: ts!(),
+            ok: res.is_ok(),
+            error: res.as_ref().err().map(|e| e.get_lowest_msg().to_string()),
+        }
+    }
+}
+
+pub fn operator() -> String {
+    if let Ok(op) = env::var("EXP_OPERATOR") {
+        return op;
+    }
+    let user = env::var("USER").unwrap_or_else(|_| "unknown".to_owned());
+    let host = fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .or_else(|| env::var("HOSTNAME").ok())
+        .unwrap_or_else(|| "unknown".to_owned());
+    format!("{user}@{}", host.trim())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{io::Read, net::TcpListener, thread};
+
+    #[test]
+    fn fire_hooks() {
+        let listener = pnk!(TcpListener::bind("127.0.0.1:0"));
+        let addr = pnk!(listener.local_addr());
+        let server = thread::spawn(move || {
+            let (mut stream, _) = pnk!(listener.accept());
+            let mut req = vec![];
+            let mut buf = [0; 4096];
+            // Read until the whole JSON body has arrived
+            while !String::from_utf8_lossy(&req).ends_with('}') {
+                let n = pnk!(stream.read(&mut buf));
+                assert!(0 < n);
+                req.extend_from_slice(&buf[..n]);
+            }
+            pnk!(stream.write_all(
+                b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            ));
+            String::from_utf8_lossy(&req).into_owned()
+        });
+
+        let dir = format!("/tmp/exp_hooks_test_{}", std::process::id());
+        let script = format!("{dir}/hook.sh");
+        pnk!(fs::create_dir_all(&dir));
+        pnk!(fs::write(
+            &script,
+            format!("#!/usr/bin/env bash\ncat >{dir}/event.json\n")
+        ));
+        pnk!(ruc::cmd::exec_output(&format!("chmod +x {script}")));
+
+        let hooks = Hooks {
+            webhooks: [format!("http://{addr}/hook")].into(),
+            scripts: [script].into(),
+        };
+        let ev = OpEvent::new(
+            &"MyEnv".into(),
+            "kick_nodes",
+            Some([2, 3].into()),
+            &Err(eg!("boom")),
+        );
+        hooks.fire(&ev);
+
+        let req = server.join().unwrap();
+        assert!(req.starts_with("POST /hook "), "{req}");
+        let body = &req[pnk!(req.find("\r\n\r\n")) + 4..];
+        let v = pnk!(serde_json::from_str::<serde_json::Value>(body));
+        assert_eq!("kick_nodes", v["operation"]);
+        assert_eq!(serde_json::json!([2, 3]), v["nodes"]);
+        assert_eq!(false, v["ok"]);
+        assert_eq!("boom", v["error"]);
+
+        let ev = pnk!(fs::read_to_string(format!("{dir}/event.json")));
+        let v = pnk!(serde_json::from_str::<serde_json::Value>(&ev));
+        assert_eq!("MyEnv", v["env"]);
+
+        pnk!(fs::remove_dir_all(&dir));
+    }
+}
@@ -0,0 +1,197 @@
+//!
+//! `exp dev/ddev export-join-kit`, everything an external participant
+//! needs to run a node of the ENV by themselves.
+//!
+//! Layout of a join kit:
+//! - `genesis/`, the genesis dir, i.e. the `genesis.json` of the EL and the `--testnet-dir` of the CL
+//! - `bootnodes.txt`, the EL enodes of the online nodes, one per line
+//! - `enrs.txt`, the CL ENRs of the online nodes, one per line
+//! - `chain_id.txt`
+//! - `deposit_contract.txt`, read from the `config.yaml` of the genesis
+//! - `run.sh`, a commented script that runs geth or reth along with lighthouse
+//!
+
+use ruc::*;
+use std::{fs, os::unix::fs::PermissionsExt, path::Path};
+
+pub const RUN_SCRIPT_NAME: &str = "run.sh";
+
+#[derive(Clone, Debug)]
+pub struct JoinKit {
+    pub env_name: String,
+    pub chain_id: u64,
+    pub deposit_contract: String,
+    pub enodes: Vec<String>,
+    pub enrs: Vec<String>,
+}
+
+impl JoinKit {
+    /// Write the kit into `dir`, which must not exist or be empty
+    pub fn write(&self, genesis_dir: &str, dir: &str) -> Result<()> {
+        let d = Path::new(dir);
+        if d.exists() && fs::read_dir(d).c(d!())?.next().is_some() {
+            return Err(eg!("'{}' already exists and is not empty", dir));
+        }
+        fs::create_dir_all(d).c(d!())?;
+
+        let cmd = format!("cp -r {genesis_dir} {dir}/genesis");
+        cmd::exec_output(&cmd).c(d!(cmd))?;
+
+        let lines = |items: &[String]| items.join("\n") + "\n";
+        for (name, contents) in [
+            ("bootnodes.txt", lines(&self.enodes)),
+            ("enrs.txt", lines(&self.enrs)),
+            ("chain_id.txt", format!("{}\n", self.chain_id)),
+            (
+                "deposit_contract.txt",
+                format!("{}\n", self.deposit_contract),
+            ),
+            (RUN_SCRIPT_NAME, self.run_script()),
+        ] {
+            fs::write(d.join(name), contents).c(d!(name))?;
+        }
+
+        let script = d.join(RUN_SCRIPT_NAME);
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).c(d!())
+    }
+
+    fn run_script(&self) -> String {
+        let env_name = &self.env_name;
+        let chain_id = self.chain_id;
+        let deposit_contract = &self.deposit_contract;
+
+        format!(
+            r#"#!/usr/bin/env bash
+
+#
+# Run a node that joins the network of the ENV '{env_name}'.
+#
+# - Chain ID: {chain_id}
+# - Deposit contract: {deposit_contract}
+#
+# Requirements:
+# - `geth` or `reth`, and `lighthouse` in $PATH
+# - the p2p ports(30303 of the EL, 9000 of the CL) are reachable by other nodes
+#
+# Usage:
+#     EXT_IP=<the public IP of this host> EL=geth ./run.sh
+#
+# Optional variables:
+# - EL, 'geth' or 'reth', default to 'geth'
+# - DATA_DIR, default to the 'data/' dir beside this script
+#
+# To become a validator, send deposits to the deposit contract,
+# then run `lighthouse validator_client` with the validator keys,
+# and with `--testnet-dir=<this kit>/genesis --beacon-nodes=http://127.0.0.1:5052`.
+#
+
+KIT_DIR=$(cd $(dirname $0) && pwd)
+GENESIS_DIR=${{KIT_DIR}}/genesis
+DATA_DIR=${{DATA_DIR:-${{KIT_DIR}}/data}}
+EL=${{EL:-geth}}
+EXT_IP=${{EXT_IP:-127.0.0.1}}
+
+BOOTNODES=$(paste -sd, ${{KIT_DIR}}/bootnodes.txt)
+ENRS=$(paste -sd, ${{KIT_DIR}}/enrs.txt)
+JWT=${{DATA_DIR}}/jwt.hex
+
+mkdir -p ${{DATA_DIR}}/el ${{DATA_DIR}}/cl || exit 1
+if [ ! -f ${{JWT}} ]; then
+    head -c 32 /dev/urandom | od -An -tx1 | tr -d ' \n' >${{JWT}} || exit 1
+fi
+
+if [ "geth" == "${{EL}}" ]; then
+    if [ ! -d ${{DATA_DIR}}/el/geth ]; then
+        geth init --datadir=${{DATA_DIR}}/el --state.scheme=hash \
+            ${{GENESIS_DIR}}/genesis.json || exit 1
+    fi
+    nohup geth \
+        --networkid={chain_id} \
+        --syncmode=full \
+        --state.scheme=hash \
+        --datadir=${{DATA_DIR}}/el \
+        --nat=extip:${{EXT_IP}} \
+        --port=30303 \
+        --discovery.port=30303 \
+        --discovery.v5 \
+        --http --http.addr=127.0.0.1 --http.port=8545 \
+        --authrpc.addr=127.0.0.1 --authrpc.port=8551 \
+        --authrpc.jwtsecret=${{JWT}} \
+        --bootnodes=${{BOOTNODES}} \
+        >${{DATA_DIR}}/el.log 2>&1 &
+elif [ "reth" == "${{EL}}" ]; then
+    nohup reth node \
+        --chain=${{GENESIS_DIR}}/genesis.json \
+        --datadir=${{DATA_DIR}}/el \
+        --nat=extip:${{EXT_IP}} \
+        --port=30303 \
+        --discovery.port=30303 \
+        --http --http.addr=127.0.0.1 --http.port=8545 \
+        --authrpc.addr=127.0.0.1 --authrpc.port=8551 \
+        --authrpc.jwtsecret=${{JWT}} \
+        --bootnodes=${{BOOTNODES}} \
+        >${{DATA_DIR}}/el.log 2>&1 &
+else
+    echo "Unknown EL: '${{EL}}', should be 'geth' or 'reth'"
+    exit 1
+fi
+
+nohup lighthouse beacon_node \
+    --testnet-dir=${{GENESIS_DIR}} \
+    --datadir=${{DATA_DIR}}/cl \
+    --enr-address=${{EXT_IP}} \
+    --port=9000 \
+    --discovery-port=9000 \
+    --execution-endpoints=http://127.0.0.1:8551 \
+    --jwt-secrets=${{JWT}} \
+    --http --http-address=127.0.0.1 --http-port=5052 \
+    --boot-nodes=${{ENRS}} \
+    --allow-insecure-genesis-sync \
+    >${{DATA_DIR}}/cl.log 2>&1 &
+
+echo "The EL(${{EL}}) and the CL are started, logs: ${{DATA_DIR}}/el.log, ${{DATA_DIR}}/cl.log"
+"#
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn write_join_kit() {
+        let base = format!("/tmp/exp_join_kit_test_{}", std::process::id());
+        let genesis_dir = format!("{base}/genesis");
+        let dir = format!("{base}/kit");
+        pnk!(fs::create_dir_all(&genesis_dir));
+        pnk!(fs::write(format!("{genesis_dir}/genesis.json"), "{}"));
+
+        let kit = JoinKit {
+            env_name: "MyEnv".to_owned(),
+            chain_id: 12345,
+            deposit_contract: "0x4242424242424242424242424242424242424242".to_owned(),
+            enodes: vec![
+                "enode://a@1.1.1.1:30303".to_owned(),
+                "enode://b@2.2.2.2:30303".to_owned(),
+            ],
+            enrs: vec!["enr:-a".to_owned()],
+        };
+        pnk!(kit.write(&genesis_dir, &dir));
+        assert!(kit.write(&genesis_dir, &dir).is_err());
+
+        assert!(Path::new(&format!("{dir}/genesis/genesis.json")).exists());
+        let enodes = pnk!(fs::read_to_string(format!("{dir}/bootnodes.txt")));
+        assert_eq!(2, enodes.lines().count());
+        assert_eq!(
+            "12345",
+            pnk!(fs::read_to_string(format!("{dir}/chain_id.txt"))).trim()
+        );
+
+        let script = format!("{dir}/{RUN_SCRIPT_NAME}");
+        assert!(pnk!(fs::read_to_string(&script)).contains("--networkid=12345"));
+        pnk!(cmd::exec_output(&format!("bash -n {script}")));
+
+        pnk!(fs::remove_dir_all(&base));
+    }
+}
@@ -0,0 +1,129 @@
+//!
+//! Advisory locks of ENVs, against concurrent mutating operations,
+//! e.g., two `exp` processes that overwrite the ENV meta of each other.
+//!
+
+use chaindev::{beacon_based::common::BASE_DIR, EnvName};
+use ruc::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    fs::{self, File, TryLockError},
+    io::Write,
+    path::Path,
+    process,
+};
+
+/// The lock file of an ENV, `kind` is 'dev' or 'ddev'
+pub fn lock_path(kind: &str, en: &EnvName) -> String {
+    format!("{}/locks/{kind}/{en}.lock", &*BASE_DIR)
+}
+
+/// Who is holding the lock, recorded in the lock file
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LockHolder {
+    pub operator: String,
+    pub pid: u32,
+    pub operation: String,
+    pub since: u64,
+}
+
+impl fmt::Display for LockHolder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}(pid {}, {}) since {}",
+            self.operator,
+            self.pid,
+            self.operation,
+            datetime!(self.since)
+        )
+    }
+}
+
+/// Released on drop, or by the OS if the process exits in any way,
+/// so a crashed `exp` never leaves a stale lock behind.
+#[derive(Debug)]
+pub struct EnvLock {
+    _file: File,
+}
+
+impl EnvLock {
+    /// Fail at once if the lock is held by others, unless `wait` is set
+    pub fn acquire(path: &str, operation: &str, wait: bool) -> Result<Self> {
+        if let Some(dir) = Path::new(path).parent() {
+            fs::create_dir_all(dir).c(d!())?;
+        }
+
+        // Do NOT truncate here, the holder info may belong to others
+        let mut f = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .c(d!(path))?;
+
+        match f.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let holder = holder_info(path);
+                if !wait {
+                    return Err(eg!(
+                        "The ENV is locked by {}, retry later, or use `--wait`",
+                        holder
+                    ));
+                }
+                eprintln!("Waiting for the ENV lock held by {holder} ...");
+                f.lock().c(d!(path))?;
+            }
+            Err(TryLockError::Error(e)) => return Err(e).c(d!(path)),
+        }
+
+        let holder = LockHolder {
+            operator: crate::hooks::operator(),
+            pid: process::id(),
+            operation: operation.to_owned(),
+            since: ts!(),
+        };
+        f.set_len(0)
+            .c(d!())
+            .and_then(|_| serde_json::to_vec(&holder).c(d!()))
+            .and_then(|h| f.write_all(&h).c(d!()))?;
+
+        Ok(Self { _file: f })
+    }
+}
+
+fn holder_info(path: &str) -> String {
+    fs::read(path)
+        .ok()
+        .and_then(|h| serde_json::from_slice::<LockHolder>(&h).ok())
+        .map(|h| h.to_string())
+        .unwrap_or_else(|| "another `exp` process".to_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn env_lock() {
+        let path = format!("/tmp/exp_lock_test_{}/MyEnv.lock", process::id());
+
+        let lock = pnk!(EnvLock::acquire(&path, "kick_nodes", false));
+        let e = EnvLock::acquire(&path, "deposit", false).unwrap_err();
+        let msg = e.get_lowest_msg().to_string();
+        assert!(
+            msg.contains(&format!("pid {}, kick_nodes", process::id())),
+            "{msg}"
+        );
+
+        drop(lock);
+        let lock = pnk!(EnvLock::acquire(&path, "deposit", false));
+        assert!(holder_info(&path).contains("deposit"));
+
+        drop(lock);
+        pnk!(fs::remove_dir_all(Path::new(&path).parent().unwrap()));
+    }
+}
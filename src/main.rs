@@ -1,7 +1,7 @@
 #![deny(warnings)]
 #![cfg_attr(test, warn(warnings))]
 
-use cfg::{Cfg, Commands};
+use cfg::{Cfg, Commands, EnvOp};
 use chaindev::beacon_based::common::BASE_DIR;
 use clap::{crate_name, CommandFactory, Parser};
 use clap_complete::{
@@ -11,11 +11,27 @@ use clap_complete::{
 use ruc::*;
 use std::{fs, io, process::ExitCode};
 
+mod audit;
+mod bootnode;
+mod bundle;
 mod cfg;
+mod chaos;
 mod common;
 mod ddev;
 mod dev;
+mod external;
+mod hooks;
+mod join_kit;
+mod lock;
+mod output;
+mod p2p;
 mod pos;
+mod process;
+mod schema;
+mod selector;
+mod snapshot;
+mod supervise;
+mod topology;
 
 fn main() -> ExitCode {
     let config = Cfg::parse();
@@ -43,17 +59,36 @@ fn main() -> ExitCode {
         ExitCode::FAILURE
     };
 
+    // Invalid cmdline arguments, no backtrace is needed
+    let cfg_err = |e: common::CfgError| {
+        eprintln!("\x1b[0;31mERROR\x1b[0m: {e}");
+        ExitCode::from(e.exit_code())
+    };
+
     match config.commands {
-        Commands::Dev(cfg) => {
-            if let Err(e) = dev::EnvCfg::from(cfg).exec() {
-                return err(e, "dev");
+        Commands::Dev(cfg) => match dev::EnvCfg::try_from(cfg) {
+            Ok(cfg) => {
+                if let Err(e) = cfg.exec() {
+                    return err(e, "dev");
+                }
             }
-        }
-        Commands::DDev(cfg) => {
-            if let Err(e) = ddev::EnvCfg::from(cfg).exec() {
-                return err(e, "d_dev");
+            Err(e) => return cfg_err(e),
+        },
+        Commands::DDev(cfg) => match ddev::EnvCfg::try_from(cfg) {
+            Ok(cfg) => {
+                if let Err(e) = cfg.exec() {
+                    return err(e, "d_dev");
+                }
             }
-        }
+            Err(e) => return cfg_err(e),
+        },
+        Commands::Env(cfg) => match cfg.op {
+            EnvOp::Migrate { check } => {
+                if let Err(e) = schema::migrate_all(check) {
+                    return err(e, "env");
+                }
+            }
+        },
         Commands::Deposit(cfg) => {
             let future = pos::deposit(
                 &cfg.rpc_endpoint,
@@ -0,0 +1,450 @@
+//!
+//! Output formats of the read-only operations,
+//! the schemas are documented in 'src/dev/README.md'.
+//!
+
+use crate::{
+    chaos::Netem,
+    hooks::OpEvent,
+    topology::{Layer, Topology},
+};
+use chaindev::{EnvName, NodeID};
+use clap::ValueEnum;
+use ruc::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Stable JSON documents, for scripts
+    Json,
+    /// Human-readable, may contain ANSI escape codes
+    Table,
+    /// Tab separated lines without headers or escape codes
+    Plain,
+}
+
+/// The common part of all per-node records
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NodeBrief {
+    pub id: NodeID,
+    pub kind: String,
+    pub el: String,
+    pub host: String,
+    pub labels: BTreeSet<String>,
+    pub notes: BTreeMap<String, String>,
+}
+
+impl NodeBrief {
+    const HEADER: [&str; 5] = ["ID", "KIND", "EL", "HOST", "LABELS"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            self.kind.clone(),
+            self.el.clone(),
+            self.host.clone(),
+            alt!(
+                self.labels.is_empty() && self.notes.is_empty(),
+                "-".to_owned(),
+                self.labels_str()
+            ),
+        ]
+    }
+
+    /// Labels and notes in the `canary,owner=alice` format
+    fn labels_str(&self) -> String {
+        self.labels
+            .iter()
+            .cloned()
+            .chain(self.notes.iter().map(|(k, v)| format!("{k}={v}")))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NodeRpcs {
+    #[serde(flatten)]
+    pub node: NodeBrief,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub el_web3: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub el_web3_ws: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub el_metric: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cl_bn: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cl_bn_metric: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cl_vc: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cl_vc_metric: Option<String>,
+}
+
+impl NodeRpcs {
+    /// <JSON field, title in the table format>
+    const CATEGORIES: [(&str, &str); 7] = [
+        ("el_web3", "EL WEB3 RPCs"),
+        ("el_web3_ws", "EL WEB3 WS RPCs"),
+        ("el_metric", "EL METRIC RPCs"),
+        ("cl_bn", "CL BEACON RPCs"),
+        ("cl_bn_metric", "CL BEACON METRIC RPCs"),
+        ("cl_vc", "CL VALIDATOR RPCs"),
+        ("cl_vc_metric", "CL VALIDATOR METRIC RPCs"),
+    ];
+
+    /// In the order of `CATEGORIES`
+    fn urls(&self) -> [&Option<String>; 7] {
+        [
+            &self.el_web3,
+            &self.el_web3_ws,
+            &self.el_metric,
+            &self.cl_bn,
+            &self.cl_bn_metric,
+            &self.cl_vc,
+            &self.cl_vc_metric,
+        ]
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FailedNode {
+    #[serde(flatten)]
+    pub node: NodeBrief,
+    /// <file name, last lines> of the captured stdout/stderr files
+    pub err_tails: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NodeSummary {
+    #[serde(flatten)]
+    pub node: NodeBrief,
+    pub validators: usize,
+    pub should_be_online: bool,
+    /// The active impairments, see `crate::chaos`
+    pub netem: Option<Netem>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeStatus {
+    /// Should be online, and all processes are alive
+    Running,
+    /// Should be offline, and no process is alive
+    Stopped,
+    /// Should be online, but some processes are missing
+    Failed,
+    /// Should be offline, but some processes are alive
+    Unexpected,
+}
+
+impl NodeStatus {
+    pub fn new(should_be_online: bool, process_cnt: u64) -> Self {
+        match (should_be_online, process_cnt) {
+            (true, 3..) => Self::Running,
+            (true, _) => Self::Failed,
+            (false, 0) => Self::Stopped,
+            (false, _) => Self::Unexpected,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NodeHealth {
+    #[serde(flatten)]
+    pub node: NodeBrief,
+    pub status: NodeStatus,
+    pub process_cnt: u64,
+    pub should_be_online: bool,
+    pub el_block_number: Option<u64>,
+    pub cl_head_slot: Option<u64>,
+    pub cl_is_syncing: Option<bool>,
+}
+
+pub fn print_json<T: Serialize>(v: &T) -> Result<()> {
+    serde_json::to_string_pretty(v)
+        .c(d!())
+        .map(|s| println!("{s}"))
+}
+
+/// Aligned columns for `table`, tab separated lines for `plain`
+pub fn print_rows(format: OutputFormat, header: &[&str], rows: &[Vec<String>]) {
+    if OutputFormat::Plain == format {
+        rows.iter().for_each(|r| println!("{}", r.join("\t")));
+        return;
+    }
+
+    let mut widths = header.iter().map(|h| h.len()).collect::<Vec<_>>();
+    for r in rows.iter() {
+        for (w, c) in widths.iter_mut().zip(r.iter()) {
+            *w = (*w).max(c.len());
+        }
+    }
+
+    let line = |cells: Vec<String>| {
+        cells
+            .iter()
+            .zip(widths.iter())
+            .map(|(c, w)| format!("{c:<w$}"))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_owned()
+    };
+
+    println!(
+        "\x1b[33;1m{}\x1b[0m",
+        line(header.iter().map(|h| h.to_string()).collect())
+    );
+    rows.iter().for_each(|r| println!("{}", line(r.clone())));
+}
+
+pub fn print_env_list(format: OutputFormat, list: &[EnvName]) -> Result<()> {
+    match format {
+        OutputFormat::Json => {
+            let list = list.iter().map(|en| en.to_string()).collect::<Vec<_>>();
+            print_json(&serde_json::json!({ "envs": list }))
+        }
+        OutputFormat::Table => {
+            if list.is_empty() {
+                println!("\x1b[31;01mNo existing env!\x1b[00m");
+            } else {
+                println!("\x1b[31;01mEnv list:\x1b[00m");
+                list.iter().for_each(|env| println!("  {env}"));
+            }
+            Ok(())
+        }
+        OutputFormat::Plain => {
+            list.iter().for_each(|env| println!("{env}"));
+            Ok(())
+        }
+    }
+}
+
+pub fn print_rpcs(format: OutputFormat, nodes: &[NodeRpcs]) -> Result<()> {
+    if OutputFormat::Json == format {
+        return print_json(&serde_json::json!({ "nodes": nodes }));
+    }
+
+    for (idx, (field, title)) in NodeRpcs::CATEGORIES.iter().enumerate() {
+        let urls = nodes
+            .iter()
+            .filter_map(|n| n.urls()[idx].as_ref().map(|url| (n, url)))
+            .collect::<Vec<_>>();
+        if urls.is_empty() {
+            continue;
+        }
+        if OutputFormat::Table == format {
+            println!("\x1b[33;1m{title}:\x1b[0m");
+            urls.iter().for_each(|(n, url)| {
+                let labels = n.node.labels_str();
+                if labels.is_empty() {
+                    println!("    {url}");
+                } else {
+                    println!("    {url}  [{labels}]");
+                }
+            });
+        } else {
+            urls.iter().for_each(|(n, url)| {
+                println!("{field}\t{}\t{url}\t{}", n.node.id, n.node.cells()[4]);
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// The `plain` format prints the rows only, without the error tails
+pub fn print_failed_nodes(
+    format: OutputFormat,
+    nodes: &[FailedNode],
+    errors: &[String],
+) -> Result<()> {
+    if OutputFormat::Json == format {
+        return print_json(&serde_json::json!({
+            "failed_nodes": nodes,
+            "errors": errors,
+        }));
+    }
+
+    let rows = nodes.iter().map(|n| n.node.cells()).collect::<Vec<_>>();
+    print_rows(format, &NodeBrief::HEADER, &rows);
+
+    if OutputFormat::Table == format {
+        for n in nodes.iter() {
+            for (f, lines) in n.err_tails.iter() {
+                println!("\n\x1b[31;1mNode {}, {f}:\x1b[0m", n.node.id);
+                lines.iter().for_each(|l| println!("    {l}"));
+            }
+        }
+    }
+
+    errors.iter().for_each(|e| eprintln!("{e}"));
+
+    Ok(())
+}
+
+/// For `table` and `plain` only,
+/// the JSON form of `show` is the full ENV document
+pub fn print_nodes(format: OutputFormat, nodes: &[NodeSummary]) {
+    let rows = nodes
+        .iter()
+        .map(|n| {
+            let mut r = n.node.cells();
+            r.push(n.validators.to_string());
+            r.push(n.should_be_online.to_string());
+            r.push(
+                n.netem
+                    .as_ref()
+                    .map(|ne| ne.to_string())
+                    .unwrap_or_else(|| "-".to_owned()),
+            );
+            r
+        })
+        .collect::<Vec<_>>();
+    let header = [&NodeBrief::HEADER[..], &["VALIDATORS", "ONLINE", "NETEM"]].concat();
+    print_rows(format, &header, &rows);
+}
+
+pub fn print_history(format: OutputFormat, evs: &[OpEvent]) -> Result<()> {
+    if OutputFormat::Json == format {
+        return print_json(&serde_json::json!({ "events": evs }));
+    }
+
+    let rows = evs
+        .iter()
+        .map(|ev| {
+            vec![
+                datetime!(ev.timestamp),
+                ev.operator.clone(),
+                ev.operation.clone(),
+                ev.nodes
+                    .as_ref()
+                    .map(|ns| {
+                        ns.iter()
+                            .map(|id| id.to_string())
+                            .collect::<Vec<_>>()
+                            .join(",")
+                    })
+                    .unwrap_or_else(|| "-".to_owned()),
+                ev.error
+                    .as_ref()
+                    .map(|e| format!("failed: {e}"))
+                    .unwrap_or_else(|| "ok".to_owned()),
+                ev.args.join(" "),
+            ]
+        })
+        .collect::<Vec<_>>();
+    print_rows(
+        format,
+        &["TIME", "OPERATOR", "OPERATION", "NODES", "RESULT", "ARGS"],
+        &rows,
+    );
+
+    Ok(())
+}
+
+pub fn print_health(format: OutputFormat, nodes: &[NodeHealth]) -> Result<()> {
+    if OutputFormat::Json == format {
+        return print_json(&serde_json::json!({ "nodes": nodes }));
+    }
+
+    let opt = |v: Option<String>| v.unwrap_or_else(|| "-".to_owned());
+    let rows = nodes
+        .iter()
+        .map(|n| {
+            let mut r = n.node.cells();
+            r.push(
+                serde_json::to_value(n.status)
+                    .unwrap()
+                    .as_str()
+                    .unwrap()
+                    .to_owned(),
+            );
+            r.push(n.process_cnt.to_string());
+            r.push(opt(n.el_block_number.map(|h| h.to_string())));
+            r.push(opt(n.cl_head_slot.map(|s| s.to_string())));
+            r.push(opt(n.cl_is_syncing.map(|s| s.to_string())));
+            r
+        })
+        .collect::<Vec<_>>();
+    let header = [
+        &NodeBrief::HEADER[..],
+        &["STATUS", "PROCESSES", "EL_BLOCK", "CL_SLOT", "CL_SYNCING"],
+    ]
+    .concat();
+    print_rows(format, &header, &rows);
+
+    Ok(())
+}
+
+pub fn print_topology(format: OutputFormat, t: &Topology) -> Result<()> {
+    if OutputFormat::Json == format {
+        return print_json(t);
+    }
+
+    let cell = |l: &Layer, m: &BTreeMap<NodeID, usize>, id: &NodeID| {
+        if l.unreachable.contains(id) {
+            "-".to_owned()
+        } else {
+            m.get(id).copied().unwrap_or_default().to_string()
+        }
+    };
+    let rows = t
+        .nodes
+        .iter()
+        .map(|id| {
+            vec![
+                id.to_string(),
+                cell(&t.el, &t.el.degrees, id),
+                cell(&t.cl, &t.cl.degrees, id),
+                cell(&t.el, &t.el.external_peers, id),
+                cell(&t.cl, &t.cl.external_peers, id),
+            ]
+        })
+        .collect::<Vec<_>>();
+    print_rows(
+        format,
+        &["ID", "EL_PEERS", "CL_PEERS", "EL_EXTERNAL", "CL_EXTERNAL"],
+        &rows,
+    );
+
+    if OutputFormat::Table == format {
+        let ids = |s: &BTreeSet<NodeID>| {
+            alt!(
+                s.is_empty(),
+                "-".to_owned(),
+                s.iter()
+                    .map(|i| i.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            )
+        };
+        for (name, l) in [("EL", &t.el), ("CL", &t.cl)] {
+            let components = l
+                .components
+                .iter()
+                .map(|c| format!("[{}]", ids(c)))
+                .collect::<Vec<_>>()
+                .join(" ");
+            println!(
+                "\n{name}: {} edges, degree(min/avg/max) {}/{:.1}/{}, {} component(s): {components}",
+                l.edges.len(),
+                l.degree.min,
+                l.degree.avg,
+                l.degree.max,
+                l.components.len(),
+            );
+            println!(
+                "{name}: isolated: {}, unreachable: {}",
+                ids(&l.isolated),
+                ids(&l.unreachable)
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,306 @@
+//!
+//! P2P identities of the nodes, i.e. the secp256k1 keys
+//! and the enodes(EL) and ENRs(CL) derived from them.
+//!
+//! Keys are derived from a seed and the node ID,
+//! so the identities can be re-created from the ENV meta at any time.
+//!
+
+use alloy::{
+    primitives::keccak256,
+    signers::k256::ecdsa::{signature::hazmat::PrehashSigner, Signature, SigningKey},
+};
+use chaindev::NodeID;
+use ruc::*;
+use std::net::Ipv4Addr;
+
+/// A new random seed of the identities
+pub fn new_seed() -> String {
+    ruc::algo::rand::rand_jwt()
+}
+
+/// The persistent identity of a regular node
+pub struct NodeIdentity {
+    /// The nodekey of geth, or the p2p secret key of reth
+    pub el_key: SigningKey,
+    /// The libp2p key of lighthouse
+    pub cl_key: SigningKey,
+    /// The hex of the JWT secret between the EL and the CL
+    pub jwt: String,
+}
+
+impl NodeIdentity {
+    /// `None` if there is no seed, i.e. the ENV was created by an old `exp`
+    pub fn derive(seed: &str, id: NodeID) -> Option<Self> {
+        if seed.is_empty() {
+            return None;
+        }
+        Some(Self {
+            el_key: derive_key(seed, id, "el"),
+            cl_key: derive_key(seed, id, "cl"),
+            jwt: hex(keccak256(format!("{seed}/{id}/jwt")).as_slice()),
+        })
+    }
+
+    /// Write the keys into the node home before starting the clients,
+    /// `el_key_path` is a hex file, the CL key is written into its network dir
+    pub fn cmd_write_keys(&self, el_key_path: &str, cl_bn_dir: &str) -> String {
+        let network_dir = format!("{cl_bn_dir}/beacon/network");
+        format!(
+            r#"
+mkdir -p $(dirname {el_key_path}) {network_dir} || exit 1
+printf '%s' {} >{el_key_path} || exit 1
+printf '{}' >{network_dir}/key || exit 1 "#,
+            key_hex(&self.el_key),
+            key_printf(&self.cl_key),
+        )
+    }
+}
+
+/// Write the JWT secret between the EL and the CL before starting the clients;
+/// without an identity, i.e. in the ENVs created by old versions,
+/// a random one is written at the first start and kept since then
+pub fn cmd_write_jwt(identity: Option<&NodeIdentity>, path: &str) -> String {
+    match identity {
+        Some(i) => format!("printf '%s' {} >{path} || exit 1", i.jwt),
+        None => format!(
+            "[ -s {path} ] || printf '%s' {} >{path} || exit 1",
+            ruc::algo::rand::rand_jwt()
+        ),
+    }
+}
+
+/// Derive the key of a component of a node, e.g. 'el' or 'cl'
+pub fn derive_key(seed: &str, id: NodeID, component: &str) -> SigningKey {
+    let secret = keccak256(format!("{seed}/{id}/{component}"));
+    // The chance of an invalid scalar is negligible
+    pnk!(SigningKey::from_slice(secret.as_slice()))
+}
+
+/// The hex of the raw secret, e.g. `-nodekeyhex` of geth
+pub fn key_hex(key: &SigningKey) -> String {
+    hex(&key.to_bytes())
+}
+
+/// A `printf` format that prints the raw secret,
+/// e.g. the `network/key` file of lighthouse
+pub fn key_printf(key: &SigningKey) -> String {
+    key.to_bytes()
+        .iter()
+        .map(|b| format!("\\x{b:02x}"))
+        .collect()
+}
+
+/// The enode URL of an EL node, `port` is used for both TCP and UDP
+pub fn enode(key: &SigningKey, ip: &str, port: u16) -> String {
+    format!("enode://{}@{ip}:{port}", enode_pubkey(key))
+}
+
+/// The public key part of an enode URL
+pub fn enode_pubkey(key: &SigningKey) -> String {
+    let pk = key.verifying_key().to_encoded_point(false);
+    // Without the '0x04' prefix
+    hex(&pk.as_bytes()[1..])
+}
+
+/// The public key part of an enode URL, e.g. the ones returned by `admin_peers`
+pub fn parse_enode_pubkey(enode: &str) -> Option<&str> {
+    enode
+        .strip_prefix("enode://")
+        .and_then(|s| s.split('@').next())
+        .filter(|s| !s.is_empty())
+}
+
+/// The libp2p peer ID of a secp256k1 key, e.g. '16Uiu2HAm...'
+pub fn peer_id(key: &SigningKey) -> String {
+    let pk = key.verifying_key().to_encoded_point(true);
+    // The protobuf encoded public key: `KeyType::Secp256k1` and the data
+    let mut pb = vec![0x08, 0x02, 0x12, pk.as_bytes().len() as u8];
+    pb.extend_from_slice(pk.as_bytes());
+    // Wrapped as an identity multihash, since it is not longer than 42 bytes
+    let mut mh = vec![0x00, pb.len() as u8];
+    mh.extend(pb);
+    base58(&mh)
+}
+
+/// The ENR(EIP-778) of a discv5 node, with a sequence number of 1
+pub fn enr(key: &SigningKey, ip: &str, udp_port: u16) -> Result<String> {
+    let ip = ip
+        .parse::<Ipv4Addr>()
+        .c(d!("An IPv4 address is needed by the ENR, got '{}'", ip))?;
+    let pk = key.verifying_key().to_encoded_point(true);
+
+    // The keys must be sorted
+    let content = [
+        rlp_uint(1),
+        rlp_bytes(b"id"),
+        rlp_bytes(b"v4"),
+        rlp_bytes(b"ip"),
+        rlp_bytes(&ip.octets()),
+        rlp_bytes(b"secp256k1"),
+        rlp_bytes(pk.as_bytes()),
+        rlp_bytes(b"udp"),
+        rlp_uint(udp_port as u64),
+    ];
+
+    let hash = keccak256(rlp_list(&content));
+    let sig: Signature = key.sign_prehash(hash.as_slice()).c(d!())?;
+
+    let mut record = vec![rlp_bytes(&sig.to_bytes())];
+    record.extend(content);
+    Ok(format!("enr:{}", base64_url(&rlp_list(&record))))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn rlp_len_prefix(len: usize, short: u8, long: u8) -> Vec<u8> {
+    if len <= 55 {
+        vec![short + len as u8]
+    } else {
+        let len = (len as u64).to_be_bytes();
+        let len = &len[len.iter().take_while(|b| 0 == **b).count()..];
+        let mut ret = vec![long + len.len() as u8];
+        ret.extend_from_slice(len);
+        ret
+    }
+}
+
+fn rlp_bytes(bytes: &[u8]) -> Vec<u8> {
+    if 1 == bytes.len() && bytes[0] < 0x80 {
+        return bytes.to_vec();
+    }
+    let mut ret = rlp_len_prefix(bytes.len(), 0x80, 0xb7);
+    ret.extend_from_slice(bytes);
+    ret
+}
+
+fn rlp_uint(n: u64) -> Vec<u8> {
+    let bytes = n.to_be_bytes();
+    rlp_bytes(&bytes[bytes.iter().take_while(|b| 0 == **b).count()..])
+}
+
+fn rlp_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let payload = items.concat();
+    let mut ret = rlp_len_prefix(payload.len(), 0xc0, 0xf7);
+    ret.extend(payload);
+    ret
+}
+
+// The alphabet of bitcoin
+fn base58(bytes: &[u8]) -> String {
+    const TABLE: &[u8; 58] =
+        b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+    let mut digits: Vec<u8> = vec![];
+    for b in bytes {
+        let mut carry = *b as u32;
+        for d in digits.iter_mut() {
+            carry += (*d as u32) << 8;
+            *d = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while 0 < carry {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let zeros = bytes.iter().take_while(|b| 0 == **b).count();
+    "1".repeat(zeros)
+        + &digits
+            .iter()
+            .rev()
+            .map(|d| TABLE[*d as usize] as char)
+            .collect::<String>()
+}
+
+// The URL-safe alphabet, without paddings
+fn base64_url(bytes: &[u8]) -> String {
+    const TABLE: &[u8; 64] =
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+    let mut ret = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |acc, (i, b)| acc | ((*b as u32) << (16 - 8 * i)));
+        for i in 0..=chunk.len() {
+            ret.push(TABLE[((n >> (18 - 6 * i)) & 0x3f) as usize] as char);
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // The example record of EIP-778
+    #[test]
+    fn enr_and_enode() {
+        let key = pnk!(SigningKey::from_slice(&[
+            0xb7, 0x1c, 0x71, 0xa6, 0x7e, 0x11, 0x77, 0xad, 0x4e, 0x90, 0x16, 0x95,
+            0xe1, 0xb4, 0xb9, 0xee, 0x17, 0xae, 0x16, 0xc6, 0x66, 0x8d, 0x31, 0x3e,
+            0xac, 0x2f, 0x96, 0xdb, 0xcd, 0xa3, 0xf2, 0x91
+        ]));
+        assert_eq!(
+            "enr:-IS4QHCYrYZbAKWCBRlAy5zzaDZXJBGkcnh4MHcBFZntXNFrdvJjX04jRzjzCBOonrkTfj499SZuOh8R33Ls8RRcy5wBgmlkgnY0gmlwhH8AAAGJc2VjcDI1NmsxoQPKY0yuDUmstAHYpMa2_oxVtw0RW_QAdpzBQA8yWM0xOIN1ZHCCdl8",
+            pnk!(enr(&key, "127.0.0.1", 30303))
+        );
+        assert!(enr(&key, "localhost", 30303).is_err());
+
+        let enode = enode(&key, "127.0.0.1", 30303);
+        assert!(enode.starts_with(
+            "enode://ca634cae0d49acb401d8a4c6b6fe8c55b70d115bf400769cc1400f3258cd3138"
+        ));
+        assert!(enode.ends_with("@127.0.0.1:30303"));
+
+        assert_eq!(Some(&enode[8..136]), parse_enode_pubkey(&enode));
+        assert!(parse_enode_pubkey("enode://@1.1.1.1:1").is_none());
+
+        let id = peer_id(&key);
+        assert!(id.starts_with("16Uiu2HA"));
+        assert_eq!(53, id.len());
+        assert_eq!("112", base58(&[0, 0, 1]));
+
+        let k0 = derive_key("seed", 1, "el");
+        assert_eq!(key_hex(&k0), key_hex(&derive_key("seed", 1, "el")));
+        assert_ne!(key_hex(&k0), key_hex(&derive_key("seed", 1, "cl")));
+        assert_ne!(key_hex(&k0), key_hex(&derive_key("seed", 2, "el")));
+        assert_eq!(64, key_hex(&k0).len());
+        assert_eq!(32 * 4, key_printf(&k0).len());
+
+        assert!(NodeIdentity::derive("", 1).is_none());
+        let i0 = pnk!(NodeIdentity::derive("seed", 1));
+        let i1 = pnk!(NodeIdentity::derive("seed", 1));
+        assert_eq!(i0.jwt, i1.jwt);
+        assert_eq!(64, i0.jwt.len());
+        assert_eq!(key_hex(&i0.el_key), key_hex(&k0));
+        assert_ne!(i0.jwt, pnk!(NodeIdentity::derive("seed", 2)).jwt);
+
+        let base = format!("/tmp/exp_p2p_test_{}", std::process::id());
+        let cmd =
+            i0.cmd_write_keys(&format!("{base}/p2p/el.key"), &format!("{base}/cl/bn"));
+        pnk!(cmd::exec_output(&cmd));
+        assert_eq!(
+            key_hex(&i0.el_key),
+            pnk!(std::fs::read_to_string(format!("{base}/p2p/el.key")))
+        );
+        assert_eq!(
+            i0.cl_key.to_bytes().to_vec(),
+            pnk!(std::fs::read(format!("{base}/cl/bn/beacon/network/key")))
+        );
+
+        // Started twice without a seed
+        let jwt_path = format!("{base}/auth.jwt");
+        pnk!(cmd::exec_output(&cmd_write_jwt(None, &jwt_path)));
+        let jwt = pnk!(std::fs::read_to_string(&jwt_path));
+        assert_eq!(64, jwt.len());
+        pnk!(cmd::exec_output(&cmd_write_jwt(None, &jwt_path)));
+        assert_eq!(jwt, pnk!(std::fs::read_to_string(&jwt_path)));
+        pnk!(cmd::exec_output(&cmd_write_jwt(Some(&i0), &jwt_path)));
+        assert_eq!(i0.jwt, pnk!(std::fs::read_to_string(&jwt_path)));
+
+        pnk!(std::fs::remove_dir_all(&base));
+    }
+}
@@ -0,0 +1,413 @@
+//!
+//! Process backends of the node components(EL, CL BN, CL VC),
+//! all functions here generate shell scripts, they run on the target hosts.
+//!
+
+use ruc::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Each node runs three processes: EL, CL beacon node and CL validator client
+pub const COMPONENTS: [&str; 3] = ["el", "cl-bn", "cl-vc"];
+
+/// How long a graceful stop can take before SIGKILL
+pub const DEFAULT_STOP_TIMEOUT_SECS: u64 = 30;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessBackend {
+    /// Detached by `nohup`, found by `ps`
+    #[default]
+    Nohup,
+    /// Managed by per-node systemd user units
+    Systemd(SystemdLimits),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SystemdLimits {
+    /// `RestartSec` of the units, they are restarted on failures
+    pub restart_sec: u32,
+    /// `MemoryMax` of each unit, e.g. '8G'
+    pub memory_max: Option<String>,
+    /// `CPUQuota` of each unit, e.g. '200%'
+    pub cpu_quota: Option<String>,
+}
+
+impl Default for SystemdLimits {
+    fn default() -> Self {
+        Self {
+            restart_sec: 5,
+            memory_max: None,
+            cpu_quota: None,
+        }
+    }
+}
+
+const UNIT_DIR: &str = r#"${XDG_CONFIG_HOME:-$HOME/.config}/systemd/user"#;
+
+/// Unique on a host, node homes are absolute paths
+fn unit_name(home: &str, component: &str) -> String {
+    let home = home
+        .trim_matches('/')
+        .chars()
+        .map(|c| alt!(c.is_ascii_alphanumeric(), c, '_'))
+        .collect::<String>();
+    format!("exp-{home}-{component}.service")
+}
+
+fn unit_names(home: &str) -> String {
+    COMPONENTS
+        .iter()
+        .map(|c| unit_name(home, c))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Run `cmd` in the background,
+/// `cmd` should contain neither `nohup` nor output redirections.
+///
+/// The stdout and stderr are captured into `{home}/{err_file}`,
+/// the file of the last run is kept as `{home}/{err_file}.1`.
+/// With the `nohup` backend, the PID is written to `{home}/{component}.pid`.
+pub fn cmd_for_launch(
+    backend: &ProcessBackend,
+    stop_timeout_secs: u64,
+    home: &str,
+    component: &str,
+    err_file: &str,
+    cmd: &str,
+) -> String {
+    // Remove the dangling line continuations
+    let cmd = cmd.trim().trim_end_matches('\\').trim_end();
+    let err = format!("{home}/{err_file}");
+    let rotate = format!("\nif [ -s {err} ]; then mv -f {err} {err}.1; fi\n");
+
+    let limits = match backend {
+        ProcessBackend::Nohup => {
+            return format!(
+                "{rotate}nohup {cmd} >{err} 2>&1 &\necho $! >{home}/{component}.pid\n"
+            );
+        }
+        ProcessBackend::Systemd(l) => l,
+    };
+
+    let unit = unit_name(home, component);
+    let script = format!("{home}/{component}.sh");
+    let restart_sec = limits.restart_sec;
+    let mut extra = String::new();
+    if let Some(m) = limits.memory_max.as_deref() {
+        extra.push_str(&format!("MemoryMax={m}\n"));
+    }
+    if let Some(q) = limits.cpu_quota.as_deref() {
+        extra.push_str(&format!("CPUQuota={q}\n"));
+    }
+
+    format!(
+        r#"
+cat >{script} <<'EXP_EOF' || exit 1
+#!/usr/bin/env bash
+exec {cmd}
+EXP_EOF
+chmod +x {script} || exit 1
+
+mkdir -p {UNIT_DIR} || exit 1
+cat >{UNIT_DIR}/{unit} <<EXP_EOF || exit 1
+[Unit]
+Description=exp node component, {component} of {home}
+
+[Service]
+Type=simple
+WorkingDirectory={home}
+ExecStart={script}
+StandardOutput=append:{err}
+StandardError=append:{err}
+Restart=on-failure
+RestartSec={restart_sec}
+TimeoutStopSec={stop_timeout_secs}
+LimitNOFILE=102400
+{extra}
+[Install]
+WantedBy=default.target
+EXP_EOF
+systemctl --user daemon-reload || exit 1
+systemctl --user stop {unit} >/dev/null 2>&1
+{rotate}
+systemctl --user enable {unit} >/dev/null 2>&1 || exit 1
+systemctl --user restart {unit} || exit 1
+"#
+    )
+}
+
+/// Stop all processes of a node, and wait until they have exited.
+///
+/// Processes are sent SIGTERM(SIGKILL if `force`) at first,
+/// and SIGKILL if they are still alive after `timeout_secs`;
+/// one result line per process is printed, it will be in the 'mgmt.log'.
+/// The script fails if any process can not be stopped.
+pub fn cmd_for_stop(
+    backend: &ProcessBackend,
+    home: &str,
+    force: bool,
+    timeout_secs: u64,
+) -> String {
+    match backend {
+        ProcessBackend::Nohup => cmd_for_stop_pids(home, force, timeout_secs),
+        ProcessBackend::Systemd(_) => cmd_for_stop_units(home, force),
+    }
+}
+
+fn cmd_for_stop_pids(home: &str, force: bool, timeout_secs: u64) -> String {
+    let sig = alt!(force, "KILL", "TERM");
+    let components = COMPONENTS.join(" ");
+    let grep = cmd_grep_bins(home, &["geth", "reth", "lighthouse"]);
+    format!(
+        r#"
+# Zombies are treated as exited
+alive() {{
+    ps -o stat= -p $1 2>/dev/null | grep -qv '^Z'
+}}
+
+pids=""
+for c in {components}; do
+    pid=$(cat {home}/$c.pid 2>/dev/null)
+    rm -f {home}/$c.pid
+    # The PID may have been reused after a reboot
+    if [ -n "$pid" ] && ps -o args= -p $pid | grep -qF '{home}/'; then
+        pids="$pids $c:$pid"
+    fi
+done
+
+# Processes without PID files, e.g., started by older versions
+for pid in $(ps ax -o pid=,args= | {grep} | awk '{{print $1}}'); do
+    case "$pids " in
+        *":$pid "*) ;;
+        *) pids="$pids orphan:$pid" ;;
+    esac
+done
+
+for i in $pids; do
+    kill -s {sig} ${{i#*:}} 2>/dev/null
+done
+
+deadline=$(( $(date +%s) + {timeout_secs} ))
+failed=0
+for i in $pids; do
+    c=${{i%%:*}}
+    pid=${{i#*:}}
+    while alive $pid && [ $(date +%s) -lt $deadline ]; do
+        sleep 0.2
+    done
+    if alive $pid; then
+        kill -s KILL $pid 2>/dev/null
+        res="forced, alive after {timeout_secs}s, killed by SIGKILL"
+    else
+        res="exited after SIG{sig}"
+    fi
+    n=0
+    while alive $pid && [ $n -lt 50 ]; do
+        sleep 0.1
+        n=$(( n + 1 ))
+    done
+    if alive $pid; then
+        res="failed, still alive after SIGKILL"
+        failed=1
+    fi
+    echo "[stop] $c(pid $pid): $res"
+done
+
+exit $failed
+"#
+    )
+}
+
+fn cmd_for_stop_units(home: &str, force: bool) -> String {
+    let units = unit_names(home);
+    let kill = alt!(
+        force,
+        format!("systemctl --user kill -s SIGKILL {units} >/dev/null 2>&1"),
+        String::new()
+    );
+    let files = COMPONENTS
+        .iter()
+        .map(|c| format!("{UNIT_DIR}/{}", unit_name(home, c)))
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!(
+        r#"
+{kill}
+# Waits for the `TimeoutStopSec`, and then SIGKILL
+systemctl --user stop {units} >/dev/null 2>&1
+for u in {units}; do
+    # 'success', 'timeout'(killed by SIGKILL), 'signal', ...
+    res=$(systemctl --user show -p Result --value $u 2>/dev/null)
+    echo "[stop] $u: ${{res:-unknown}}"
+done
+systemctl --user disable {units} >/dev/null 2>&1
+rm -f {files}
+systemctl --user daemon-reload
+"#
+    )
+}
+
+/// Prints the last `n` lines of each file(relative to `home`),
+/// the output can be parsed by `parse_tails`
+pub fn cmd_tails(home: &str, files: &[&str], n: usize) -> String {
+    format!(
+        "cd {home} 2>/dev/null && tail -v -n {n} {} 2>/dev/null; true",
+        files.join(" ")
+    )
+}
+
+/// <file name> => <last lines>, missing or empty files are omitted
+pub fn parse_tails(output: &str) -> BTreeMap<String, Vec<String>> {
+    let mut ret = BTreeMap::new();
+    let mut cur: Option<(String, Vec<String>)> = None;
+    let mut flush = |cur: Option<(String, Vec<String>)>| {
+        if let Some((f, mut lines)) = cur {
+            while lines.last().is_some_and(|l: &String| l.is_empty()) {
+                lines.pop();
+            }
+            if !lines.is_empty() {
+                ret.insert(f, lines);
+            }
+        }
+    };
+    for l in output.lines() {
+        if let Some(f) = l.strip_prefix("==> ").and_then(|l| l.strip_suffix(" <==")) {
+            flush(cur.replace((f.to_owned(), vec![])));
+        } else if let Some((_, lines)) = cur.as_mut() {
+            lines.push(l.to_owned());
+        }
+    }
+    flush(cur);
+    ret
+}
+
+/// Filters the lines of `ps ax -o pid=,args=` down to the processes
+/// started from `{home}/{bin}_bin`, `bins` are alternatives
+pub fn cmd_grep_bins(home: &str, bins: &[&str]) -> String {
+    format!(
+        "grep -E '^ *[0-9]+ {}/({})_bin '",
+        ere_escape(home),
+        bins.join("|")
+    )
+}
+
+// Node homes contain '+', e.g. '{hostname}+{user}'
+fn ere_escape(s: &str) -> String {
+    s.chars().fold(String::new(), |mut ret, c| {
+        if r"\.[]()*+?{}|^$".contains(c) {
+            ret.push('\\');
+        }
+        ret.push(c);
+        ret
+    })
+}
+
+/// Prints the number of active units of a node
+pub fn cmd_cnt_running(home: &str) -> String {
+    format!(
+        "systemctl --user is-active {} 2>/dev/null | grep -c '^active$' || true",
+        unit_names(home)
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn launch_scripts() {
+        let cmd = "/tmp/n/geth_bin \\\n    --port=1 \\\n    ";
+
+        let nohup =
+            cmd_for_launch(&ProcessBackend::Nohup, 30, "/tmp/n", "el", "el.err", cmd);
+        assert!(
+            nohup.contains("nohup /tmp/n/geth_bin \\\n    --port=1 >/tmp/n/el.err 2>&1")
+        );
+        assert!(nohup.contains("mv -f /tmp/n/el.err /tmp/n/el.err.1"));
+        assert!(nohup.contains("echo $! >/tmp/n/el.pid"));
+
+        let systemd = ProcessBackend::Systemd(SystemdLimits {
+            memory_max: Some("8G".to_owned()),
+            ..Default::default()
+        });
+        let unit = cmd_for_launch(&systemd, 30, "/tmp/n", "el", "el.err", cmd);
+        assert!(unit.contains("exec /tmp/n/geth_bin \\\n    --port=1\nEXP_EOF"));
+        assert!(unit.contains("exp-tmp_n-el.service"));
+        assert!(unit.contains("MemoryMax=8G\n"));
+        assert!(!unit.contains("CPUQuota"));
+        assert!(unit.contains("TimeoutStopSec=30\n"));
+        assert!(unit.contains("StandardError=append:/tmp/n/el.err\n"));
+
+        assert_eq!(3, cmd_cnt_running("/tmp/n").matches(".service").count());
+
+        let home = "/tmp/__CHAIN_DEV__/a.b/host+root/1";
+        let stop = cmd_for_stop(&ProcessBackend::Nohup, home, false, 30);
+        let grep = cmd_grep_bins(home, &["geth", "reth", "lighthouse"]);
+        assert!(stop.contains(&grep));
+        let ps = format!(
+            r#"printf ' 12 {home}/geth_bin --port=1\n 13 {home}x/geth_bin\n 14 /tmp/__CHAIN_DEV__/aXb/hostroot/1/geth_bin\n' | {grep}"#
+        );
+        let out = pnk!(cmd::exec_output(&ps));
+        assert_eq!(
+            vec![format!(" 12 {home}/geth_bin --port=1")],
+            out.lines().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn stop_by_pid_files() {
+        let home = format!("/tmp/exp_process_test_{}", std::process::id());
+        let script = |name: &str, trap: &str| {
+            format!(
+                "printf '%s\\n' '#!/usr/bin/env bash' \"trap {trap} TERM\" 'echo {name} >&2' \\
+                'while true; do sleep 0.1; done' >{home}/{name} && chmod +x {home}/{name}"
+            )
+        };
+        let launch = [
+            format!("rm -rf {home} && mkdir -p {home}"),
+            script("geth_bin", "'exit 0'"),
+            script("lighthouse_bin", "''"), // ignores SIGTERM
+            cmd_for_launch(
+                &ProcessBackend::Nohup,
+                1,
+                &home,
+                "el",
+                "el.err",
+                &format!("{home}/geth_bin"),
+            ),
+            cmd_for_launch(
+                &ProcessBackend::Nohup,
+                1,
+                &home,
+                "cl-bn",
+                "cl.bn.err",
+                &format!("{home}/lighthouse_bin"),
+            ),
+            "sleep 0.5".to_owned(),
+        ]
+        .join("\n");
+        pnk!(ruc::cmd::exec_output(&launch));
+
+        let res = pnk!(ruc::cmd::exec_output(&cmd_for_stop(
+            &ProcessBackend::Nohup,
+            &home,
+            false,
+            1
+        )));
+        assert!(res.contains("el(pid"), "{res}");
+        assert!(res.contains("exited after SIGTERM"), "{res}");
+        assert!(res.contains("forced, alive after 1s"), "{res}");
+        assert!(!res.contains("failed"), "{res}");
+
+        let files = ["el.err", "cl.bn.err", "cl.vc.err"];
+        let tails = pnk!(ruc::cmd::exec_output(&cmd_tails(&home, &files, 20)));
+        let tails = parse_tails(&tails);
+        assert_eq!(2, tails.len(), "{tails:?}");
+        assert_eq!(vec!["geth_bin"], tails["el.err"]);
+        assert_eq!(vec!["lighthouse_bin"], tails["cl.bn.err"]);
+
+        pnk!(ruc::cmd::exec_output(&format!("rm -rf {home}")));
+    }
+}
@@ -0,0 +1,266 @@
+//!
+//! Versions of the persisted ENV meta(the `CONFIG` file of each ENV),
+//! and the migrations between them, so that old ENVs keep working after
+//! an upgrade of `exp`.
+//!
+//! The version is stamped as `meta.custom_data.schema_version`,
+//! a missing one means the ENV was created before versioning(v0).
+//!
+
+use crate::{
+    common::NodeCustomData,
+    lock::{lock_path, EnvLock},
+};
+use chaindev::{beacon_based::common::BASE_DIR, EnvName};
+use ruc::*;
+use serde_json::Value as JsonValue;
+use std::{fs, path::Path};
+
+/// The version of the ENVs created by this `exp`
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Upgrade the meta from the version `to - 1` to `to`
+struct Migration {
+    to: u32,
+    about: &'static str,
+    apply: fn(&mut JsonValue) -> Result<()>,
+}
+
+/// The seed of the node identities(`identity_seed`, see `crate::p2p`) is not migrated:
+/// a new seed would give every node of an old ENV new p2p keys and JWT secrets
+/// at its next start, and break the enodes, ENRs and peer IDs already used by others;
+/// it stays empty in the old ENVs, so their clients keep the keys on disk.
+const MIGRATIONS: &[Migration] = &[Migration {
+    to: 1,
+    about: "fill the missing fields of the node custom data",
+    apply: v1_fill_node_data,
+}];
+
+/// Kinds of ENVs, `(kind, the data dir in ${BASE_DIR})`,
+/// the data dirs are defined by chaindev
+const ENV_KINDS: [(&str, &str); 2] = [("dev", "__DEV__"), ("ddev", "__D_DEV__")];
+
+/// The `CONFIG` file of an ENV, `kind` is 'dev' or 'ddev'
+pub fn cfg_path(kind: &str, en: &EnvName) -> String {
+    let dir = ENV_KINDS
+        .iter()
+        .find(|(k, _)| *k == kind)
+        .map(|(_, dir)| *dir)
+        .unwrap_or(kind);
+    format!("{}/{dir}/envs/{en}/CONFIG", &*BASE_DIR)
+}
+
+pub fn version(cfg: &JsonValue) -> u32 {
+    cfg["meta"]["custom_data"]["schema_version"]
+        .as_u64()
+        .unwrap_or(0) as u32
+}
+
+/// Apply all the pending migrations to the meta in place,
+/// return `(the original version, descriptions of the applied migrations)`
+pub fn migrate(cfg: &mut JsonValue) -> Result<(u32, Vec<&'static str>)> {
+    let ver = version(cfg);
+    if ver > SCHEMA_VERSION {
+        return Err(eg!(
+            "The ENV is of schema v{}, but this `exp` only supports v{} and below, please upgrade `exp`",
+            ver,
+            SCHEMA_VERSION
+        ));
+    }
+
+    let mut applied = vec![];
+    for m in MIGRATIONS.iter().filter(|m| m.to > ver) {
+        (m.apply)(cfg).c(d!("v{} -> v{}", m.to - 1, m.to))?;
+        cfg["meta"]["custom_data"]["schema_version"] = m.to.into();
+        applied.push(m.about);
+    }
+
+    Ok((ver, applied))
+}
+
+/// Migrate the `CONFIG` file of an ENV in place, nothing is written if `check` is set;
+/// the original file is kept as `CONFIG.v<N>.bak`, and the change is committed to the ENV git repo.
+pub fn migrate_cfg_file(path: &str, check: bool) -> Result<(u32, Vec<&'static str>)> {
+    let mut cfg = fs::read(path)
+        .c(d!(path))
+        .and_then(|c| serde_json::from_slice::<JsonValue>(&c).c(d!(path)))?;
+
+    let (ver, applied) = migrate(&mut cfg).c(d!(path))?;
+    if check || applied.is_empty() {
+        return Ok((ver, applied));
+    }
+
+    fs::copy(path, format!("{path}.v{ver}.bak")).c(d!())?;
+    serde_json::to_vec_pretty(&cfg)
+        .c(d!())
+        .and_then(|c| fs::write(path, c).c(d!(path)))?;
+
+    if let Some(home) = Path::new(path).parent() {
+        let cmd = format!(
+            "cd {} && git add CONFIG && git commit -m 'schema migration: v{} -> v{}'",
+            home.display(),
+            ver,
+            SCHEMA_VERSION
+        );
+        info_omit!(
+            cmd::exec_output(&cmd),
+            "Failed to commit the migrated CONFIG"
+        );
+    }
+
+    Ok((ver, applied))
+}
+
+/// Migrate the ENV if needed before any operation on it,
+/// do nothing if the ENV does not exist
+pub fn ensure(kind: &str, en: &EnvName, wait: bool) -> Result<()> {
+    let path = cfg_path(kind, en);
+    if !Path::new(&path).exists() {
+        return Ok(());
+    }
+
+    let (_, pending) = migrate_cfg_file(&path, true).c(d!())?;
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    let _lock = EnvLock::acquire(&lock_path(kind, en), "migrate", wait).c(d!())?;
+    let (ver, applied) = migrate_cfg_file(&path, false).c(d!())?;
+    if !applied.is_empty() {
+        eprintln!("ENV '{en}' is migrated: v{ver} -> v{SCHEMA_VERSION}");
+    }
+
+    Ok(())
+}
+
+/// `exp env migrate`, for all the ENVs of both `exp dev` and `exp ddev`
+pub fn migrate_all(check: bool) -> Result<()> {
+    let mut outdated = 0;
+    let mut failed = 0;
+
+    for (kind, dir) in ENV_KINDS {
+        let envs_dir = format!("{}/{dir}/envs", &*BASE_DIR);
+        let Ok(entries) = fs::read_dir(&envs_dir) else {
+            continue;
+        };
+
+        let mut envs = entries
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().is_dir())
+            .map(|e| EnvName::from(e.file_name().to_string_lossy().as_ref()))
+            .collect::<Vec<_>>();
+        envs.sort();
+
+        for en in envs {
+            let path = cfg_path(kind, &en);
+            let res = if check {
+                migrate_cfg_file(&path, true)
+            } else {
+                EnvLock::acquire(&lock_path(kind, &en), "migrate", false)
+                    .c(d!())
+                    .and_then(|_lock| migrate_cfg_file(&path, false))
+            };
+
+            match res {
+                Ok((ver, applied)) if applied.is_empty() => {
+                    println!("{kind}\t{en}\tv{ver}\tup to date");
+                }
+                Ok((ver, applied)) => {
+                    outdated += 1;
+                    let state = alt!(check, "outdated", "migrated");
+                    println!(
+                        "{kind}\t{en}\tv{ver} -> v{SCHEMA_VERSION}\t{state}: {}",
+                        applied.join("; ")
+                    );
+                }
+                Err(e) => {
+                    failed += 1;
+                    println!("{kind}\t{en}\t-\tfailed: {}", e.get_lowest_msg());
+                }
+            }
+        }
+    }
+
+    if 0 < failed {
+        return Err(eg!("{} ENV(s) can not be migrated", failed));
+    }
+    if check && 0 < outdated {
+        return Err(eg!(
+            "{} ENV(s) need to be migrated, run `exp env migrate`",
+            outdated
+        ));
+    }
+
+    Ok(())
+}
+
+// Before v1, `custom_data` of a node may be null, or lack some fields
+fn v1_fill_node_data(cfg: &mut JsonValue) -> Result<()> {
+    for field in ["fuhrer_nodes", "nodes"] {
+        let Some(nodes) = cfg["meta"][field].as_object_mut() else {
+            continue;
+        };
+        for (id, n) in nodes.iter_mut() {
+            let n = n.as_object_mut().c(d!("node {}", id))?;
+            let data = n.entry("custom_data").or_insert(JsonValue::Null);
+            if data.is_null() {
+                *data = NodeCustomData::default().to_json_value();
+            }
+
+            let obj = data.as_object_mut().c(d!("node {}", id))?;
+            let default = NodeCustomData::default().to_json_value();
+            for (k, v) in default.as_object().c(d!())? {
+                obj.entry(k.as_str()).or_insert_with(|| v.clone());
+            }
+
+            // Make sure it can be used by the current `exp`
+            serde_json::from_value::<NodeCustomData>(data.clone())
+                .c(d!("node {}", id))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn migrations() {
+        let mut cfg = json!({
+            "meta": {
+                "custom_data": { "el_geth_bin": "geth" },
+                "fuhrer_nodes": {
+                    "1": { "id": 1, "custom_data": null }
+                },
+                "nodes": {
+                    "2": { "id": 2, "custom_data": { "el_kind": "Reth" } }
+                }
+            }
+        });
+        assert_eq!(0, version(&cfg));
+
+        let (ver, applied) = pnk!(migrate(&mut cfg));
+        assert_eq!(0, ver);
+        assert_eq!(MIGRATIONS.len(), applied.len());
+        assert_eq!(SCHEMA_VERSION, version(&cfg));
+
+        let fuhrer = &cfg["meta"]["fuhrer_nodes"]["1"]["custom_data"];
+        assert_eq!(json!({}), fuhrer["deposits"]);
+        let node = &cfg["meta"]["nodes"]["2"]["custom_data"];
+        assert_eq!("Reth", node["el_kind"]);
+        assert_eq!(json!([]), node["labels"]);
+        // The keys on disk are kept
+        assert!(cfg["meta"]["custom_data"]["identity_seed"].is_null());
+
+        // Idempotent
+        let (ver, applied) = pnk!(migrate(&mut cfg));
+        assert_eq!(SCHEMA_VERSION, ver);
+        assert!(applied.is_empty());
+
+        // From a newer `exp`
+        cfg["meta"]["custom_data"]["schema_version"] = (SCHEMA_VERSION + 1).into();
+        assert!(migrate(&mut cfg).is_err());
+    }
+}
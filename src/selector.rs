@@ -0,0 +1,396 @@
+//!
+//! The node selector expression language,
+//! accepted by all node-taking operations.
+//!
+//! ```text
+//! expr   := term ('|' term)*
+//! term   := factor ('&' factor)*
+//! factor := '!' factor | '(' expr ')' | atom
+//! atom   := 'all' | 'fuhrer' | 'failed' | IDs, e.g., '3,2,4-9'
+//!         | 'host=' HOST | 'el=' ('geth' | 'reth')
+//!         | 'kind=' ('fuhrer_node' | 'archive_node' | 'fullnode')
+//!         | 'label=' LABEL | 'validators' ('=' | '>' | '>=' | '<' | '<=') NUM
+//! ```
+//!
+
+use crate::common::{parse_nodes, Eth1Kind};
+use chaindev::NodeID;
+use ruc::*;
+use std::{collections::BTreeSet, str::FromStr};
+
+const NODE_KINDS: [&str; 3] = ["fuhrer_node", "archive_node", "fullnode"];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Selector {
+    All,
+    Ids(BTreeSet<NodeID>),
+    Fuhrer,
+    /// Nodes with less than 3 running processes
+    Failed,
+    Host(String),
+    El(Eth1Kind),
+    Kind(String),
+    Label(String),
+    Validators(Cmp, usize),
+    Not(Box<Selector>),
+    And(Box<Selector>, Box<Selector>),
+    Or(Box<Selector>, Box<Selector>),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Cmp {
+    Eq,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+impl Cmp {
+    fn eval(self, l: usize, r: usize) -> bool {
+        match self {
+            Self::Eq => l == r,
+            Self::Gt => l > r,
+            Self::Ge => l >= r,
+            Self::Lt => l < r,
+            Self::Le => l <= r,
+        }
+    }
+}
+
+/// What a selector needs to know about a node
+#[derive(Clone, Debug, Default)]
+pub struct NodeFacts {
+    pub id: NodeID,
+    /// The host ID and all the addresses of the host
+    pub hosts: Vec<String>,
+    pub el: Eth1Kind,
+    pub kind: String,
+    pub fuhrer: bool,
+    pub labels: BTreeSet<String>,
+    pub validators: usize,
+}
+
+impl Selector {
+    /// Plain node IDs can be resolved without loading the ENV
+    pub fn as_ids(&self) -> Option<&BTreeSet<NodeID>> {
+        if let Self::Ids(ids) = self {
+            Some(ids)
+        } else {
+            None
+        }
+    }
+
+    /// Checking the node processes is expensive,
+    /// so it should be done only if `failed` is used
+    pub fn needs_failed(&self) -> bool {
+        match self {
+            Self::Failed => true,
+            Self::Not(s) => s.needs_failed(),
+            Self::And(l, r) | Self::Or(l, r) => l.needs_failed() || r.needs_failed(),
+            _ => false,
+        }
+    }
+
+    /// IDs that are written in the expression outside of any `!`
+    pub fn explicit_ids(&self) -> BTreeSet<NodeID> {
+        match self {
+            Self::Ids(ids) => ids.clone(),
+            Self::And(l, r) | Self::Or(l, r) => {
+                let mut ids = l.explicit_ids();
+                ids.append(&mut r.explicit_ids());
+                ids
+            }
+            _ => BTreeSet::new(),
+        }
+    }
+
+    pub fn matches(&self, n: &NodeFacts, failed: &BTreeSet<NodeID>) -> bool {
+        match self {
+            Self::All => true,
+            Self::Ids(ids) => ids.contains(&n.id),
+            Self::Fuhrer => n.fuhrer,
+            Self::Failed => failed.contains(&n.id),
+            Self::Host(h) => n.hosts.iter().any(|i| i == h),
+            Self::El(k) => n.el == *k,
+            Self::Kind(k) => n.kind == *k,
+            Self::Label(l) => n.labels.contains(l),
+            Self::Validators(cmp, v) => cmp.eval(n.validators, *v),
+            Self::Not(s) => !s.matches(n, failed),
+            Self::And(l, r) => l.matches(n, failed) && r.matches(n, failed),
+            Self::Or(l, r) => l.matches(n, failed) || r.matches(n, failed),
+        }
+    }
+
+    pub fn select<'a>(
+        &self,
+        nodes: impl IntoIterator<Item = &'a NodeFacts>,
+        failed: &BTreeSet<NodeID>,
+    ) -> BTreeSet<NodeID> {
+        nodes
+            .into_iter()
+            .filter(|n| self.matches(n, failed))
+            .map(|n| n.id)
+            .collect()
+    }
+}
+
+impl FromStr for Selector {
+    type Err = Box<dyn RucError>;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let tokens = tokenize(s);
+        if tokens.is_empty() {
+            return Err(eg!("the node selector is empty"));
+        }
+
+        let mut p = Parser { tokens, pos: 0 };
+        let ret = p.expr().c(d!())?;
+        if p.pos < p.tokens.len() {
+            return Err(eg!("unexpected '{}'", p.tokens[p.pos]));
+        }
+
+        Ok(ret)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+    Atom(String),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::And => write!(f, "&"),
+            Self::Or => write!(f, "|"),
+            Self::Not => write!(f, "!"),
+            Self::LParen => write!(f, "("),
+            Self::RParen => write!(f, ")"),
+            Self::Atom(a) => write!(f, "{a}"),
+        }
+    }
+}
+
+fn tokenize(s: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut atom = String::new();
+
+    let flush = |atom: &mut String, tokens: &mut Vec<Token>| {
+        let a = atom.trim();
+        if !a.is_empty() {
+            tokens.push(Token::Atom(a.to_owned()));
+        }
+        atom.clear();
+    };
+
+    for c in s.chars() {
+        let t = match c {
+            '&' => Token::And,
+            '|' => Token::Or,
+            '!' => Token::Not,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            _ => {
+                atom.push(c);
+                continue;
+            }
+        };
+        flush(&mut atom, &mut tokens);
+        tokens.push(t);
+    }
+    flush(&mut atom, &mut tokens);
+
+    tokens
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let t = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        t
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn expr(&mut self) -> Result<Selector> {
+        let mut l = self.term().c(d!())?;
+        while Some(&Token::Or) == self.peek() {
+            self.pos += 1;
+            let r = self.term().c(d!())?;
+            l = Selector::Or(Box::new(l), Box::new(r));
+        }
+        Ok(l)
+    }
+
+    fn term(&mut self) -> Result<Selector> {
+        let mut l = self.factor().c(d!())?;
+        while Some(&Token::And) == self.peek() {
+            self.pos += 1;
+            let r = self.factor().c(d!())?;
+            l = Selector::And(Box::new(l), Box::new(r));
+        }
+        Ok(l)
+    }
+
+    fn factor(&mut self) -> Result<Selector> {
+        match self.next() {
+            Some(Token::Not) => {
+                self.factor().c(d!()).map(|s| Selector::Not(Box::new(s)))
+            }
+            Some(Token::LParen) => {
+                let s = self.expr().c(d!())?;
+                if Some(Token::RParen) == self.next() {
+                    Ok(s)
+                } else {
+                    Err(eg!("unclosed '('"))
+                }
+            }
+            Some(Token::Atom(a)) => parse_atom(&a).c(d!()),
+            Some(t) => Err(eg!("unexpected '{}'", t)),
+            None => Err(eg!("unexpected end of the node selector")),
+        }
+    }
+}
+
+fn parse_atom(a: &str) -> Result<Selector> {
+    match a {
+        "all" => return Ok(Selector::All),
+        "fuhrer" => return Ok(Selector::Fuhrer),
+        "failed" => return Ok(Selector::Failed),
+        _ => {}
+    }
+
+    if let Some(v) = a.strip_prefix("validators") {
+        let v = v.trim_start();
+        let (cmp, n) = if let Some(n) = v.strip_prefix(">=") {
+            (Cmp::Ge, n)
+        } else if let Some(n) = v.strip_prefix("<=") {
+            (Cmp::Le, n)
+        } else if let Some(n) = v.strip_prefix('>') {
+            (Cmp::Gt, n)
+        } else if let Some(n) = v.strip_prefix('<') {
+            (Cmp::Lt, n)
+        } else if let Some(n) = v.strip_prefix('=') {
+            (Cmp::Eq, n)
+        } else {
+            return Err(eg!("invalid comparison: '{}'", a));
+        };
+        let n = n
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| eg!("invalid validator number: '{}'", a))?;
+        return Ok(Selector::Validators(cmp, n));
+    }
+
+    if let Some((k, v)) = a.split_once('=') {
+        let v = v.trim();
+        if v.is_empty() {
+            return Err(eg!("empty value: '{}'", a));
+        }
+        return match k.trim() {
+            "host" => Ok(Selector::Host(v.to_owned())),
+            "el" => match v {
+                "geth" => Ok(Selector::El(Eth1Kind::Geth)),
+                "reth" => Ok(Selector::El(Eth1Kind::Reth)),
+                _ => Err(eg!("unknown EL kind: '{}', expected 'geth' or 'reth'", v)),
+            },
+            "kind" => {
+                if NODE_KINDS.contains(&v) {
+                    Ok(Selector::Kind(v.to_owned()))
+                } else {
+                    Err(eg!(
+                        "unknown node kind: '{}', expected one of {:?}",
+                        v,
+                        NODE_KINDS
+                    ))
+                }
+            }
+            "label" => Ok(Selector::Label(v.to_owned())),
+            k => Err(eg!("unknown key: '{}'", k)),
+        };
+    }
+
+    parse_nodes(a).map(Selector::Ids).map_err(|_| {
+        eg!(
+            "unknown selector: '{}', expected node IDs like '3,2,4-9', or a keyword",
+            a
+        )
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn facts(id: NodeID, el: Eth1Kind, fuhrer: bool, validators: usize) -> NodeFacts {
+        NodeFacts {
+            id,
+            hosts: vec!["10.0.0.2".to_owned()],
+            el,
+            kind: alt!(fuhrer, "fuhrer_node", "archive_node").to_owned(),
+            fuhrer,
+            labels: alt!(id.is_multiple_of(2), set! {B "canary".to_owned()}, set! {B}),
+            validators,
+        }
+    }
+
+    #[test]
+    fn parse_selectors() {
+        assert_eq!(
+            Selector::Ids(set! {B 2, 3, 4, 5}),
+            "3,2,4-5".parse::<Selector>().unwrap()
+        );
+        assert_eq!(
+            Selector::Or(
+                Box::new(Selector::And(
+                    Box::new(Selector::El(Eth1Kind::Reth)),
+                    Box::new(Selector::Not(Box::new(Selector::Ids(set! {B 5}))))
+                )),
+                Box::new(Selector::Validators(Cmp::Ge, 2))
+            ),
+            " el=reth & !5 | validators >= 2 "
+                .parse::<Selector>()
+                .unwrap()
+        );
+        assert!(Selector::from_str("(all").is_err());
+        assert!(Selector::from_str("all &").is_err());
+        assert!(Selector::from_str("el=besu").is_err());
+        assert!(Selector::from_str("3-1").is_err());
+        assert!(Selector::from_str("").is_err());
+    }
+
+    #[test]
+    fn select_nodes() {
+        let nodes = [
+            facts(1, Eth1Kind::Geth, true, 64),
+            facts(2, Eth1Kind::Geth, false, 0),
+            facts(3, Eth1Kind::Reth, false, 2),
+            facts(4, Eth1Kind::Reth, false, 0),
+        ];
+        let failed = set! {B 3};
+        let sel = |s: &str| s.parse::<Selector>().unwrap().select(&nodes, &failed);
+
+        assert_eq!(set! {B 1, 2, 3, 4}, sel("all"));
+        assert_eq!(set! {B 2, 3, 4}, sel("!fuhrer"));
+        assert_eq!(set! {B 3, 4}, sel("el=reth"));
+        assert_eq!(set! {B 4}, sel("el=reth & !3"));
+        assert_eq!(set! {B 1, 3}, sel("validators>0"));
+        assert_eq!(set! {B 2, 3, 4}, sel("label=canary & !fuhrer | failed"));
+        assert_eq!(set! {B 2, 4}, sel("label=canary & !(fuhrer | failed)"));
+        assert_eq!(set! {B 1, 2, 3, 4}, sel("host=10.0.0.2"));
+        assert_eq!(set! {B}, sel("kind=fullnode"));
+    }
+}
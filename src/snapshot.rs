@@ -0,0 +1,312 @@
+//!
+//! The shared parts of `exp dev/ddev snapshot` and `clone`,
+//! full copies of the node data and the meta of an ENV.
+//!
+//! Layout of a snapshot:
+//! - `${ENV_HOME}/snapshots/<name>/SNAPSHOT`, the manifest
+//! - `${ENV_HOME}/snapshots/<name>/CONFIG`, the ENV meta
+//! - `${ENV_HOME}/snapshots/<name>/nodes/N<id>_*`, the tar packages of each node
+//!
+
+use crate::common::{CL_BN_DIR, CL_VC_DIR, EL_DIR};
+use chaindev::NodeID;
+use ruc::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::Path,
+};
+
+pub const SNAPSHOTS_DIR: &str = "snapshots";
+
+/// The data paths of a node, relative to the node home
+pub const SNAPSHOT_PATHS: [&str; 3] = [EL_DIR, CL_BN_DIR, CL_VC_DIR];
+
+/// The p2p identities of the clients, relative to the node home,
+/// they are dropped from the copied data of a clone, so new ones will be generated
+pub const P2P_KEY_PATHS: [&str; 4] = [
+    "el/geth/nodekey",
+    "el/discovery-secret",
+    "cl/bn/beacon/network/key",
+    "cl/bn/beacon/network/enr.dat",
+];
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub name: String,
+    pub created_at: u64,
+    /// The nodes that were online, they are started again after restoring
+    pub online: BTreeSet<NodeID>,
+    /// <node ID, location>, the location is the node home in `dev`,
+    /// and `<host address>:<node home>` in `ddev`
+    pub nodes: BTreeMap<NodeID, String>,
+}
+
+impl Manifest {
+    pub fn load(env_home: &str, name: &str) -> Result<Self> {
+        let p = format!("{}/SNAPSHOT", snapshot_dir(env_home, name));
+        fs::read(&p)
+            .c(d!("snapshot '{}' not found", name))
+            .and_then(|m| serde_json::from_slice(&m).c(d!(p)))
+    }
+
+    pub fn save(&self, env_home: &str) -> Result<()> {
+        let p = format!("{}/SNAPSHOT", snapshot_dir(env_home, &self.name));
+        serde_json::to_vec_pretty(self)
+            .c(d!())
+            .and_then(|m| fs::write(&p, m).c(d!(p)))
+    }
+
+    /// A snapshot can only be restored to the same nodes
+    pub fn check_nodes(&self, nodes: &BTreeMap<NodeID, String>) -> Result<()> {
+        if &self.nodes == nodes {
+            return Ok(());
+        }
+        let diff = self
+            .nodes
+            .keys()
+            .chain(nodes.keys())
+            .filter(|id| self.nodes.get(id) != nodes.get(id))
+            .collect::<BTreeSet<_>>();
+        Err(eg!(
+            "The nodes have been changed since the snapshot: {:?}, push or kick them to match it at first",
+            diff
+        ))
+    }
+}
+
+pub fn snapshot_dir(env_home: &str, name: &str) -> String {
+    format!("{env_home}/{SNAPSHOTS_DIR}/{name}")
+}
+
+pub fn nodes_dir(env_home: &str, name: &str) -> String {
+    format!("{}/nodes", snapshot_dir(env_home, name))
+}
+
+/// Create the dirs of a new snapshot
+pub fn init(env_home: &str, name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-.".contains(c));
+    if !valid {
+        return Err(eg!(
+            "Invalid snapshot name: '{}', only ASCII letters, digits and '_-.' are allowed",
+            name
+        ));
+    }
+    let dir = snapshot_dir(env_home, name);
+    if Path::new(&dir).exists() {
+        return Err(eg!("Snapshot '{}' already exists", name));
+    }
+    fs::create_dir_all(nodes_dir(env_home, name)).c(d!())
+}
+
+/// All snapshots of the ENV, sorted by the creation time
+pub fn list(env_home: &str) -> Result<Vec<Manifest>> {
+    let dir = format!("{env_home}/{SNAPSHOTS_DIR}");
+    if !Path::new(&dir).exists() {
+        return Ok(vec![]);
+    }
+    let mut ret = vec![];
+    for entry in fs::read_dir(&dir).c(d!())? {
+        let name = entry.c(d!())?.file_name().to_string_lossy().into_owned();
+        match Manifest::load(env_home, &name) {
+            Ok(m) => ret.push(m),
+            // e.g., an interrupted `snapshot create`
+            Err(e) => eprintln!("Snapshot '{name}': {}", e.get_lowest_msg()),
+        }
+    }
+    ret.sort_by_key(|m| m.created_at);
+    Ok(ret)
+}
+
+pub fn remove(env_home: &str, name: &str) -> Result<()> {
+    Manifest::load(env_home, name)
+        .c(d!())
+        .and_then(|_| fs::remove_dir_all(snapshot_dir(env_home, name)).c(d!()))
+}
+
+/// The tar packages of a node in the snapshot
+pub fn node_packages(env_home: &str, name: &str, id: NodeID) -> Result<Vec<String>> {
+    let dir = nodes_dir(env_home, name);
+    let prefix = format!("N{id}_");
+    let mut ret = fs::read_dir(&dir)
+        .c(d!(dir))?
+        .filter_map(|e| e.ok())
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .filter(|f| f.starts_with(&prefix))
+        .map(|f| format!("{dir}/{f}"))
+        .collect::<Vec<_>>();
+    if ret.is_empty() {
+        return Err(eg!("No data of node {} in the snapshot", id));
+    }
+    ret.sort();
+    Ok(ret)
+}
+
+/// Pack the data of a node, with the paths relative to '/',
+/// in the same format as `collect_tgz_from_nodes` of chaindev
+pub fn cmd_pack(node_home: &str, tgz: &str) -> String {
+    let home = node_home.trim_start_matches('/');
+    let paths = SNAPSHOT_PATHS
+        .iter()
+        .map(|p| format!("{home}/{p}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!("tar -C / -zcf {tgz} {paths}")
+}
+
+/// Replace the data of a node with the packages
+pub fn cmd_unpack(node_home: &str, tgzs: &[String]) -> String {
+    let paths = SNAPSHOT_PATHS
+        .iter()
+        .map(|p| format!("{node_home}/{p}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let mut cmd = format!("rm -rf {paths} || exit 1;");
+    for tgz in tgzs {
+        cmd.push_str(&format!(" tar -C / -zxf {tgz} || exit 1;"));
+    }
+    cmd
+}
+
+fn cmd_drop_p2p_keys(node_home: &str) -> String {
+    let keys = P2P_KEY_PATHS
+        .iter()
+        .map(|p| format!("{node_home}/{p}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!("rm -f {keys} || exit 1;")
+}
+
+/// Copy the data of a node to another node on the same host, for a clone
+pub fn cmd_copy_as_clone(src_home: &str, dst_home: &str) -> String {
+    let mut cmd = String::new();
+    for p in SNAPSHOT_PATHS {
+        let dst = format!("{dst_home}/{p}");
+        let parent = Path::new(&dst)
+            .parent()
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| dst_home.to_owned());
+        cmd.push_str(&format!(
+            " rm -rf {dst} && mkdir -p {parent} && cp -a {src_home}/{p} {dst} || exit 1;"
+        ));
+    }
+    cmd.push(' ');
+    cmd.push_str(&cmd_drop_p2p_keys(dst_home));
+    cmd
+}
+
+/// Pack the data of a node, with the paths relative to the node home,
+/// so it can be unpacked into another node home
+pub fn cmd_pack_relative(node_home: &str, tgz: &str) -> String {
+    format!("tar -C {node_home} -zcf {tgz} {}", SNAPSHOT_PATHS.join(" "))
+}
+
+/// The counterpart of `cmd_pack_relative`, for a clone
+pub fn cmd_unpack_as_clone(node_home: &str, tgz: &str) -> String {
+    let paths = SNAPSHOT_PATHS
+        .iter()
+        .map(|p| format!("{node_home}/{p}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!(
+        "rm -rf {paths} || exit 1; tar -C {node_home} -zxf {tgz} || exit 1; rm -f {tgz}; {}",
+        cmd_drop_p2p_keys(node_home)
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn pack_and_unpack() {
+        let base = format!("/tmp/exp_snapshot_test_{}", std::process::id());
+        let node_home = format!("{base}/node");
+        let env_home = format!("{base}/env");
+        for p in SNAPSHOT_PATHS {
+            pnk!(fs::create_dir_all(format!("{node_home}/{p}")));
+            pnk!(fs::write(format!("{node_home}/{p}/data"), p));
+        }
+
+        pnk!(init(&env_home, "s1"));
+        assert!(init(&env_home, "s1").is_err());
+        assert!(init(&env_home, "../s2").is_err());
+
+        let tgz = format!("{}/N1_fullnode.tgz", nodes_dir(&env_home, "s1"));
+        pnk!(ruc::cmd::exec_output(&cmd_pack(&node_home, &tgz)));
+        Manifest {
+            name: "s1".to_owned(),
+            created_at: ts!(),
+            online: [1].into(),
+            nodes: [(1, node_home.clone())].into(),
+        }
+        .save(&env_home)
+        .unwrap();
+
+        // Changed after the snapshot
+        pnk!(fs::write(format!("{node_home}/{EL_DIR}/data"), "changed"));
+        pnk!(fs::write(format!("{node_home}/{CL_VC_DIR}/new"), "new"));
+
+        let m = pnk!(Manifest::load(&env_home, "s1"));
+        pnk!(m.check_nodes(&[(1, node_home.clone())].into()));
+        assert!(m.check_nodes(&[(2, node_home.clone())].into()).is_err());
+
+        let tgzs = pnk!(node_packages(&env_home, "s1", 1));
+        assert!(node_packages(&env_home, "s1", 2).is_err());
+        pnk!(ruc::cmd::exec_output(&cmd_unpack(&node_home, &tgzs)));
+
+        for p in SNAPSHOT_PATHS {
+            let data = pnk!(fs::read_to_string(format!("{node_home}/{p}/data")));
+            assert_eq!(p, data);
+        }
+        assert!(!Path::new(&format!("{node_home}/{CL_VC_DIR}/new")).exists());
+
+        assert_eq!(1, pnk!(list(&env_home)).len());
+        pnk!(remove(&env_home, "s1"));
+        assert!(pnk!(list(&env_home)).is_empty());
+
+        pnk!(fs::remove_dir_all(&base));
+    }
+
+    #[test]
+    fn copy_as_clone() {
+        let base = format!("/tmp/exp_clone_test_{}", std::process::id());
+        let src = format!("{base}/src");
+        for p in SNAPSHOT_PATHS {
+            pnk!(fs::create_dir_all(format!("{src}/{p}")));
+            pnk!(fs::write(format!("{src}/{p}/data"), p));
+        }
+        pnk!(fs::create_dir_all(format!("{src}/el/geth")));
+        pnk!(fs::write(format!("{src}/el/geth/nodekey"), "key"));
+
+        let check = |dst: &str| {
+            for p in SNAPSHOT_PATHS {
+                let data = pnk!(fs::read_to_string(format!("{dst}/{p}/data")));
+                assert_eq!(p, data);
+            }
+            assert!(!Path::new(&format!("{dst}/el/geth/nodekey")).exists());
+        };
+
+        let dst = format!("{base}/dst0");
+        pnk!(fs::create_dir_all(format!("{dst}/el")));
+        pnk!(fs::write(format!("{dst}/el/old"), "old"));
+        pnk!(ruc::cmd::exec_output(&cmd_copy_as_clone(&src, &dst)));
+        check(&dst);
+        assert!(!Path::new(&format!("{dst}/el/old")).exists());
+
+        let dst = format!("{base}/dst1");
+        let tgz = format!("{base}/data.tgz");
+        pnk!(fs::create_dir_all(&dst));
+        pnk!(ruc::cmd::exec_output(&cmd_pack_relative(&src, &tgz)));
+        pnk!(ruc::cmd::exec_output(&cmd_unpack_as_clone(&dst, &tgz)));
+        check(&dst);
+        assert!(!Path::new(&tgz).exists());
+
+        pnk!(fs::remove_dir_all(&base));
+    }
+}
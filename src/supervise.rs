@@ -0,0 +1,117 @@
+//!
+//! The shared parts of `exp dev/ddev supervise`,
+//! which restarts the crashed nodes that should be online.
+//!
+
+use chaindev::NodeID;
+use ruc::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Exponential backoff of the restarts, per node
+pub struct Backoff {
+    base_secs: u64,
+    max_secs: u64,
+    // <node ID, (restart attempts, the earliest timestamp of the next attempt)>
+    states: BTreeMap<NodeID, (u32, u64)>,
+}
+
+impl Backoff {
+    pub fn new(base_secs: u64, max_secs: u64) -> Self {
+        Self {
+            base_secs: base_secs.max(1),
+            max_secs: max_secs.max(base_secs),
+            states: BTreeMap::new(),
+        }
+    }
+
+    /// The node is found healthy, forget its failures
+    pub fn reset(&mut self, id: NodeID) {
+        self.states.remove(&id);
+    }
+
+    /// Return the attempt number(starts from 1) if a restart is allowed at `now`,
+    /// the next attempt will be delayed by `base * 2^(attempt - 1)` seconds at most.
+    pub fn try_attempt(&mut self, id: NodeID, now: u64) -> Option<u32> {
+        let (attempts, next_ts) = self.states.entry(id).or_insert((0, 0));
+        if now < *next_ts {
+            return None;
+        }
+        *attempts += 1;
+        let delay = self
+            .base_secs
+            .saturating_mul(1u64 << (*attempts - 1).min(32))
+            .min(self.max_secs);
+        *next_ts = now + delay;
+        Some(*attempts)
+    }
+}
+
+/// The payload of webhook notifications
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RestartEvent {
+    pub env: String,
+    pub node: NodeID,
+    pub host: String,
+    /// Alive processes before the restart
+    pub process_cnt: u64,
+    pub attempt: u32,
+    pub timestamp: u64,
+    /// `None` if restarted successfully
+    pub error: Option<String>,
+}
+
+impl RestartEvent {
+    /// One line for the 'mgmt.log' of the node
+    pub fn log_line(&self) -> String {
+        format!(
+            "\n\n[ {} ]\n[supervise] {}/3 processes alive, restart attempt {}: {}\n",
+            datetime!(self.timestamp),
+            self.process_cnt,
+            self.attempt,
+            self.error.as_deref().unwrap_or("ok")
+        )
+    }
+
+    pub fn notify(&self, webhook: &str) -> Result<()> {
+        let body = serde_json::to_vec(self).c(d!())?;
+        ruc::http::post(
+            webhook,
+            &body,
+            Some(&[("Content-Type", "application/json")]),
+        )
+        .c(d!())
+        .and_then(|(code, _)| {
+            if code.is_success() {
+                Ok(())
+            } else {
+                Err(eg!("Webhook responded with code {}", code))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn backoff() {
+        let mut b = Backoff::new(10, 35);
+
+        assert_eq!(Some(1), b.try_attempt(1, 100));
+        assert_eq!(None, b.try_attempt(1, 109));
+        assert_eq!(Some(2), b.try_attempt(1, 110));
+        assert_eq!(None, b.try_attempt(1, 129));
+        assert_eq!(Some(3), b.try_attempt(1, 130));
+        // Capped by the max
+        assert_eq!(None, b.try_attempt(1, 164));
+        assert_eq!(Some(4), b.try_attempt(1, 165));
+
+        // Independent between nodes
+        assert_eq!(Some(1), b.try_attempt(2, 165));
+
+        b.reset(1);
+        assert_eq!(Some(1), b.try_attempt(1, 166));
+    }
+}
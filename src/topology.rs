@@ -0,0 +1,288 @@
+//!
+//! `exp dev/ddev topology`, the peer graphs of the EL and the CL,
+//! for diagnosing partitions, e.g. a group of nodes that only peer among themselves.
+//!
+//! The peers reported by each node are mapped back to the nodes of the ENV
+//! by their identities, i.e. the public keys in the enodes(EL) and
+//! the libp2p peer IDs(CL); both the live ones and the derived ones are used,
+//! so the unreachable nodes can still be recognized as the peers of others.
+//!
+
+use crate::{
+    common::{cl_get_peers, el_get_peers},
+    p2p::{self, NodeIdentity},
+};
+use chaindev::{EnvName, NodeID};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// What is collected from a node in one layer
+#[derive(Clone, Debug, Default)]
+pub struct Probe {
+    /// The identities of the node
+    pub ids: BTreeSet<String>,
+    /// The identities of its peers, `None` if its RPC is unreachable
+    pub peers: Option<Vec<String>>,
+}
+
+/// Probe a node in both layers, `(EL, CL)`
+pub fn probe(
+    el_rpc: &str,
+    cl_rpc: &str,
+    identity: Option<&NodeIdentity>,
+) -> (Probe, Probe) {
+    let mut el = Probe::default();
+    let mut cl = Probe::default();
+
+    if let Some(i) = identity {
+        el.ids.insert(p2p::enode_pubkey(&i.el_key));
+        cl.ids.insert(p2p::peer_id(&i.cl_key));
+    }
+
+    if let Ok((enode, peers)) = el_get_peers(el_rpc) {
+        el.ids
+            .extend(p2p::parse_enode_pubkey(&enode).map(|s| s.to_owned()));
+        el.peers = Some(
+            peers
+                .iter()
+                .filter_map(|p| p2p::parse_enode_pubkey(p))
+                .map(|s| s.to_owned())
+                .collect(),
+        );
+    }
+
+    if let Ok((peer_id, peers)) = cl_get_peers(cl_rpc) {
+        cl.ids.insert(peer_id);
+        cl.peers = Some(peers);
+    }
+
+    (el, cl)
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct DegreeStats {
+    pub min: usize,
+    pub max: usize,
+    pub avg: f64,
+}
+
+/// The peer graph of one layer
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Layer {
+    /// Undirected, `(smaller ID, bigger ID)`,
+    /// an edge reported by either side is counted
+    pub edges: BTreeSet<(NodeID, NodeID)>,
+    /// <node ID, peers within the ENV>
+    pub degrees: BTreeMap<NodeID, usize>,
+    /// <node ID, peers out of the ENV>, e.g. the ones of an external network
+    pub external_peers: BTreeMap<NodeID, usize>,
+    /// Nodes whose RPC can not be reached
+    pub unreachable: BTreeSet<NodeID>,
+    /// Reachable nodes without any peer within the ENV
+    pub isolated: BTreeSet<NodeID>,
+    /// Connected components, the biggest first,
+    /// more than one of them means the network is partitioned
+    pub components: Vec<BTreeSet<NodeID>>,
+    /// Of the reachable nodes
+    pub degree: DegreeStats,
+}
+
+impl Layer {
+    pub fn build(probes: &BTreeMap<NodeID, Probe>) -> Self {
+        let owners = probes
+            .iter()
+            .flat_map(|(id, p)| p.ids.iter().map(move |i| (i.as_str(), *id)))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut layer = Self::default();
+        for (id, p) in probes.iter() {
+            let Some(peers) = p.peers.as_ref() else {
+                layer.unreachable.insert(*id);
+                continue;
+            };
+            let mut external = 0;
+            for peer in peers {
+                match owners.get(peer.as_str()) {
+                    Some(other) if other != id => {
+                        layer.edges.insert((*id.min(other), *id.max(other)));
+                    }
+                    Some(_) => {}
+                    None => external += 1,
+                }
+            }
+            layer.external_peers.insert(*id, external);
+        }
+
+        layer.degrees = probes.keys().map(|id| (*id, 0)).collect();
+        for (a, b) in layer.edges.iter() {
+            *layer.degrees.entry(*a).or_default() += 1;
+            *layer.degrees.entry(*b).or_default() += 1;
+        }
+
+        let reachable = layer
+            .degrees
+            .iter()
+            .filter(|(id, _)| !layer.unreachable.contains(id))
+            .map(|(_, d)| *d)
+            .collect::<Vec<_>>();
+        if !reachable.is_empty() {
+            layer.degree = DegreeStats {
+                min: reachable.iter().copied().min().unwrap_or_default(),
+                max: reachable.iter().copied().max().unwrap_or_default(),
+                avg: reachable.iter().sum::<usize>() as f64 / reachable.len() as f64,
+            };
+        }
+
+        layer.isolated = layer
+            .degrees
+            .iter()
+            .filter(|(id, d)| 0 == **d && !layer.unreachable.contains(id))
+            .map(|(id, _)| *id)
+            .collect();
+
+        layer.components = components(probes.keys().copied(), &layer.edges);
+        layer
+    }
+}
+
+fn components(
+    nodes: impl Iterator<Item = NodeID>,
+    edges: &BTreeSet<(NodeID, NodeID)>,
+) -> Vec<BTreeSet<NodeID>> {
+    let mut adj = nodes
+        .map(|id| (id, BTreeSet::new()))
+        .collect::<BTreeMap<_, _>>();
+    for (a, b) in edges.iter() {
+        adj.entry(*a).or_default().insert(*b);
+        adj.entry(*b).or_default().insert(*a);
+    }
+
+    let mut seen = BTreeSet::new();
+    let mut ret = vec![];
+    for id in adj.keys() {
+        if !seen.insert(*id) {
+            continue;
+        }
+        let mut comp = BTreeSet::from([*id]);
+        let mut stack = vec![*id];
+        while let Some(i) = stack.pop() {
+            for j in adj[&i].iter() {
+                if seen.insert(*j) {
+                    comp.insert(*j);
+                    stack.push(*j);
+                }
+            }
+        }
+        ret.push(comp);
+    }
+
+    // Stable for the same sizes, i.e. ordered by the smallest IDs
+    ret.sort_by_key(|c| std::cmp::Reverse(c.len()));
+    ret
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Topology {
+    pub env_name: EnvName,
+    /// The probed nodes, bootnodes are not included
+    pub nodes: BTreeSet<NodeID>,
+    pub el: Layer,
+    pub cl: Layer,
+}
+
+impl Topology {
+    /// `probes`: <node ID, (EL, CL)>
+    pub fn build(env_name: &EnvName, probes: BTreeMap<NodeID, (Probe, Probe)>) -> Self {
+        let (el, cl): (BTreeMap<_, _>, BTreeMap<_, _>) = probes
+            .into_iter()
+            .map(|(id, (el, cl))| ((id, el), (id, cl)))
+            .unzip();
+        Self {
+            env_name: env_name.clone(),
+            nodes: el.keys().copied().collect(),
+            el: Layer::build(&el),
+            cl: Layer::build(&cl),
+        }
+    }
+
+    /// A Graphviz graph, EL edges are solid, CL edges are dashed,
+    /// and the unreachable nodes are gray
+    pub fn to_dot(&self) -> String {
+        let mut dot = format!("graph \"{}\" {{\n", self.env_name);
+        dot.push_str("    node [shape=circle];\n");
+        for id in self.nodes.iter() {
+            let unreachable =
+                self.el.unreachable.contains(id) || self.cl.unreachable.contains(id);
+            if unreachable {
+                dot.push_str(&format!("    {id} [style=filled, fillcolor=gray];\n"));
+            } else {
+                dot.push_str(&format!("    {id};\n"));
+            }
+        }
+        for (a, b) in self.el.edges.iter() {
+            dot.push_str(&format!("    {a} -- {b} [color=blue];\n"));
+        }
+        for (a, b) in self.cl.edges.iter() {
+            dot.push_str(&format!("    {a} -- {b} [color=red, style=dashed];\n"));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn probe(ids: &[&str], peers: Option<&[&str]>) -> Probe {
+        Probe {
+            ids: ids.iter().map(|i| i.to_string()).collect(),
+            peers: peers.map(|p| p.iter().map(|i| i.to_string()).collect()),
+        }
+    }
+
+    #[test]
+    fn partitions() {
+        // {1, 2, 3} and {4, 5}, 6 is isolated, 7 is unreachable but peered by 5
+        let probes = BTreeMap::from([
+            (1, probe(&["a"], Some(&["b", "c", "ext"]))),
+            (2, probe(&["b"], Some(&["a"]))),
+            (3, probe(&["c"], Some(&["a", "b"]))),
+            (4, probe(&["d"], Some(&["e"]))),
+            (5, probe(&["e"], Some(&["d", "g"]))),
+            (6, probe(&["f"], Some(&["f"]))),
+            (7, probe(&["g"], None)),
+        ]);
+        let l = Layer::build(&probes);
+
+        assert_eq!(5, l.edges.len());
+        assert!(l.edges.contains(&(2, 3)));
+        assert_eq!(BTreeSet::from([7]), l.unreachable);
+        assert_eq!(BTreeSet::from([6]), l.isolated);
+        assert_eq!(1, l.external_peers[&1]);
+        assert_eq!(
+            vec![
+                BTreeSet::from([1, 2, 3]),
+                BTreeSet::from([4, 5, 7]),
+                BTreeSet::from([6])
+            ],
+            l.components
+        );
+        assert_eq!(0, l.degree.min);
+        assert_eq!(2, l.degree.max);
+        assert_eq!(1, l.degrees[&7]);
+
+        let t = Topology::build(
+            &EnvName::from("MyEnv"),
+            probes
+                .into_iter()
+                .map(|(id, p)| (id, (p.clone(), p)))
+                .collect(),
+        );
+        let dot = t.to_dot();
+        assert!(dot.starts_with("graph \"MyEnv\" {"));
+        assert!(dot.contains("    1 -- 2 [color=blue];"));
+        assert!(dot.contains("    1 -- 2 [color=red, style=dashed];"));
+        assert!(dot.contains("    7 [style=filled, fillcolor=gray];"));
+    }
+}